@@ -0,0 +1,12 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate toml_query;
+
+use toml_query::fuzzing::{FuzzDocument, Operation, run_all};
+
+// Run sequences of read/set/insert/delete operations with almost-valid queries against generated
+// documents.
+fuzz_target!(|input: (FuzzDocument, Vec<Operation>)| {
+    let (FuzzDocument(mut doc), ops) = input;
+    run_all(&mut doc, &ops);
+});
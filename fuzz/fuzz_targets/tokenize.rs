@@ -0,0 +1,17 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate toml;
+extern crate toml_query;
+
+use std::collections::BTreeMap;
+
+use toml::Value;
+use toml_query::read::TomlValueReadExt;
+
+// Feed arbitrary strings to the tokenizer. Reading from an empty document means the resolver is
+// only reached for the first token.
+fuzz_target!(|query: &str| {
+    let doc = Value::Table(BTreeMap::new());
+    let _ = doc.read(query);
+    let _ = doc.read_with_seperator(query, '/');
+});
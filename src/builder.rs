@@ -0,0 +1,152 @@
+/// Building TOML documents from queries
+///
+/// `DocBuilder` assembles a new document with `TomlValueInsertExt::insert()`, so intermediate
+/// tables are created as needed. Values which are already there are replaced with
+/// `TomlValueSetExt::set()`.
+
+use std::collections::BTreeMap;
+
+use toml::Value;
+
+use read::TomlValueReadExt;
+use set::TomlValueSetExt;
+use insert::TomlValueInsertExt;
+use error::{Error, Result};
+
+/// A builder for TOML documents
+///
+/// The first failing operation is reported by `DocBuilder::build()`, later operations are
+/// ignored.
+///
+/// ```rust
+/// # extern crate toml;
+/// # extern crate toml_query;
+/// use toml_query::builder::DocBuilder;
+/// use toml_query::read::TomlValueReadExt;
+///
+/// # fn main() {
+/// let doc = DocBuilder::new()
+///     .set("package.name", "toml-query")
+///     .push("package.keywords", "toml")
+///     .push("package.keywords", "query")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(doc.read("package.keywords.[1]").unwrap(), Some(&toml::Value::from("query")));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DocBuilder {
+    value: Value,
+    error: Option<Error>,
+}
+
+impl DocBuilder {
+
+    /// A builder for an empty document
+    pub fn new() -> DocBuilder {
+        DocBuilder::from(Value::Table(BTreeMap::new()))
+    }
+
+    /// Put `value` at `query`, replacing the value which is there
+    ///
+    /// Array elements are replaced, not shifted. If there is no value at `query`, it is inserted.
+    pub fn set<V: Into<Value>>(self, query: &str, value: V) -> DocBuilder {
+        self.apply(|doc| {
+            if doc.read(query)?.is_some() {
+                doc.set(query, value.into()).map(|_| ())
+            } else {
+                doc.insert(query, value.into()).map(|_| ())
+            }
+        })
+    }
+
+    /// Append `value` to the array at `query`, creating the array if there is none
+    pub fn push<V: Into<Value>>(self, query: &str, value: V) -> DocBuilder {
+        self.apply(|doc| {
+            if doc.read(query)?.is_none() {
+                let _ = doc.insert(query, Value::Array(Vec::new()))?;
+            }
+
+            match doc.read_mut(query)? {
+                Some(&mut Value::Array(ref mut a)) => {
+                    a.push(value.into());
+                    Ok(())
+                },
+                Some(other) => Err(Error::TypeErrorAt(String::from(query), "Array", ::util::name_of_val(other))),
+                None        => Err(Error::NotAvailable(String::from(query))),
+            }
+        })
+    }
+
+    /// Get the document, or the error of the first operation which failed
+    pub fn build(self) -> Result<Value> {
+        match self.error {
+            Some(e) => Err(e),
+            None    => Ok(self.value),
+        }
+    }
+
+    fn apply<F>(mut self, f: F) -> DocBuilder
+        where F: FnOnce(&mut Value) -> Result<()>
+    {
+        if self.error.is_none() {
+            self.error = f(&mut self.value).err();
+        }
+        self
+    }
+
+}
+
+impl Default for DocBuilder {
+    fn default() -> DocBuilder {
+        DocBuilder::new()
+    }
+}
+
+/// A builder which starts with `value`
+impl From<Value> for DocBuilder {
+    fn from(value: Value) -> DocBuilder {
+        DocBuilder { value, error: None }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_build() {
+        let doc = DocBuilder::new()
+            .set("a.b", 1)
+            .set("a.c", "c")
+            .push("list", "x")
+            .push("list", "y")
+            .set("list.[0]", "w")
+            .set("a.b", 2)
+            .build()
+            .unwrap();
+
+        let expected: Value = toml_from_str(r#"
+        list = [ "w", "y" ]
+
+        [a]
+        b = 2
+        c = "c"
+        "#).unwrap();
+        assert_eq!(doc, expected);
+    }
+
+    #[test]
+    fn test_first_error() {
+        let res = DocBuilder::new()
+            .set("a", 1)
+            .push("a", 2)
+            .set("b.[0]", 3)
+            .build();
+
+        assert!(is_match!(res, Err(Error::TypeErrorAt(_, "Array", "Integer"))));
+    }
+
+}
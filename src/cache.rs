@@ -0,0 +1,254 @@
+/// Read-through caching of query results
+///
+/// `CachedDocument` wraps a `toml::Value` and remembers the result of every query read through
+/// `CachedDocument::get()`. Modifications through the wrapper drop the cached results they could
+/// have changed, so reads never see stale values. Meant for read-mostly servers which evaluate
+/// the same queries over and over.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use toml::Value;
+
+use read::{TomlValueReadAllExt, TomlValueReadExt};
+use set::TomlValueSetExt;
+use insert::TomlValueInsertExt;
+use delete::TomlValueDeleteExt;
+use path::{Segment, TomlPath};
+use error::Result;
+
+/// A TOML document which caches the results of queries
+///
+/// The cache is shared between threads, `get()` only needs a shared reference. Reads through
+/// `TomlValueReadExt` return references into the value and bypass the cache.
+#[derive(Debug)]
+pub struct CachedDocument {
+    value: Value,
+    cache: Mutex<HashMap<TomlPath, Option<Value>>>,
+}
+
+impl CachedDocument {
+
+    pub fn new(value: Value) -> CachedDocument {
+        CachedDocument {
+            value,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+
+    /// Get a clone of the value at `query`, from the cache if it was read before
+    ///
+    /// That there is no value at `query` is cached as well. Errors are not cached. Queries which
+    /// do not name a single path, like `a.*` or `a.[?(@.b == 1)]`, are read without the cache.
+    pub fn get(&self, query: &str) -> Result<Option<Value>> {
+        let path = match TomlPath::from_query_in(query, '.', &self.value) {
+            Ok(path) => path,
+            Err(_)   => return self.value.read(query).map(|v| v.cloned()),
+        };
+        if let Some(cached) = self.lock().get(&path) {
+            return Ok(cached.clone());
+        }
+
+        let value = self.value.read(query)?.cloned();
+        let _ = self.lock().insert(path, value.clone());
+        Ok(value)
+    }
+
+    /// The number of cached query results
+    pub fn cached(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<'_, HashMap<TomlPath, Option<Value>>> {
+        // the map is consistent even if a thread panicked while holding the lock
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Drop the cached results which a modification at `query` could have changed
+    ///
+    /// These are the results for paths above and below the modified path. Modifications of array
+    /// elements can move the other elements, so they drop all results for the array. If `query`
+    /// does not name a single path, all results are dropped.
+    fn invalidate(&mut self, query: &str, sep: char) {
+        let mut path = match TomlPath::from_query_in(query, sep, &self.value) {
+            Ok(path) => path,
+            Err(_)   => return self.lock().clear(),
+        };
+        if let Some(&Segment::Index(_)) = path.last() {
+            let _ = path.pop();
+        }
+
+        self.lock().retain(|cached, _| !cached.starts_with(&path) && !path.starts_with(cached));
+    }
+
+}
+
+impl From<Value> for CachedDocument {
+    fn from(value: Value) -> CachedDocument {
+        CachedDocument::new(value)
+    }
+}
+
+impl Clone for CachedDocument {
+    fn clone(&self) -> CachedDocument {
+        CachedDocument {
+            value: self.value.clone(),
+            cache: Mutex::new(self.lock().clone()),
+        }
+    }
+}
+
+impl<'doc> TomlValueReadExt<'doc> for CachedDocument {
+
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        self.value.read_with_seperator(query, sep)
+    }
+
+    fn read_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Option<&'doc mut Value>> {
+        self.invalidate(query, sep);
+        self.value.read_mut_with_seperator(query, sep)
+    }
+
+}
+
+impl<'doc> TomlValueReadAllExt<'doc> for CachedDocument {
+
+    fn read_all_with_seperator(&'doc self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc Value)>> {
+        self.value.read_all_with_seperator(query, sep)
+    }
+
+    fn read_all_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc mut Value)>> {
+        let paths = self.value.read_all_with_seperator(query, sep)?
+            .into_iter()
+            .map(|(path, _)| path.to_string())
+            .collect::<Vec<_>>();
+
+        for path in paths {
+            self.invalidate(&path, '.');
+        }
+        self.value.read_all_mut_with_seperator(query, sep)
+    }
+
+}
+
+impl TomlValueSetExt for CachedDocument {
+
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        self.invalidate(query, sep);
+        self.value.set_with_seperator(query, sep, value)
+    }
+
+}
+
+impl TomlValueInsertExt for CachedDocument {
+
+    fn insert_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        self.invalidate(query, sep);
+        self.value.insert_with_seperator(query, sep, value)
+    }
+
+}
+
+impl TomlValueDeleteExt for CachedDocument {
+
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        self.invalidate(query, sep);
+        self.value.delete_with_seperator(query, sep)
+    }
+
+    fn prune_at(&mut self, query: &str) -> Result<Vec<TomlPath>> {
+        self.invalidate(query, '.');
+        self.value.prune_at(query)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::Error;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> CachedDocument {
+        CachedDocument::new(toml_from_str(r#"
+        [server]
+        port = 80
+        hosts = [ "a", "b" ]
+
+        [log]
+        level = "info"
+        "#).unwrap())
+    }
+
+    #[test]
+    fn test_cache() {
+        let doc = doc();
+        assert_eq!(doc.get("server.port").unwrap(), Some(Value::Integer(80)));
+        assert_eq!(doc.get("server.port").unwrap(), Some(Value::Integer(80)));
+        assert_eq!(doc.get("server.missing").unwrap(), None);
+        assert!(doc.get("server.port.[0]").is_err());
+        assert_eq!(doc.cached(), 2);
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let mut doc = doc();
+        for query in &["server", "server.port", "server.hosts.[1]", "log.level", "x.y"] {
+            let _ = doc.get(query).unwrap();
+        }
+
+        let _ = doc.set("server.port", Value::Integer(8080)).unwrap();
+        assert_eq!(doc.cached(), 3);
+        assert_eq!(doc.get("server.port").unwrap(), Some(Value::Integer(8080)));
+        assert_eq!(doc.get("server").unwrap().unwrap()["port"], Value::Integer(8080));
+
+        let _ = doc.delete("server.hosts.[0]").unwrap();
+        assert_eq!(doc.get("server.hosts.[1]").unwrap(), None);
+        assert_eq!(doc.get("server.hosts.[0]").unwrap(), Some(Value::String(String::from("b"))));
+
+        let _ = doc.insert("x.y", Value::Integer(1)).unwrap();
+        assert_eq!(doc.get("x.y").unwrap(), Some(Value::Integer(1)));
+
+        *doc.read_mut("log.level").unwrap().unwrap() = Value::String(String::from("debug"));
+        assert_eq!(doc.get("log.level").unwrap(), Some(Value::String(String::from("debug"))));
+    }
+
+    #[test]
+    fn test_indices_from_end() {
+        let mut doc = doc();
+        assert_eq!(doc.get("server.hosts.[-1]").unwrap(), Some(Value::String(String::from("b"))));
+        assert_eq!(doc.get("server.hosts.[1]").unwrap(), Some(Value::String(String::from("b"))));
+        assert_eq!(doc.cached(), 1);
+
+        let _ = doc.insert("server.hosts.[+]", Value::String(String::from("c"))).unwrap();
+        assert_eq!(doc.get("server.hosts.[last]").unwrap(), Some(Value::String(String::from("c"))));
+        let _ = doc.set("server.hosts.[-1]", Value::String(String::from("d"))).unwrap();
+        assert_eq!(doc.get("server.hosts.[2]").unwrap(), Some(Value::String(String::from("d"))));
+        let _ = doc.delete("server.hosts.[-3]").unwrap();
+        assert_eq!(doc.get("server.hosts.[0]").unwrap(), Some(Value::String(String::from("b"))));
+    }
+
+    #[test]
+    fn test_uncached_queries() {
+        let doc = doc();
+        for query in &["server.*", "server.hosts.[?(@ == 'b')]", "server.hosts.[-3]"] {
+            let expected = doc.value().read(query).map(|v| v.cloned());
+            assert_eq!(format!("{:?}", doc.get(query)), format!("{:?}", expected), "{}", query);
+        }
+        assert!(is_match!(doc.get("server.hosts.[-3]"), Err(Error::IndexFromEndOutOfBounds(3, 2))));
+        assert_eq!(doc.cached(), 0);
+    }
+
+}
@@ -0,0 +1,206 @@
+/// Comments for serialized TOML documents
+///
+/// `toml::Value` has no place for comments, so they are kept in a separate `Comments` table,
+/// keyed by the path of the value they belong to. `to_string_commented()` serializes a document
+/// and writes every comment as `#` lines above the key or section header of its value.
+
+use std::collections::{BTreeMap, HashMap};
+
+use toml::Value;
+
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+
+/// Comments for the values of a document
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Comments(BTreeMap<TomlPath, String>);
+
+impl Comments {
+
+    pub fn new() -> Comments {
+        Comments::default()
+    }
+
+    /// Set the comment for the value at `query`, replacing the previous one
+    ///
+    /// The comment may span several lines, each of them becomes a `#` line.
+    pub fn set(&mut self, query: &str, comment: &str) -> Result<Option<String>> {
+        let path = TomlPath::from_query(query, '.')?;
+        Ok(self.0.insert(path, String::from(comment)))
+    }
+
+    pub fn get(&self, path: &TomlPath) -> Option<&str> {
+        self.0.get(path).map(String::as_str)
+    }
+
+    pub fn remove(&mut self, path: &TomlPath) -> Option<String> {
+        self.0.remove(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+}
+
+/// Serialize `value`, writing the `comments` above the keys and section headers of their values
+///
+/// Values which are serialized inline, like the elements of an array of integers or the keys of
+/// inline tables, cannot have comments. Their comments are not written.
+pub fn to_string_commented(value: &Value, comments: &Comments) -> Result<String> {
+    let text = ::toml::to_string(value).map_err(|e| Error::CannotSerialize(String::new(), e.to_string()))?;
+    if comments.is_empty() {
+        return Ok(text);
+    }
+
+    let mut out      = String::with_capacity(text.len());
+    let mut table    = TomlPath::new();
+    let mut elements = HashMap::new();
+
+    for line in text.lines() {
+        let path = if line.starts_with("[[") {
+            table = header_path(value, &line[2..line.len() - 2], &elements);
+            let count = elements.entry(table.clone()).or_insert(0);
+            *count += 1;
+            table.push(Segment::Index(*count - 1));
+            Some(table.clone())
+        } else if line.starts_with('[') {
+            table = header_path(value, &line[1..line.len() - 1], &elements);
+            Some(table.clone())
+        } else if line.is_empty() {
+            None
+        } else {
+            let (key, _) = parse_key(line);
+            let mut path = table.clone();
+            path.push(Segment::Key(key));
+            Some(path)
+        };
+
+        if let Some(comment) = path.and_then(|p| comments.get(&p)) {
+            for comment_line in comment.lines() {
+                out.push_str("# ");
+                out.push_str(comment_line);
+                out.push('\n');
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+/// The path of the table with the section header `header`
+///
+/// Arrays of tables on the way resolve to their last element written so far.
+fn header_path(value: &Value, header: &str, elements: &HashMap<TomlPath, usize>) -> TomlPath {
+    let mut path    = TomlPath::new();
+    let mut current = Some(value);
+    let mut rest    = header;
+
+    while !rest.is_empty() {
+        let (key, after) = parse_key(rest);
+        rest = after.trim_start_matches('.');
+        path.push(Segment::Key(key.clone()));
+        current = current.and_then(|v| v.get(&key));
+
+        match current {
+            Some(&Value::Array(ref ary)) if !rest.is_empty() => {
+                let idx = elements.get(&path).map(|n| n.saturating_sub(1)).unwrap_or(0);
+                path.push(Segment::Index(idx));
+                current = ary.get(idx);
+            },
+            _ => { },
+        }
+    }
+
+    path
+}
+
+/// Split a (bare or quoted) key off the start of `s`
+fn parse_key(s: &str) -> (String, &str) {
+    if !s.starts_with('"') {
+        let end = s.find(&['.', ' ', '='][..]).unwrap_or(s.len());
+        return (String::from(&s[..end]), &s[end..]);
+    }
+
+    let mut key   = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"'  => return (key, &s[i + 1..]),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => key.push('\n'),
+                Some((_, 't')) => key.push('\t'),
+                Some((_, 'r')) => key.push('\r'),
+                Some((_, c))   => key.push(c),
+                None           => break,
+            },
+            c    => key.push(c),
+        }
+    }
+    (key, "")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_comments() {
+        let doc : Value = toml_from_str(r#"
+        name = "app"
+
+        [server]
+        port = 80
+
+        [[server.route]]
+        path = "/"
+
+        [[server.route]]
+        path = "/api"
+
+        [server."a.b"]
+        c = 1
+        "#).unwrap();
+
+        let mut comments = Comments::new();
+        comments.set("name", "The name of the application").unwrap();
+        comments.set("server", "Server settings").unwrap();
+        comments.set("server.port", "Port to listen on,\nbelow 1024 needs root").unwrap();
+        comments.set("server.route.[1]", "The API").unwrap();
+        comments.set("server.route.[1].path", "Prefix").unwrap();
+        let _ = comments.0.insert(TomlPath::from(vec![
+            Segment::Key(String::from("server")),
+            Segment::Key(String::from("a.b")),
+            Segment::Key(String::from("c")),
+        ]), String::from("Quoted"));
+
+        let text = to_string_commented(&doc, &comments).unwrap();
+        assert_eq!(toml_from_str::<Value>(&text).unwrap(), doc);
+
+        let expected = [
+            "# The name of the application\nname = \"app\"\n",
+            "# Server settings\n[server]\n# Port to listen on,\n# below 1024 needs root\nport = 80\n",
+            "# The API\n[[server.route]]\n# Prefix\npath = \"/api\"\n",
+            "[server.\"a.b\"]\n# Quoted\nc = 1\n",
+        ];
+        for part in expected.iter() {
+            assert!(text.contains(part), "{} not in {}", part, text);
+        }
+        assert_eq!(text.matches('#').count(), 7);
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("a.b"), (String::from("a"), ".b"));
+        assert_eq!(parse_key("a = 1"), (String::from("a"), " = 1"));
+        assert_eq!(parse_key(r#""a.\"b" = 1"#), (String::from("a.\"b"), " = 1"));
+    }
+
+}
@@ -0,0 +1,122 @@
+/// Cursors into TOML documents
+///
+/// A `Cursor` points to an already resolved value in a document. Queries executed through it are
+/// relative to that value, so the path to it is only resolved once, no matter how many values
+/// are read below it.
+
+use toml::Value;
+
+use read::{TomlValueReadExt, TomlValueReadTypeExt};
+use error::Result;
+
+/// A handle to a resolved value in a document, for reading values below it
+///
+/// Obtained via `TomlValueReadExt::cursor()`. All references handed out by a cursor have the
+/// lifetime of the document borrow, not of the cursor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor<'doc> {
+    value: &'doc Value,
+}
+
+impl<'doc> Cursor<'doc> {
+
+    /// Create a cursor pointing to `value`
+    pub fn new(value: &'doc Value) -> Cursor<'doc> {
+        Cursor { value }
+    }
+
+    /// The value the cursor points to
+    pub fn value(&self) -> &'doc Value {
+        self.value
+    }
+
+    /// Read the value at `query`, relative to the cursor, using a custom seperator
+    pub fn read_with_seperator(&self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        self.value.read_with_seperator(query, sep)
+    }
+
+    /// Read the value at `query`, relative to the cursor
+    pub fn read(&self, query: &str) -> Result<Option<&'doc Value>> {
+        self.value.read(query)
+    }
+
+    /// Create a new cursor at `query`, relative to this cursor
+    pub fn cursor(&self, query: &str) -> Result<Option<Cursor<'doc>>> {
+        self.read(query).map(|o| o.map(Cursor::new))
+    }
+
+    /// See `TomlValueReadTypeExt::read_string`
+    pub fn read_string(&self, query: &str) -> Result<Option<String>> {
+        self.value.read_string(query)
+    }
+
+    /// See `TomlValueReadTypeExt::read_int`
+    pub fn read_int(&self, query: &str) -> Result<Option<i64>> {
+        self.value.read_int(query)
+    }
+
+    /// See `TomlValueReadTypeExt::read_float`
+    pub fn read_float(&self, query: &str) -> Result<Option<f64>> {
+        self.value.read_float(query)
+    }
+
+    /// See `TomlValueReadTypeExt::read_bool`
+    pub fn read_bool(&self, query: &str) -> Result<Option<bool>> {
+        self.value.read_bool(query)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use read::TomlValueReadExt;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_cursor_reads() {
+        let toml : Value = toml_from_str(r#"
+        [theme]
+        name = "dark"
+        size = 12
+        scale = 1.5
+        bold = true
+
+        [theme.colors]
+        fg = "white"
+        "#).unwrap();
+
+        let theme = toml.cursor("theme").unwrap().unwrap();
+
+        assert_eq!(theme.read_string("name").unwrap(), Some(String::from("dark")));
+        assert_eq!(theme.read_int("size").unwrap(), Some(12));
+        assert_eq!(theme.read_float("scale").unwrap(), Some(1.5));
+        assert_eq!(theme.read_bool("bold").unwrap(), Some(true));
+        assert!(theme.read("missing").unwrap().is_none());
+
+        let colors = theme.cursor("colors").unwrap().unwrap();
+        assert_eq!(colors.read("fg").unwrap(), toml.read("theme.colors.fg").unwrap());
+    }
+
+    #[test]
+    fn test_cursor_outlives_itself() {
+        let toml : Value = toml_from_str(r#"
+        [a]
+        b = 1
+        "#).unwrap();
+
+        let value = {
+            let cursor = toml.cursor("a").unwrap().unwrap();
+            cursor.read("b").unwrap().unwrap()
+        };
+
+        assert_eq!(value, &Value::Integer(1));
+    }
+
+    #[test]
+    fn test_cursor_missing() {
+        let toml : Value = toml_from_str("").unwrap();
+        assert!(toml.cursor("a").unwrap().is_none());
+    }
+
+}
@@ -4,6 +4,8 @@ use toml::Value;
 
 use tokenizer::Token;
 use tokenizer::tokenize_with_seperator;
+use read::{IntoEntries, TomlValueReadExt};
+use path::{Segment, TomlPath};
 use error::{Error, Result};
 
 pub trait TomlValueDeleteExt {
@@ -43,153 +45,299 @@ pub trait TomlValueDeleteExt {
         self.delete_with_seperator(query, '.')
     }
 
+    /// Delete the value at `path`
+    ///
+    /// Unlike a query, the keys of `path` are never parsed, see `path::Path`. Otherwise like
+    /// `TomlValueDeleteExt::delete_with_seperator`.
+    fn delete_path(&mut self, path: &TomlPath) -> Result<Option<Value>> {
+        self.delete_with_seperator(&path.to_string(), '.')
+    }
+
+    /// Remove all empty tables and arrays below the value at `query`
+    ///
+    /// See `prune()`. The returned paths start with the segments of `query`. If there is no value
+    /// at `query`, nothing is removed.
+    fn prune_at(&mut self, query: &str) -> Result<Vec<TomlPath>>;
+
+    /// Remove the table or array at `query`, even if it is not empty, and iterate over its
+    /// entries
+    ///
+    /// The entries are yielded like by `TomlValueReadExt::iter_at()`, but owned.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * `Err(Error::TypeError(_, _))` if the value at `query` is neither a Table nor an Array.
+    ///   The value is not removed then.
+    fn drain(&mut self, query: &str) -> Result<IntoEntries>
+        where Self: for<'doc> TomlValueReadExt<'doc>
+    {
+        let contents = match self.read_mut(query)? {
+            Some(value) => {
+                let empty = match *value {
+                    Value::Table(_) => Value::Table(Default::default()),
+                    Value::Array(_) => Value::Array(Vec::new()),
+                    ref other => return Err(Error::TypeError("Table or Array", ::util::name_of_val(other))),
+                };
+                ::std::mem::replace(value, empty)
+            },
+            None => return Err(Error::NotAvailable(String::from(query))),
+        };
+
+        let _ = self.delete(query)?;
+        Ok(IntoEntries::new(contents).unwrap_or_else(|_| unreachable!()))
+    }
+
+}
+
+/// Remove all empty tables and arrays below `value`, recursively
+///
+/// Tables and arrays which only become empty because their content was pruned are removed as
+/// well. `value` itself is never removed.
+///
+/// # Return value
+///
+/// The paths of the removed values, relative to `value`, deepest first. Array indices refer to
+/// the positions in the array before anything was removed.
+pub fn prune(value: &mut Value) -> Vec<TomlPath> {
+    let mut removed = Vec::new();
+    prune_inner(value, &mut TomlPath::new(), &mut removed);
+    removed
+}
+
+fn prune_inner(value: &mut Value, path: &mut TomlPath, removed: &mut Vec<TomlPath>) {
+    fn is_empty_structure(val: &Value) -> bool {
+        match *val {
+            Value::Table(ref t) => t.is_empty(),
+            Value::Array(ref a) => a.is_empty(),
+            _                   => false,
+        }
+    }
+
+    match *value {
+        Value::Table(ref mut t) => {
+            let keys = t.keys().cloned().collect::<Vec<String>>();
+            for key in keys {
+                path.push(Segment::Key(key.clone()));
+
+                let empty = {
+                    let sub = t.get_mut(&key).unwrap(); // safe because key is from this table
+                    prune_inner(sub, path, removed);
+                    is_empty_structure(sub)
+                };
+
+                if empty {
+                    let _ = t.remove(&key);
+                    removed.push(path.clone());
+                }
+
+                let _ = path.pop();
+            }
+        },
+        Value::Array(ref mut a) => {
+            let mut i = 0;
+            let mut original_idx = 0;
+            while i < a.len() {
+                path.push(Segment::Index(original_idx));
+
+                prune_inner(&mut a[i], path, removed);
+                if is_empty_structure(&a[i]) {
+                    let _ = a.remove(i);
+                    removed.push(path.clone());
+                } else {
+                    i += 1;
+                }
+
+                let _ = path.pop();
+                original_idx += 1;
+            }
+        },
+        _ => { /* Nothing to prune */ }
+    }
 }
 
 impl TomlValueDeleteExt for Value {
 
-    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+    fn prune_at(&mut self, query: &str) -> Result<Vec<TomlPath>> {
         use resolver::mut_resolver::resolve;
-        use std::ops::Index;
-
-        let mut tokens = try!(tokenize_with_seperator(query, sep));
-        let last_token = tokens.pop_last();
-
-        /// Check whether a structure (Table/Array) is empty. If the Value has not these types,
-        /// the default value is returned
-        #[inline]
-        fn is_empty(val: Option<&Value>, default: bool) -> bool {
-            val.map(|v| match v {
-                    &Value::Table(ref tab) => tab.is_empty(),
-                    &Value::Array(ref arr) => arr.is_empty(),
-                    _                      => default
-                })
-                .unwrap_or(default)
-        }
 
-        #[inline]
-        fn is_table(val: Option<&Value>) -> bool {
-            val.map(|v| is_match!(v, &Value::Table(_))).unwrap_or(false)
-        }
+        let tokens = tokenize_with_seperator(query, '.')?;
+        let mut path = TomlPath::from_tokens(&tokens)?;
 
-        #[inline]
-        fn is_array(val: Option<&Value>) -> bool {
-            val.map(|v| is_match!(v, &Value::Array(_))).unwrap_or(false)
+        match resolve(self, &tokens, false)? {
+            None        => Ok(Vec::new()),
+            Some(value) => {
+                let mut removed = Vec::new();
+                prune_inner(value, &mut path, &mut removed);
+                Ok(removed)
+            },
         }
+    }
 
-        #[inline]
-        fn name_of_val(val: Option<&Value>) -> &'static str {
-            val.map(::util::name_of_val).unwrap_or("None")
-        }
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        instrument!("delete", query, {
+            tokenize_with_seperator(query, sep).and_then(|tokens| delete_impl(self, &tokens))
+        })
+    }
+
+    fn delete_path(&mut self, path: &TomlPath) -> Result<Option<Value>> {
+        let query = path.to_string();
+        instrument!("delete", &query, path.to_tokens().and_then(|tokens| delete_impl(self, &tokens)))
+    }
 
-        if last_token.is_none() {
-            match self {
-                &mut Value::Table(ref mut tab) => {
-                    match tokens {
-                        Token::Identifier { ident, .. } => {
-                            if is_empty(tab.get(&ident), true) {
-                                Ok(tab.remove(&ident))
+}
+
+pub(crate) fn delete_impl(document: &mut Value, tokens: &[Token]) -> Result<Option<Value>> {
+    use resolver::mut_resolver::resolve;
+    use resolver::array_index;
+    use std::ops::Index;
+
+    let (last_token, parents) = tokens.split_last().ok_or(Error::EmptyQueryError)?;
+
+    /// Check whether a structure (Table/Array) is empty. If the Value has not these types,
+    /// the default value is returned
+    #[inline]
+    fn is_empty(val: Option<&Value>, default: bool) -> bool {
+        val.map(|v| match v {
+                &Value::Table(ref tab) => tab.is_empty(),
+                &Value::Array(ref arr) => arr.is_empty(),
+                _                      => default
+            })
+            .unwrap_or(default)
+    }
+
+    #[inline]
+    fn is_table(val: Option<&Value>) -> bool {
+        val.map(|v| is_match!(v, &Value::Table(_))).unwrap_or(false)
+    }
+
+    #[inline]
+    fn is_array(val: Option<&Value>) -> bool {
+        val.map(|v| is_match!(v, &Value::Array(_))).unwrap_or(false)
+    }
+
+    #[inline]
+    fn name_of_val(val: Option<&Value>) -> &'static str {
+        val.map(::util::name_of_val).unwrap_or("None")
+    }
+
+    if parents.is_empty() {
+        match document {
+            &mut Value::Table(ref mut tab) => {
+                match *last_token {
+                    Token::Identifier { ref ident, .. } => {
+                        if is_empty(tab.get(&ident[..]), true) {
+                            Ok(tab.remove(&ident[..]))
+                        } else {
+                            if is_table(tab.get(&ident[..])) {
+                                Err(Error::CannotDeleteNonEmptyTable(Some(ident.to_string())))
+                            } else if is_array(tab.get(&ident[..])) {
+                                Err(Error::CannotDeleteNonEmptyArray(Some(ident.to_string())))
                             } else {
-                                if is_table(tab.get(&ident)) {
-                                    Err(Error::CannotDeleteNonEmptyTable(Some(ident.clone())))
-                                } else if is_array(tab.get(&ident)) {
-                                    Err(Error::CannotDeleteNonEmptyArray(Some(ident.clone())))
-                                } else {
-                                    let act = name_of_val(tab.get(&ident));
-                                    let tbl = "table";
-                                    Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                                }
+                                let act = name_of_val(tab.get(&ident[..]));
+                                let tbl = "table";
+                                Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
                             }
-                        },
-                        _ => Ok(None)
-                    }
-                },
-                &mut Value::Array(ref mut arr) => {
-                    match tokens {
-                        Token::Identifier { ident, .. } => Err(Error::NoIdentifierInArray(ident)),
-                        Token::Index { idx , .. } => {
-                            if is_empty(Some(arr.index(idx)), true) {
-                                Ok(Some(arr.remove(idx)))
+                        }
+                    },
+                    Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
+                    _ => Ok(None)
+                }
+            },
+            &mut Value::Array(ref mut arr) => {
+                match *last_token {
+                    Token::Identifier { ref ident, .. } => Err(Error::NoIdentifierInArray(ident.to_string())),
+                    Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
+                    Token::Index { idx, from_end, .. } => {
+                        let idx = array_index(idx, from_end, arr.len())?;
+                        if idx >= arr.len() {
+                            return Err(Error::ArrayIndexOutOfBounds(idx, arr.len()))
+                        }
+                        if is_empty(Some(arr.index(idx)), true) {
+                            Ok(Some(arr.remove(idx)))
+                        } else {
+                            if is_table(Some(arr.index(idx))) {
+                                Err(Error::CannotDeleteNonEmptyTable(None))
+                            } else if is_array(Some(arr.index(idx))) {
+                                Err(Error::CannotDeleteNonEmptyArray(None))
                             } else {
-                                if is_table(Some(arr.index(idx))) {
-                                    Err(Error::CannotDeleteNonEmptyTable(None))
-                                } else if is_array(Some(arr.index(idx))) {
-                                    Err(Error::CannotDeleteNonEmptyArray(None))
-                                } else {
-                                    let act = name_of_val(Some(arr.index(idx)));
-                                    let tbl = "table";
-                                    Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                                }
+                                let act = name_of_val(Some(arr.index(idx)));
+                                let tbl = "table";
+                                Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
                             }
-                        },
-                    }
-                },
-                _ => {
-                    let kind = match tokens {
-                        Token::Identifier { ident, .. } => Error::QueryingValueAsTable(ident),
-                        Token::Index { idx , .. } => Error::QueryingValueAsArray(idx),
-                    };
-                    Err(Error::from(kind))
+                        }
+                    },
                 }
+            },
+            _ => {
+                let kind = match *last_token {
+                    Token::Identifier { ref ident, .. } => Error::QueryingValueAsTable(ident.to_string()),
+                    Token::Index { idx , .. } => Error::QueryingValueAsArray(idx),
+                    Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Error::UnexpectedMultiMatch,
+                };
+                Err(Error::from(kind))
             }
-        } else {
-            let val = try!(resolve(self, &tokens, true))
-                .unwrap(); // safe because of resolve() guarantees
-            let last_token = last_token.unwrap();
-            match val {
-                &mut Value::Table(ref mut tab) => {
-                    match *last_token {
-                        Token::Identifier { ref ident, .. } => {
-                            if is_empty(tab.get(ident), true) {
-                                Ok(tab.remove(ident))
+        }
+    } else {
+        let val = try!(resolve(document, parents, true))
+            .unwrap(); // safe because of resolve() guarantees
+        match val {
+            &mut Value::Table(ref mut tab) => {
+                match *last_token {
+                    Token::Identifier { ref ident, .. } => {
+                        if is_empty(tab.get(&ident[..]), true) {
+                            Ok(tab.remove(&ident[..]))
+                        } else {
+                            if is_table(tab.get(&ident[..])) {
+                                Err(Error::CannotDeleteNonEmptyTable(Some(ident.to_string())))
+                            } else if is_array(tab.get(&ident[..])) {
+                                Err(Error::CannotDeleteNonEmptyArray(Some(ident.to_string())))
                             } else {
-                                if is_table(tab.get(ident)) {
-                                    Err(Error::CannotDeleteNonEmptyTable(Some(ident.clone())))
-                                } else if is_array(tab.get(ident)) {
-                                    Err(Error::CannotDeleteNonEmptyArray(Some(ident.clone())))
-                                } else {
-                                    let act = name_of_val(tab.get(ident));
-                                    let tbl = "table";
-                                    Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                                }
-                            }
-                        },
-                        Token::Index { idx, .. } => Err(Error::NoIndexInTable(idx)),
-                    }
-                },
-                &mut Value::Array(ref mut arr) => {
-                    match *last_token {
-                        Token::Identifier { ident, .. } => Err(Error::NoIdentifierInArray(ident)),
-                        Token::Index { idx, .. } => {
-                            if idx > arr.len() {
-                                return Err(Error::ArrayIndexOutOfBounds(idx, arr.len()))
+                                let act = name_of_val(tab.get(&ident[..]));
+                                let tbl = "table";
+                                Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
                             }
-                            if is_empty(Some(&arr.index(idx)), true) {
-                                Ok(Some(arr.remove(idx)))
+                        }
+                    },
+                    Token::Index { idx, .. } => Err(Error::NoIndexInTable(idx)),
+                    Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
+                }
+            },
+            &mut Value::Array(ref mut arr) => {
+                match *last_token {
+                    Token::Identifier { ref ident, .. } => Err(Error::NoIdentifierInArray(ident.to_string())),
+                    Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
+                    Token::Index { idx, from_end, .. } => {
+                        let idx = array_index(idx, from_end, arr.len())?;
+                        if idx >= arr.len() {
+                            return Err(Error::ArrayIndexOutOfBounds(idx, arr.len()))
+                        }
+                        if is_empty(Some(&arr.index(idx)), true) {
+                            Ok(Some(arr.remove(idx)))
+                        } else {
+                            if is_table(Some(&arr.index(idx))) {
+                                Err(Error::CannotDeleteNonEmptyTable(None))
+                            } else if is_array(Some(&arr.index(idx))) {
+                                Err(Error::CannotDeleteNonEmptyArray(None))
                             } else {
-                                if is_table(Some(&arr.index(idx))) {
-                                    Err(Error::CannotDeleteNonEmptyTable(None))
-                                } else if is_array(Some(&arr.index(idx))) {
-                                    Err(Error::CannotDeleteNonEmptyArray(None))
-                                } else {
-                                    let act = name_of_val(Some(arr.index(idx)));
-                                    let tbl = "table";
-                                    Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
-                                }
+                                let act = name_of_val(Some(arr.index(idx)));
+                                let tbl = "table";
+                                Err(Error::CannotAccessBecauseTypeMismatch(tbl, act))
                             }
-                        },
-                    }
-                },
-                _ => {
-                    let kind = match *last_token {
-                        Token::Identifier { ident, .. } => Error::QueryingValueAsTable(ident),
-                        Token::Index { idx, .. }        => Error::QueryingValueAsArray(idx),
-                    };
-                    Err(Error::from(kind))
+                        }
+                    },
                 }
+            },
+            _ => {
+                let kind = match *last_token {
+                    Token::Identifier { ref ident, .. } => Error::QueryingValueAsTable(ident.to_string()),
+                    Token::Index { idx, .. }        => Error::QueryingValueAsArray(idx),
+                    Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Error::UnexpectedMultiMatch,
+                };
+                Err(Error::from(kind))
             }
         }
     }
-
 }
 
 #[cfg(test)]
@@ -197,6 +345,29 @@ mod test {
     use super::*;
     use toml::Value;
     use toml::from_str as toml_from_str;
+    use read::TomlValueReadExt;
+
+    #[test]
+    fn test_drain() {
+        let mut toml : Value = toml_from_str(r#"
+        list = [ "a", "b" ]
+
+        [table]
+        b = 1
+        a = { c = 2 }
+        "#).unwrap();
+
+        let entries = toml.drain("table").unwrap().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, Segment::Key(String::from("a")));
+        assert_eq!(entries[1], (Segment::Key(String::from("b")), Value::Integer(1)));
+        assert!(toml.read("table").unwrap().is_none());
+
+        let res = toml.drain("list.[0]");
+        assert!(is_match!(res, Err(Error::TypeError(_, "String"))));
+        assert_eq!(toml.drain("list").unwrap().len(), 2);
+        assert!(is_match!(toml.drain("list"), Err(Error::NotAvailable(_))));
+    }
 
     #[test]
     fn test_delete_from_empty_document() {
@@ -664,5 +835,74 @@ mod test {
         assert!(is_match!(res, Error::QueryingValueAsArray(0)));
     }
 
-}
+    #[test]
+    fn test_delete_index_from_end() {
+        let mut toml : Value = toml_from_str(r#"
+        values = [ 1, 2, 3 ]
+        "#).unwrap();
+
+        let res = toml.delete_with_seperator(&String::from("values.[-3]"), '.');
+        assert_eq!(res.unwrap(), Some(Value::Integer(1)));
+        assert_eq!(toml["values"], Value::Array(vec![Value::Integer(2), Value::Integer(3)]));
+
+        let res = toml.delete_with_seperator(&String::from("values.[-3]"), '.');
+        assert!(is_match!(res, Err(Error::IndexFromEndOutOfBounds(3, 2))));
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut toml : Value = toml_from_str(r#"
+        keep = 1
+        empty_array = []
+        tables = [ {}, { a = 1 }, {} ]
 
+        [empty]
+
+        [nested.inner]
+
+        [partial]
+        a = 1
+
+        [partial.empty]
+        "#).unwrap();
+
+        let removed = prune(&mut toml)
+            .into_iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(removed, vec![
+            "empty", "empty_array", "nested.inner", "nested", "partial.empty", "tables.[0]",
+            "tables.[2]",
+        ]);
+
+        let expected : Value = toml_from_str(r#"
+        keep = 1
+        tables = [ { a = 1 } ]
+
+        [partial]
+        a = 1
+        "#).unwrap();
+        assert_eq!(toml, expected);
+    }
+
+    #[test]
+    fn test_prune_at() {
+        let mut toml : Value = toml_from_str(r#"
+        [a.b]
+        [a.c]
+        [other.d]
+        "#).unwrap();
+
+        let removed = toml.prune_at("a").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].to_string(), "a.b");
+        assert_eq!(removed[1].to_string(), "a.c");
+
+        assert!(toml.read("a").unwrap().is_some());
+        assert!(toml.read("other.d").unwrap().is_some());
+
+        assert!(toml.prune_at("not.there").unwrap().is_empty());
+    }
+
+}
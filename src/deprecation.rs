@@ -0,0 +1,107 @@
+/// Deprecated paths
+///
+/// A `Deprecations` table maps old paths to the paths which replaced them. Installed on a
+/// `Document` with `Document::with_deprecations()`, reads of an old path, or of a value below it,
+/// resolve to the new path, and a warning with the message is logged.
+
+use path::TomlPath;
+use error::Result;
+
+/// An old path, the path which replaced it and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    old: TomlPath,
+    new: TomlPath,
+    message: String,
+}
+
+impl Deprecation {
+
+    pub fn old_path(&self) -> &TomlPath {
+        &self.old
+    }
+
+    pub fn new_path(&self) -> &TomlPath {
+        &self.new
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+}
+
+/// A table of deprecated paths
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Deprecations(Vec<Deprecation>);
+
+impl Deprecations {
+
+    pub fn new() -> Deprecations {
+        Deprecations::default()
+    }
+
+    /// Deprecate `old` in favour of `new`, both queries with `.` as seperator
+    ///
+    /// Values below `old` move along, so deprecating `server.addr` for `listen.addr` makes
+    /// `server.addr.port` resolve to `listen.addr.port`.
+    pub fn deprecate(mut self, old: &str, new: &str, message: &str) -> Result<Deprecations> {
+        self.0.push(Deprecation {
+            old: TomlPath::from_query(old, '.')?,
+            new: TomlPath::from_query(new, '.')?,
+            message: String::from(message),
+        });
+        Ok(self)
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, Deprecation> {
+        self.0.iter()
+    }
+
+    /// The path `path` resolves to and the deprecation which applies to it, if any
+    ///
+    /// If several old paths are prefixes of `path`, the longest one applies. The new path is not
+    /// resolved again, deprecations do not chain.
+    pub fn resolve(&self, path: &TomlPath) -> Option<(TomlPath, &Deprecation)> {
+        self.0
+            .iter()
+            .filter(|d| path.starts_with(&d.old))
+            .max_by_key(|d| d.old.len())
+            .map(|d| {
+                let mut new = d.new.clone();
+                for segment in path.segments()[d.old.len()..].iter() {
+                    new.push(segment.clone());
+                }
+                (new, d)
+            })
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(query: &str) -> TomlPath {
+        TomlPath::from_query(query, '.').unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let deprecations = Deprecations::new()
+            .deprecate("server", "listen", "Renamed in 2.0").unwrap()
+            .deprecate("server.host", "listen.address", "Use an address").unwrap();
+
+        let (new, d) = deprecations.resolve(&path("server.port")).unwrap();
+        assert_eq!(new, path("listen.port"));
+        assert_eq!(d.message(), "Renamed in 2.0");
+
+        let (new, d) = deprecations.resolve(&path("server.host.[0]")).unwrap();
+        assert_eq!(new, path("listen.address.[0]"));
+        assert_eq!(d.old_path(), &path("server.host"));
+
+        assert!(deprecations.resolve(&path("listen.port")).is_none());
+        assert!(deprecations.resolve(&path("serve")).is_none());
+    }
+
+}
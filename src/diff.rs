@@ -0,0 +1,280 @@
+/// Differences between TOML documents
+///
+/// `diff()` compares two documents value by value and reports every difference together with
+/// its path. Tables are compared key by key and arrays index by index. Volatile values, like
+/// timestamps, can be left out with `diff_ignoring()`.
+///
+/// A `Changeset` holds the differences of two documents, and can be partitioned by path
+/// patterns, e.g. into changes which can be applied automatically and changes which need a
+/// review.
+
+use std::fmt;
+
+use toml::Value;
+
+use path::{PathPattern, Segment, TomlPath};
+
+/// A difference between two documents
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// The value is only in the new document
+    Added(TomlPath, Value),
+
+    /// The value is only in the old document
+    Removed(TomlPath, Value),
+
+    /// The value is in both documents, but differs. Tables and arrays only differ if their types
+    /// differ, otherwise the differences of their contents are reported.
+    Changed(TomlPath, Value, Value),
+}
+
+impl Difference {
+
+    pub fn path(&self) -> &TomlPath {
+        match *self {
+            Difference::Added(ref path, _)      => path,
+            Difference::Removed(ref path, _)    => path,
+            Difference::Changed(ref path, _, _) => path,
+        }
+    }
+
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Difference::Added(ref path, ref new)          => write!(f, "+ {} = {}", path, new),
+            Difference::Removed(ref path, ref old)        => write!(f, "- {} = {}", path, old),
+            Difference::Changed(ref path, ref old, ref new) => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// A list of differences
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Changeset(Vec<Difference>);
+
+impl Changeset {
+
+    /// The differences between `old` and `new`, see `diff()`
+    pub fn between(old: &Value, new: &Value) -> Changeset {
+        Changeset(diff(old, new))
+    }
+
+    pub fn differences(&self) -> &[Difference] {
+        &self.0
+    }
+
+    pub fn into_differences(self) -> Vec<Difference> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The differences at or below a path which matches one of `patterns`
+    pub fn filter(&self, patterns: &[PathPattern]) -> Changeset {
+        self.split(patterns).0
+    }
+
+    /// Split the changeset into the differences at or below a path which matches one of
+    /// `patterns`, and the other differences
+    ///
+    /// Both changesets keep the order of this changeset.
+    pub fn split(&self, patterns: &[PathPattern]) -> (Changeset, Changeset) {
+        let (matching, rest) = self.0
+            .iter()
+            .cloned()
+            .partition(|d| patterns.iter().any(|p| p.matches_at_or_above(d.path())));
+
+        (Changeset(matching), Changeset(rest))
+    }
+
+}
+
+impl From<Vec<Difference>> for Changeset {
+    fn from(differences: Vec<Difference>) -> Changeset {
+        Changeset(differences)
+    }
+}
+
+impl IntoIterator for Changeset {
+    type Item = Difference;
+    type IntoIter = ::std::vec::IntoIter<Difference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Compare `old` with `new`
+///
+/// The differences are ordered by path, like `walk::walk()` visits the values.
+pub fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    diff_ignoring(old, new, &[])
+}
+
+/// Compare `old` with `new`, ignoring the values which match one of the `ignore` patterns and
+/// everything below them
+pub fn diff_ignoring(old: &Value, new: &Value, ignore: &[PathPattern]) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_inner(old, new, &mut TomlPath::new(), ignore, &mut differences);
+    differences
+}
+
+fn diff_inner(old: &Value, new: &Value, path: &mut TomlPath, ignore: &[PathPattern], differences: &mut Vec<Difference>) {
+    match (old, new) {
+        (&Value::Table(ref o), &Value::Table(ref n)) => {
+            let mut keys = o.keys().chain(n.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                path.push(Segment::Key(key.clone()));
+                diff_entry(o.get(key), n.get(key), path, ignore, differences);
+                let _ = path.pop();
+            }
+        },
+
+        (&Value::Array(ref o), &Value::Array(ref n)) => {
+            for idx in 0..::std::cmp::max(o.len(), n.len()) {
+                path.push(Segment::Index(idx));
+                diff_entry(o.get(idx), n.get(idx), path, ignore, differences);
+                let _ = path.pop();
+            }
+        },
+
+        _ => if old != new {
+            differences.push(Difference::Changed(path.clone(), old.clone(), new.clone()));
+        },
+    }
+}
+
+fn diff_entry(old: Option<&Value>,
+              new: Option<&Value>,
+              path: &mut TomlPath,
+              ignore: &[PathPattern],
+              differences: &mut Vec<Difference>)
+{
+    if ignore.iter().any(|p| p.matches(path)) {
+        return;
+    }
+
+    match (old, new) {
+        (Some(o), Some(n)) => diff_inner(o, n, path, ignore, differences),
+        (Some(o), None)    => differences.push(Difference::Removed(path.clone(), o.clone())),
+        (None, Some(n))    => differences.push(Difference::Added(path.clone(), n.clone())),
+        (None, None)       => { },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_diff() {
+        let old: Value = toml_from_str(r#"
+        a = 1
+        b = [ 1, 2, 3 ]
+
+        [c]
+        d = "d"
+        e = true
+        "#).unwrap();
+        let new: Value = toml_from_str(r#"
+        a = 2
+        b = [ 1, 2 ]
+
+        [c]
+        d = "d"
+        f = 1.5
+        "#).unwrap();
+
+        let differences = diff(&old, &new)
+            .into_iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(differences, vec![
+            "~ a: 1 -> 2",
+            "- b.[2] = 3",
+            "- c.e = true",
+            "+ c.f = 1.5",
+        ]);
+    }
+
+    #[test]
+    fn test_type_change() {
+        let old: Value = toml_from_str("a = [ 1 ]").unwrap();
+        let new: Value = toml_from_str("[a]\nb = 1").unwrap();
+
+        let differences = diff(&old, &new);
+        assert_eq!(differences.len(), 1);
+        assert!(is_match!(differences[0], Difference::Changed(_, Value::Array(_), Value::Table(_))));
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_changeset_split() {
+        let old: Value = toml_from_str(r#"
+        [package]
+        version = "1.0.0"
+        authors = [ "a" ]
+
+        [dependencies]
+        toml = "0.4"
+        "#).unwrap();
+        let new: Value = toml_from_str(r#"
+        [package]
+        version = "1.0.1"
+        authors = [ "a", "b" ]
+
+        [dependencies]
+        toml = "0.5"
+        "#).unwrap();
+
+        let changeset = Changeset::between(&old, &new);
+        let safe = vec![PathPattern::new("package.version").unwrap(), PathPattern::new("package.authors").unwrap()];
+        let (auto, review) = changeset.split(&safe);
+
+        assert_eq!(auto.len(), 2);
+        assert_eq!(auto, changeset.filter(&safe));
+        assert_eq!(review.into_differences(), vec![
+            Difference::Changed(TomlPath::from(vec![Segment::Key(String::from("dependencies")),
+                                                    Segment::Key(String::from("toml"))]),
+                                Value::from("0.4"),
+                                Value::from("0.5")),
+        ]);
+        assert!(changeset.filter(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_ignoring() {
+        let old: Value = toml_from_str(r#"
+        [meta]
+        generated_at = 1
+
+        [a]
+        checksum = "abc"
+        value = 1
+        "#).unwrap();
+        let new: Value = toml_from_str(r#"
+        [a]
+        checksum = "def"
+        value = 2
+        "#).unwrap();
+
+        let ignore = vec![PathPattern::new("meta").unwrap(), PathPattern::new("*.checksum").unwrap()];
+        let differences = diff_ignoring(&old, &new, &ignore);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path().to_string(), "a.value");
+    }
+
+}
@@ -0,0 +1,1201 @@
+/// A TOML document which keeps track of its modifications
+///
+/// `Document` wraps a `toml::Value` and implements the same extension traits. Every modification
+/// done through it is recorded, which allows `StablePath` handles to notice when the value they
+/// point to was removed or replaced.
+///
+/// A `Document` can also keep an index of its values (see `Document::build_index()`), which is
+/// patched on every modification, and protect values from modifications (see
+/// `Document::freeze()` and `Document::with_approver()`).
+///
+/// With `Document::snapshot()` and `Document::restore()`, several modifications can be rolled
+/// back at once, e.g. when one of them fails. `Document::with_undo()` keeps a history of the
+/// modifications, to take them back one by one with `Document::undo()` and `Document::redo()`.
+///
+/// With `Document::with_deprecations()`, reads of deprecated paths resolve to the paths which
+/// replaced them.
+
+use std::fmt;
+use std::sync::Arc;
+
+use toml::Value;
+
+use read::{TomlValueReadAllExt, TomlValueReadExt, TomlValueReadTypeExt};
+use set::TomlValueSetExt;
+use insert::TomlValueInsertExt;
+use delete::TomlValueDeleteExt;
+use path::{Segment, TomlPath, value_at, value_at_mut};
+use index::PathIndex;
+use format::FormatStyle;
+use deprecation::Deprecations;
+use error::{Error, Result};
+
+/// The number of modifications a `Document` remembers by default, see
+/// `Document::with_change_limit()`
+pub const DEFAULT_CHANGE_LIMIT: usize = 1024;
+
+/// A modification of a document
+#[derive(Debug, Clone)]
+struct Change {
+    generation: u64,
+    path: TomlPath,
+
+    /// Whether the array elements after the modified index moved
+    shifts: bool,
+}
+
+impl Change {
+
+    /// Whether this change removed or replaced the value at `path` (or moved it to another index)
+    fn affects(&self, path: &TomlPath) -> bool {
+        affects(&self.path, self.shifts, path)
+    }
+
+}
+
+/// Whether a modification at `changed` removes, replaces or moves the value at `path`
+///
+/// `shifts` tells whether the array elements after a modified index move.
+fn affects(changed: &TomlPath, shifts: bool, path: &TomlPath) -> bool {
+    if path.starts_with(changed) {
+        return true;
+    }
+
+    if !shifts || changed.is_empty() {
+        return false;
+    }
+
+    let depth = changed.len() - 1;
+    match (changed.segments().last(), path.segments().get(depth)) {
+        (Some(&Segment::Index(modified)), Some(&Segment::Index(idx))) => {
+            idx >= modified && changed.segments()[..depth] == path.segments()[..depth]
+        },
+        _ => false,
+    }
+}
+
+/// The kinds of modifications an approver is asked about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Set,
+    Insert,
+    Delete,
+    Prune,
+
+    /// Mutable borrow via `read_mut()`
+    ReadMut,
+
+    /// Replacing the whole value via `Document::restore()`
+    Restore,
+
+    /// Modification via `Document::with_raw_mut()`, asked about with the modified value
+    RawMut,
+}
+
+impl Operation {
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Operation::Set     => "set",
+            Operation::Insert  => "insert",
+            Operation::Delete  => "delete",
+            Operation::Prune   => "prune",
+            Operation::ReadMut => "read_mut",
+            Operation::Restore => "restore",
+            Operation::RawMut  => "raw_mut",
+        }
+    }
+
+}
+
+type ApproveFn = dyn Fn(&TomlPath, Operation, Option<&Value>) -> bool + Send + Sync;
+
+/// A callback deciding whether a modification is allowed, see `Document::with_approver()`
+#[derive(Clone)]
+struct Approver(Arc<ApproveFn>);
+
+impl fmt::Debug for Approver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Approver")
+    }
+}
+
+/// A TOML document with a generation counter
+///
+/// The generation is incremented with every successful modification. Reads do not change it,
+/// but `read_mut()` does, because the returned reference may be used to modify the value.
+///
+/// Queries without an explicit seperator (`read()`, `set()`, ...) use the seperator of the
+/// document, which is `.` unless changed with `Document::with_seperator()`.
+#[derive(Debug, Clone)]
+pub struct Document {
+    value: Value,
+    seperator: char,
+    generation: u64,
+    changes: Vec<Change>,
+    change_limit: usize,
+
+    /// The generation of the newest change dropped from `changes`
+    forgotten: u64,
+    index: Option<PathIndex>,
+    frozen: Vec<TomlPath>,
+    approver: Option<Approver>,
+    snapshots: Vec<(SnapshotId, Value)>,
+    next_snapshot: u64,
+    history: Option<UndoStack>,
+    deprecations: Option<Deprecations>,
+}
+
+/// The undo and redo history of a `Document`, see `Document::with_undo()`
+///
+/// Every entry is the value at a path before it was modified (for the undo stack) or before the
+/// modification was undone (for the redo stack).
+#[derive(Debug, Clone, Default)]
+struct UndoStack {
+    limit: usize,
+    undo: Vec<(TomlPath, Value)>,
+    redo: Vec<(TomlPath, Value)>,
+}
+
+/// A handle to a state of a `Document`, see `Document::snapshot()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SnapshotId(u64);
+
+/// A handle to a value in a `Document` which is resolved again on every access
+///
+/// Get one via `Document::stable_path()` and resolve it with `Document::resolve()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StablePath {
+    path: TomlPath,
+    generation: u64,
+}
+
+impl StablePath {
+
+    /// The path the handle points to
+    pub fn path(&self) -> &TomlPath {
+        &self.path
+    }
+
+    /// The generation of the document when the handle was created
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+}
+
+impl Document {
+
+    pub fn new(value: Value) -> Document {
+        Document {
+            value,
+            seperator: '.',
+            generation: 0,
+            changes: Vec::new(),
+            change_limit: DEFAULT_CHANGE_LIMIT,
+            forgotten: 0,
+            index: None,
+            frozen: Vec::new(),
+            approver: None,
+            snapshots: Vec::new(),
+            next_snapshot: 0,
+            history: None,
+            deprecations: None,
+        }
+    }
+
+    /// Use `sep` as the seperator for queries without an explicit seperator
+    pub fn with_seperator(mut self, sep: char) -> Document {
+        self.seperator = sep;
+        self
+    }
+
+    pub fn seperator(&self) -> char {
+        self.seperator
+    }
+
+    /// Ask `approver` before every modification through the document
+    ///
+    /// The approver gets the path of the modification, the kind of operation and, for `Set` and
+    /// `Insert`, the new value. If it returns `false`, the modification fails with
+    /// `Error::PermissionDenied` and the document is not changed. Frozen values are checked
+    /// first, the approver is not asked about modifications of them.
+    pub fn with_approver<F>(mut self, approver: F) -> Document
+        where F: Fn(&TomlPath, Operation, Option<&Value>) -> bool + Send + Sync + 'static
+    {
+        self.approver = Some(Approver(Arc::new(approver)));
+        self
+    }
+
+    /// Remember the last `limit` modifications for `Document::resolve()`
+    ///
+    /// A `StablePath` created before the oldest remembered modification can not be checked
+    /// anymore and resolves to `Error::ValueReplaced`.
+    pub fn with_change_limit(mut self, limit: usize) -> Document {
+        self.change_limit = limit;
+        self.forget_changes();
+        self
+    }
+
+    /// Keep a history of the last `limit` modifications, see `Document::undo()`
+    ///
+    /// Every entry of the history holds a copy of the table or array which was modified, or of
+    /// the whole document for modifications of top level keys.
+    pub fn with_undo(mut self, limit: usize) -> Document {
+        self.history = Some(UndoStack { limit, ..UndoStack::default() });
+        self
+    }
+
+    /// Resolve reads of the old paths of `deprecations` to their new paths
+    ///
+    /// Every such read logs a warning with the message of the deprecation. Modifications are not
+    /// redirected, except through `read_mut()`.
+    pub fn with_deprecations(mut self, deprecations: Deprecations) -> Document {
+        self.deprecations = Some(deprecations);
+        self
+    }
+
+    /// The wrapped value
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+
+    /// The number of modifications done through this document so far
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Check many boolean flags at once, see `TomlValueReadTypeExt::flags_enabled()`
+    ///
+    /// Unlike the trait method, this uses the seperator of the document.
+    pub fn flags_enabled(&self, queries: &[&str]) -> Result<Vec<bool>> {
+        self.flags_enabled_with_seperator(queries, self.seperator)
+    }
+
+    /// Get a `StablePath` for the value at `query`
+    ///
+    /// Returns `Ok(None)` if there is no value at `query`.
+    pub fn stable_path(&self, query: &str) -> Result<Option<StablePath>> {
+        self.stable_path_with_seperator(query, self.seperator)
+    }
+
+    /// Get a `StablePath` for the value at `query`, using a custom seperator
+    pub fn stable_path_with_seperator(&self, query: &str, sep: char) -> Result<Option<StablePath>> {
+        if self.value.read_with_seperator(query, sep)?.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(StablePath {
+            path: TomlPath::from_query_in(query, sep, &self.value)?,
+            generation: self.generation,
+        }))
+    }
+
+    /// Get the value `stable` points to
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(value)` if the value was not touched by any modification since `stable` was created.
+    ///   Modifications of values below it do not count.
+    /// * `Err(Error::ValueReplaced(_))` if the value, or one of the values above it, was set,
+    ///   inserted or mutably borrowed in the meantime, or it moved because an array element
+    ///   before it was inserted or deleted. Get a new `StablePath` to access the new value.
+    ///   This is also returned if `stable` is older than the modifications the document
+    ///   remembers, see `Document::with_change_limit()`.
+    /// * `Err(Error::ValueRemoved(_))` if there is no value at the path anymore
+    pub fn resolve(&self, stable: &StablePath) -> Result<&Value> {
+        let query = stable.path.to_string();
+        let value = self.value.read(&query)?;
+
+        let stale = stable.generation < self.forgotten || self.changes
+            .iter()
+            .rev()
+            .take_while(|c| c.generation > stable.generation)
+            .any(|c| c.affects(&stable.path));
+
+        match value {
+            None                => Err(Error::ValueRemoved(query)),
+            Some(_) if stale    => Err(Error::ValueReplaced(query)),
+            Some(v)             => Ok(v),
+        }
+    }
+
+    /// Index the paths of all values of the document, so reads of them skip resolving the query
+    ///
+    /// The index is patched on every modification done through the document: the values in the
+    /// table or array which was modified are indexed again. Values below a value borrowed with
+    /// `read_mut()` are read without the index until the next modification of a table or array
+    /// above them, or until the index is built again.
+    pub fn build_index(&mut self) {
+        self.index = Some(PathIndex::build(&self.value));
+    }
+
+    pub fn drop_index(&mut self) {
+        self.index = None;
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Protect the value at `query`, and everything below it, from modifications
+    ///
+    /// Modifications through the document which would change, remove or move a frozen value
+    /// fail with `Error::FrozenPath`: modifications at or below it, modifications which replace
+    /// or delete a table or array above it, `read_mut()` of any of these values, and inserting or
+    /// deleting an array element before it. There does not need to be a value at `query` yet.
+    pub fn freeze(&mut self, query: &str) -> Result<()> {
+        let path = TomlPath::from_query(query, self.seperator)?;
+        if !self.frozen.contains(&path) {
+            self.frozen.push(path);
+        }
+        Ok(())
+    }
+
+    /// Allow modifications of the value at `query` again
+    ///
+    /// Returns whether `query` was frozen. Values frozen via other queries stay frozen.
+    pub fn unfreeze(&mut self, query: &str) -> Result<bool> {
+        let path = TomlPath::from_query(query, self.seperator)?;
+        let len  = self.frozen.len();
+        self.frozen.retain(|f| *f != path);
+        Ok(self.frozen.len() != len)
+    }
+
+    /// Modify the value at `query` with `f`, keeping the modification only if it is allowed
+    ///
+    /// Unlike `read_mut()`, which is checked before the value is handed out, `f` may do anything
+    /// with the value. Afterwards, the modified value is checked like a replacement of the value:
+    /// it must not change frozen values, and the approver must allow `Operation::RawMut` with the
+    /// modified value. If it is not allowed, the value is left as it was before `f` was called.
+    ///
+    /// A modification counts like `set()` of the modified value. If `f` does not change the
+    /// value, nothing is checked or recorded.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(result)` with what `f` returned
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * `Err(Error::FrozenPath(_))` if a frozen value was changed
+    /// * `Err(Error::PermissionDenied(_, _))` if the approver denies the modification
+    pub fn with_raw_mut<F, T>(&mut self, query: &str, f: F) -> Result<T>
+        where F: FnOnce(&mut Value) -> T
+    {
+        let path = TomlPath::from_query_in(query, self.seperator, &self.value)?;
+
+        // Modified on a copy, so the document only changes if the modification is allowed
+        let mut value = match value_at(&self.value, path.segments()) {
+            Some(current) => current.clone(),
+            None          => return Err(Error::NotAvailable(String::from(query))),
+        };
+        let result = f(&mut value);
+
+        if value_at(&self.value, path.segments()) != Some(&value) {
+            self.check_replace(&path, &value, Operation::RawMut)?;
+            let undo = self.remember(&path);
+            let _    = self.replace(&path, value);
+            self.push_undo(undo);
+        }
+        Ok(result)
+    }
+
+    /// Remember the current value of the document, to get back to it with `Document::restore()`
+    ///
+    /// The snapshot is kept until it is discarded with `Document::discard_snapshot()`, so the
+    /// document can be restored to it more than once.
+    pub fn snapshot(&mut self) -> SnapshotId {
+        let id = SnapshotId(self.next_snapshot);
+        self.next_snapshot += 1;
+        self.snapshots.push((id, self.value.clone()));
+        id
+    }
+
+    /// Replace the value of the document with the value it had when `id` was taken
+    ///
+    /// Restoring counts as a modification of the whole document, so all `StablePath`s taken
+    /// before are stale afterwards.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::UnknownSnapshot(_))` if `id` was discarded or taken from another document
+    /// * `Err(Error::FrozenPath(_))` if a frozen value would change
+    /// * `Err(Error::PermissionDenied(_, _))` if the approver denies `Operation::Restore`
+    pub fn restore(&mut self, id: SnapshotId) -> Result<()> {
+        let value = match self.snapshots.iter().find(|s| s.0 == id) {
+            Some(&(_, ref value)) => value.clone(),
+            None                  => return Err(Error::UnknownSnapshot(id.0)),
+        };
+
+        let root = TomlPath::new();
+        self.check_replace(&root, &value, Operation::Restore)?;
+        let undo = self.remember(&root);
+        self.replace(&root, value);
+        self.push_undo(undo);
+        Ok(())
+    }
+
+    /// Forget the snapshot `id`
+    ///
+    /// Returns whether there was such a snapshot.
+    pub fn discard_snapshot(&mut self, id: SnapshotId) -> bool {
+        let len = self.snapshots.len();
+        self.snapshots.retain(|s| s.0 != id);
+        self.snapshots.len() != len
+    }
+
+    /// Take back the last modification
+    ///
+    /// Returns `Ok(false)` if there is nothing to undo, which is always the case without
+    /// `Document::with_undo()`. Undoing counts as a modification of the table or array which was
+    /// modified, and can be taken back with `Document::redo()` until the next other modification.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::FrozenPath(_))` if a frozen value would change
+    /// * `Err(Error::PermissionDenied(_, _))` if the approver denies `Operation::Restore`
+    pub fn undo(&mut self) -> Result<bool> {
+        self.step_history(true)
+    }
+
+    /// Do the last modification taken back with `Document::undo()` again
+    ///
+    /// Returns `Ok(false)` if there is nothing to redo, see `Document::undo()` for the errors.
+    pub fn redo(&mut self) -> Result<bool> {
+        self.step_history(false)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.as_ref().map(|h| !h.undo.is_empty()).unwrap_or(false)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.as_ref().map(|h| !h.redo.is_empty()).unwrap_or(false)
+    }
+
+    /// Move the last entry of the undo (or redo) stack to the other stack, swapping the value
+    /// of the entry with the value in the document
+    fn step_history(&mut self, undo: bool) -> Result<bool> {
+        let (path, value) = {
+            let entry = self.history.as_ref().and_then(|h| if undo { h.undo.last() } else { h.redo.last() });
+            match entry {
+                Some(&(ref path, ref value)) => {
+                    self.check_replace(path, value, Operation::Restore)?;
+                    (path.clone(), value.clone())
+                },
+                None => return Ok(false),
+            }
+        };
+
+        let current = self.replace(&path, value);
+        if let Some(ref mut history) = self.history {
+            let (from, to) = if undo {
+                (&mut history.undo, &mut history.redo)
+            } else {
+                (&mut history.redo, &mut history.undo)
+            };
+            let _ = from.pop();
+            to.push((path, current));
+        }
+        Ok(true)
+    }
+
+    /// Copy the value at `path` for the undo stack, if there is one
+    fn remember(&self, path: &TomlPath) -> Option<(TomlPath, Value)> {
+        self.history.as_ref()?;
+        value_at(&self.value, path.segments()).map(|v| (path.clone(), v.clone()))
+    }
+
+    /// Push an entry from `Document::remember()` to the undo stack, which clears the redo stack
+    fn push_undo(&mut self, entry: Option<(TomlPath, Value)>) {
+        if let (Some(history), Some(entry)) = (self.history.as_mut(), entry) {
+            history.undo.push(entry);
+            if history.undo.len() > history.limit {
+                let excess = history.undo.len() - history.limit;
+                let _ = history.undo.drain(..excess);
+            }
+            history.redo.clear();
+        }
+    }
+
+    /// Put `value` at `path`, where there must be a value already, and return the old value
+    fn replace(&mut self, path: &TomlPath, value: Value) -> Value {
+        let old = match value_at_mut(&mut self.value, path.segments()) {
+            Some(current) => ::std::mem::replace(current, value),
+            None          => unreachable!("Replaced value at '{}' vanished", path),
+        };
+        self.patch_index(path);
+        self.record(path.clone(), false);
+        old
+    }
+
+    /// Check whether replacing the value at `path` with `value` is allowed
+    ///
+    /// It is not if a frozen value would change, or if the approver denies it.
+    fn check_replace(&self, path: &TomlPath, value: &Value, op: Operation) -> Result<()> {
+        for frozen in self.frozen.iter() {
+            let (old, new) = if frozen.starts_with(path) {
+                let below = &frozen.segments()[path.len()..];
+                (value_at(&self.value, frozen.segments()), value_at(value, below))
+            } else if path.starts_with(frozen) {
+                (value_at(&self.value, path.segments()), Some(value))
+            } else {
+                continue;
+            };
+
+            if old != new {
+                return Err(Error::FrozenPath(frozen.to_string()));
+            }
+        }
+
+        match self.approver {
+            Some(ref approver) if !(approver.0)(path, op, Some(value)) => {
+                Err(Error::PermissionDenied(op.name(), path.to_string()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Check whether the modification `op` at `path` is allowed
+    ///
+    /// It is not if it would touch a frozen value, or if the approver denies it.
+    fn check_allowed(&self, path: &TomlPath, op: Operation, value: Option<&Value>) -> Result<()> {
+        let shifts = is_match!(op, Operation::Insert | Operation::Delete);
+        if let Some(frozen) = self.frozen.iter().find(|f| path.starts_with(f) || affects(path, shifts, f)) {
+            return Err(Error::FrozenPath(frozen.to_string()));
+        }
+
+        match self.approver {
+            Some(ref approver) if !(approver.0)(path, op, value) => {
+                Err(Error::PermissionDenied(op.name(), path.to_string()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Index the values below `path` again
+    fn patch_index(&mut self, path: &TomlPath) {
+        if let Some(ref mut index) = self.index {
+            let query = path.to_string();
+            let value = if path.is_empty() {
+                Some(&self.value)
+            } else {
+                self.value.read(&query).ok().and_then(|v| v)
+            };
+
+            match value {
+                Some(value) => index.patch(value, path),
+                None        => index.remove_below(path),
+            }
+        }
+    }
+
+    fn record(&mut self, path: TomlPath, shifts: bool) {
+        self.generation += 1;
+        self.changes.push(Change {
+            generation: self.generation,
+            path,
+            shifts,
+        });
+        self.forget_changes();
+    }
+
+    /// Drop the oldest changes above the change limit
+    fn forget_changes(&mut self) {
+        if self.changes.len() > self.change_limit {
+            let excess = self.changes.len() - self.change_limit;
+            self.forgotten = self.changes[excess - 1].generation;
+            let _ = self.changes.drain(..excess);
+        }
+    }
+
+}
+
+impl From<Value> for Document {
+    fn from(value: Value) -> Document {
+        Document::new(value)
+    }
+}
+
+/// The deepest value above `path` which exists in `value`
+///
+/// Modifications at `path` can only move values below it.
+fn existing_parent(value: &Value, path: &TomlPath) -> TomlPath {
+    let mut parent = path.parent().unwrap_or_default();
+    while !parent.is_empty() && !is_match!(value.read(&parent.to_string()), Ok(Some(_))) {
+        let _ = parent.pop();
+    }
+    parent
+}
+
+impl Document {
+
+    /// The query to read instead of `query`, if it is deprecated
+    fn redirect(&self, query: &str, sep: char) -> Result<Option<String>> {
+        let deprecations = match self.deprecations {
+            Some(ref deprecations) => deprecations,
+            None                   => return Ok(None),
+        };
+
+        // queries which do not name a single path can not be deprecated
+        let path = match TomlPath::from_query_in(query, sep, &self.value) {
+            Ok(path) => path,
+            Err(_)   => return Ok(None),
+        };
+        Ok(deprecations.resolve(&path).map(|(new, deprecation)| {
+            ::util::log_deprecated(&path, &new, deprecation.message());
+            new.to_string()
+        }))
+    }
+
+}
+
+impl<'doc> TomlValueReadExt<'doc> for Document {
+
+    fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        let redirected = self.redirect(query, sep)?;
+        let (query, sep) = match redirected {
+            Some(ref new) => (new.as_str(), '.'),
+            None          => (query, sep),
+        };
+
+        if let Some(ref index) = self.index {
+            // queries which do not name a single path are resolved without the index
+            if let Ok(path) = TomlPath::from_query(query, sep) {
+                if let Some(value) = index.get(&self.value, &path) {
+                    return Ok(Some(value));
+                }
+            }
+        }
+        self.value.read_with_seperator(query, sep)
+    }
+
+    fn read(&'doc self, query: &str) -> Result<Option<&'doc Value>> {
+        self.read_with_seperator(query, self.seperator)
+    }
+
+    fn read_mut(&'doc mut self, query: &str) -> Result<Option<&'doc mut Value>> {
+        let sep = self.seperator;
+        self.read_mut_with_seperator(query, sep)
+    }
+
+    fn keys_at(&'doc self, query: &str, max_depth: usize) -> Result<Vec<TomlPath>> {
+        let path = TomlPath::from_query_in(query, self.seperator, &self.value)?;
+        self.value.keys_at(&path.to_string(), max_depth)
+    }
+
+    fn serialize_at(&'doc self, query: &str) -> Result<String> {
+        let path = TomlPath::from_query_in(query, self.seperator, &self.value)?;
+        self.value.serialize_at(&path.to_string())
+    }
+
+    fn serialize_at_with(&'doc self, query: &str, style: &FormatStyle) -> Result<String> {
+        let path = TomlPath::from_query_in(query, self.seperator, &self.value)?;
+        self.value.serialize_at_with(&path.to_string(), style)
+    }
+
+    fn read_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Option<&'doc mut Value>> {
+        let redirected = self.redirect(query, sep)?;
+        let (query, sep) = match redirected {
+            Some(ref new) => (new.as_str(), '.'),
+            None          => (query, sep),
+        };
+
+        let path = TomlPath::from_query_in(query, sep, &self.value)?;
+        self.check_allowed(&path, Operation::ReadMut, None)?;
+        if self.value.read_with_seperator(query, sep)?.is_some() {
+            if let Some(ref mut index) = self.index {
+                index.remove_below(&path);
+            }
+            let undo = self.remember(&path);
+            self.push_undo(undo);
+            self.record(path, false);
+        }
+        self.value.read_mut_with_seperator(query, sep)
+    }
+
+}
+
+impl<'doc> TomlValueReadAllExt<'doc> for Document {
+
+    fn read_all_with_seperator(&'doc self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc Value)>> {
+        self.value.read_all_with_seperator(query, sep)
+    }
+
+    fn read_all(&'doc self, query: &str) -> Result<Vec<(TomlPath, &'doc Value)>> {
+        self.read_all_with_seperator(query, self.seperator)
+    }
+
+    fn read_all_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc mut Value)>> {
+        let paths = self.value.read_all_with_seperator(query, sep)?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+
+        for path in &paths {
+            self.check_allowed(path, Operation::ReadMut, None)?;
+        }
+        for path in paths {
+            if let Some(ref mut index) = self.index {
+                index.remove_below(&path);
+            }
+            let undo = self.remember(&path);
+            self.push_undo(undo);
+            self.record(path, false);
+        }
+        self.value.read_all_mut_with_seperator(query, sep)
+    }
+
+    fn read_all_mut(&'doc mut self, query: &str) -> Result<Vec<(TomlPath, &'doc mut Value)>> {
+        let sep = self.seperator;
+        self.read_all_mut_with_seperator(query, sep)
+    }
+
+}
+
+impl TomlValueSetExt for Document {
+
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        let path   = TomlPath::from_query_in(query, sep, &self.value)?;
+        self.check_allowed(&path, Operation::Set, Some(&value))?;
+        let parent = existing_parent(&self.value, &path);
+        let undo   = self.remember(&parent);
+        let old    = self.value.set_with_seperator(query, sep, value);
+        self.patch_index(&parent);
+        let old    = old?;
+        self.push_undo(undo);
+        self.record(path, false);
+        Ok(old)
+    }
+
+    fn set(&mut self, query: &str, value: Value) -> Result<Option<Value>> {
+        let sep = self.seperator;
+        self.set_with_seperator(query, sep, value)
+    }
+
+}
+
+impl TomlValueInsertExt for Document {
+
+    fn insert_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        let path   = TomlPath::from_query_in(query, sep, &self.value)?;
+        self.check_allowed(&path, Operation::Insert, Some(&value))?;
+        let parent = existing_parent(&self.value, &path);
+        let undo   = self.remember(&parent);
+        let old    = self.value.insert_with_seperator(query, sep, value);
+        self.patch_index(&parent); // failed inserts may have created tables
+        let old    = old?;
+        self.push_undo(undo);
+        self.record(path, true);
+        Ok(old)
+    }
+
+    fn insert(&mut self, query: &str, value: Value) -> Result<Option<Value>> {
+        let sep = self.seperator;
+        self.insert_with_seperator(query, sep, value)
+    }
+
+}
+
+impl TomlValueDeleteExt for Document {
+
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        let path   = TomlPath::from_query_in(query, sep, &self.value)?;
+        self.check_allowed(&path, Operation::Delete, None)?;
+        let parent = existing_parent(&self.value, &path);
+        let undo   = self.remember(&parent);
+        let old    = self.value.delete_with_seperator(query, sep);
+        self.patch_index(&parent);
+        let old    = old?;
+        self.push_undo(undo);
+        self.record(path, true);
+        Ok(old)
+    }
+
+    fn delete(&mut self, query: &str) -> Result<Option<Value>> {
+        let sep = self.seperator;
+        self.delete_with_seperator(query, sep)
+    }
+
+    fn prune_at(&mut self, query: &str) -> Result<Vec<TomlPath>> {
+        let path    = TomlPath::from_query_in(query, self.seperator, &self.value)?;
+        self.check_allowed(&path, Operation::Prune, None)?;
+        let undo    = self.remember(&path);
+        let removed = self.value.prune_at(&path.to_string())?;
+        self.patch_index(&path);
+        if !removed.is_empty() {
+            self.push_undo(undo);
+        }
+        for path in removed.iter() {
+            self.record(path.clone(), true);
+        }
+        Ok(removed)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Document {
+        Document::new(toml_from_str(r#"
+        [a]
+        b = 1
+        c = [ 1, 2, 3 ]
+
+        [d]
+        e = "e"
+        "#).unwrap())
+    }
+
+    #[test]
+    fn test_unmodified() {
+        let doc = doc();
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+        assert_eq!(doc.resolve(&stable).unwrap(), &Value::Integer(1));
+        assert!(doc.stable_path("a.x").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unrelated_modification() {
+        let mut doc = doc();
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+
+        let _ = doc.set("d.e", Value::Integer(2)).unwrap();
+        let _ = doc.insert("a.x", Value::Integer(2)).unwrap();
+
+        assert_eq!(doc.generation(), 2);
+        assert_eq!(doc.resolve(&stable).unwrap(), &Value::Integer(1));
+    }
+
+    #[test]
+    fn test_modification_below() {
+        let mut doc = doc();
+        let stable = doc.stable_path("a").unwrap().unwrap();
+        let _ = doc.set("a.b", Value::Integer(2)).unwrap();
+        assert!(doc.resolve(&stable).is_ok());
+    }
+
+    #[test]
+    fn test_replaced() {
+        let mut doc = doc();
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+        let _ = doc.set("a.b", Value::Integer(2)).unwrap();
+
+        let res = doc.resolve(&stable);
+        assert!(is_match!(res, Err(Error::ValueReplaced(_))));
+
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+        assert_eq!(doc.resolve(&stable).unwrap(), &Value::Integer(2));
+    }
+
+    #[test]
+    fn test_parent_replaced() {
+        let mut doc = doc();
+        let stable = doc.stable_path("d.e").unwrap().unwrap();
+        let _ = doc.set("d", toml_from_str::<Value>("e = 'new'").unwrap()).unwrap();
+
+        let res = doc.resolve(&stable);
+        assert!(is_match!(res, Err(Error::ValueReplaced(_))));
+    }
+
+    #[test]
+    fn test_read_mut_counts_as_modification() {
+        let mut doc = doc();
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+        let _ = doc.read_mut("a.b").unwrap();
+
+        let res = doc.resolve(&stable);
+        assert!(is_match!(res, Err(Error::ValueReplaced(_))));
+    }
+
+    #[test]
+    fn test_removed() {
+        let mut doc = doc();
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+        let _ = doc.delete("a.b").unwrap();
+
+        let res = doc.resolve(&stable);
+        assert!(is_match!(res, Err(Error::ValueRemoved(_))));
+    }
+
+    #[test]
+    fn test_indices_from_end() {
+        let mut doc = doc();
+        doc.build_index();
+        assert_eq!(doc.read("a.c.[-1]").unwrap(), Some(&Value::Integer(3)));
+
+        let last = doc.stable_path("a.c.[last]").unwrap().unwrap();
+        assert_eq!(last.path().to_string(), "a.c.[2]");
+
+        assert_eq!(doc.set("a.c.[-1]", Value::Integer(4)).unwrap(), Some(Value::Integer(3)));
+        assert!(is_match!(doc.resolve(&last), Err(Error::ValueReplaced(_))));
+        let _ = doc.insert("a.c.[+]", Value::Integer(5)).unwrap();
+        assert_eq!(doc.read("a.c.[last]").unwrap(), Some(&Value::Integer(5)));
+        assert_eq!(doc.delete("a.c.[-2]").unwrap(), Some(Value::Integer(4)));
+        assert_eq!(doc.read("a.c.[-1]").unwrap(), Some(&Value::Integer(5)));
+        assert_eq!(doc.value().read("a.c").unwrap(), doc.read("a.c").unwrap());
+
+        doc.freeze("a.c.[0]").unwrap();
+        assert!(is_match!(doc.insert("a.c.[-3]", Value::Integer(0)), Err(Error::FrozenPath(_))));
+    }
+
+    #[test]
+    fn test_change_limit() {
+        let mut doc = doc().with_change_limit(2);
+        let old = doc.stable_path("a.b").unwrap().unwrap();
+        let _ = doc.set("d.e", Value::Integer(1)).unwrap();
+        let recent = doc.stable_path("a.b").unwrap().unwrap();
+        let _ = doc.set("d.e", Value::Integer(2)).unwrap();
+        let _ = doc.set("d.e", Value::Integer(3)).unwrap();
+
+        assert_eq!(doc.changes.len(), 2);
+        assert!(is_match!(doc.resolve(&old), Err(Error::ValueReplaced(_))));
+        assert_eq!(doc.resolve(&recent).unwrap(), &Value::Integer(1));
+    }
+
+    #[test]
+    fn test_array_shift() {
+        let mut doc = doc();
+        let first = doc.stable_path("a.c.[0]").unwrap().unwrap();
+        let last  = doc.stable_path("a.c.[2]").unwrap().unwrap();
+        let _ = doc.delete("a.c.[1]").unwrap();
+
+        assert_eq!(doc.resolve(&first).unwrap(), &Value::Integer(1));
+        let res = doc.resolve(&last);
+        assert!(is_match!(res, Err(Error::ValueRemoved(_))));
+
+        let middle = doc.stable_path("a.c.[1]").unwrap().unwrap();
+        let _ = doc.insert("a.c.[0]", Value::Integer(0)).unwrap();
+        let res = doc.resolve(&middle);
+        assert!(is_match!(res, Err(Error::ValueReplaced(_))));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut doc = doc();
+        doc.build_index();
+        assert_eq!(doc.read("a.c.[2]").unwrap(), Some(&Value::Integer(3)));
+
+        let _ = doc.insert("a.c.[0]", Value::Integer(0)).unwrap();
+        let _ = doc.insert("x.y.z", Value::Integer(1)).unwrap();
+        let _ = doc.delete("d.e").unwrap();
+        let _ = doc.set("a.b", toml_from_str::<Value>("f = 1").unwrap()).unwrap();
+        *doc.read_mut("a.b").unwrap().unwrap() = Value::Integer(5);
+
+        let moved = Box::new(doc);
+        for doc in &[moved.clone(), moved] {
+            for query in &["a.b", "a.b.f", "a.c.[0]", "a.c.[3]", "x.y.z", "d.e"] {
+                assert_eq!(doc.read(query).ok(), doc.value().read(query).ok(), "{}", query);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seperator() {
+        let mut doc = doc().with_seperator('/');
+        assert_eq!(doc.read("a/b").unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(doc.read_int("a/c/[1]").unwrap(), Some(2));
+        assert_eq!(doc.keys_at("a/c", 1).unwrap().len(), 3);
+        assert_eq!(doc.serialize_at("a/b").unwrap(), "b = 1\n");
+        assert_eq!(doc.read_with_seperator("a.b", '.').unwrap(), Some(&Value::Integer(1)));
+
+        let _ = doc.set("a/b", Value::Integer(2)).unwrap();
+        let _ = doc.insert("x/y", Value::Integer(3)).unwrap();
+        let _ = doc.delete("d/e").unwrap();
+        assert_eq!(doc.prune_at("d").unwrap().len(), 0);
+
+        let stable = doc.stable_path("x/y").unwrap().unwrap();
+        assert_eq!(doc.resolve(&stable).unwrap(), &Value::Integer(3));
+        assert_eq!(doc.value().read("a.b").unwrap(), Some(&Value::Integer(2)));
+        assert!(doc.value().read("d.e").unwrap().is_none());
+
+        let _ = doc.insert("a/on", Value::Boolean(true)).unwrap();
+        assert_eq!(doc.flags_enabled(&["a/on", "a/off", "off"]).unwrap(), vec![true, false, false]);
+        assert!(is_match!(doc.flags_enabled(&["a/b"]), Err(Error::TypeErrorAt(ref q, "Boolean", _)) if q == "a/b"));
+    }
+
+    #[test]
+    fn test_freeze() {
+        let mut doc = doc();
+        doc.freeze("a.c.[1]").unwrap();
+        doc.freeze("d").unwrap();
+
+        assert!(is_match!(doc.set("d.e", Value::Integer(1)), Err(Error::FrozenPath(_))));
+        assert!(is_match!(doc.insert("d.f", Value::Integer(1)), Err(Error::FrozenPath(_))));
+        assert!(is_match!(doc.delete("a.c.[0]"), Err(Error::FrozenPath(_))));
+        assert!(is_match!(doc.insert("a.c.[1]", Value::Integer(1)), Err(Error::FrozenPath(_))));
+        assert!(is_match!(doc.set("a.c", Value::Integer(1)), Err(Error::FrozenPath(ref p)) if p == "a.c.[1]"));
+        assert!(is_match!(doc.read_mut("a"), Err(Error::FrozenPath(_))));
+        assert!(is_match!(doc.prune_at("d"), Err(Error::FrozenPath(_))));
+        assert_eq!(doc.generation(), 0);
+
+        assert!(doc.set("a.c.[0]", Value::Integer(0)).is_ok());
+        assert!(doc.delete("a.c.[2]").is_ok());
+        assert!(doc.insert("a.x", Value::Integer(1)).is_ok());
+        assert!(doc.read_mut("a.b").is_ok());
+
+        assert!(doc.unfreeze("d").unwrap());
+        assert!(!doc.unfreeze("d").unwrap());
+        assert!(doc.set("d.e", Value::Integer(1)).is_ok());
+    }
+
+    #[test]
+    fn test_approver() {
+        let mut doc = doc().with_approver(|path, op, value| {
+            let tenant = TomlPath::from(vec![Segment::Key(String::from("a"))]);
+            path.starts_with(&tenant) && op != Operation::Delete && value != Some(&Value::Integer(0))
+        });
+
+        assert!(doc.set("a.b", Value::Integer(2)).is_ok());
+        assert!(doc.insert("a.c.[0]", Value::Integer(5)).is_ok());
+        assert!(doc.read_mut("a.b").is_ok());
+
+        let res = doc.set("d.e", Value::Integer(2));
+        assert!(is_match!(res, Err(Error::PermissionDenied("set", ref p)) if p == "d.e"));
+        assert!(is_match!(doc.delete("a.b"), Err(Error::PermissionDenied("delete", _))));
+        assert!(is_match!(doc.set("a.b", Value::Integer(0)), Err(Error::PermissionDenied(_, _))));
+        assert!(is_match!(doc.clone().read_mut("d"), Err(Error::PermissionDenied("read_mut", _))));
+
+        assert_eq!(doc.generation(), 3);
+        assert_eq!(doc.read("d.e").unwrap(), Some(&Value::String(String::from("e"))));
+    }
+
+    #[test]
+    fn test_raw_mut() {
+        let mut doc = doc().with_undo(10);
+        doc.freeze("a.c.[0]").unwrap();
+
+        let len = doc.with_raw_mut("a.c", |v| {
+            let ary = v.as_array_mut().unwrap();
+            ary.push(Value::Integer(4));
+            ary.len()
+        }).unwrap();
+        assert_eq!(len, 4);
+        assert_eq!(doc.generation(), 1);
+
+        let res = doc.with_raw_mut("a", |v| v.as_table_mut().unwrap().remove("c"));
+        assert!(is_match!(res, Err(Error::FrozenPath(ref p)) if p == "a.c.[0]"));
+        assert_eq!(doc.read("a.c.[3]").unwrap(), Some(&Value::Integer(4)));
+
+        assert!(doc.with_raw_mut("a.b", |_| ()).is_ok());
+        assert!(is_match!(doc.with_raw_mut("x", |_| ()), Err(Error::NotAvailable(_))));
+        assert_eq!(doc.generation(), 1);
+
+        assert!(doc.undo().unwrap());
+        assert!(doc.read("a.c.[3]").unwrap().is_none());
+
+        let mut doc = doc.with_approver(|_, op, value| op != Operation::RawMut || value != Some(&Value::Integer(0)));
+        assert!(doc.with_raw_mut("a.b", |v| *v = Value::Integer(2)).is_ok());
+        let res = doc.with_raw_mut("a.b", |v| *v = Value::Integer(0));
+        assert!(is_match!(res, Err(Error::PermissionDenied("raw_mut", ref p)) if p == "a.b"));
+        assert_eq!(doc.read("a.b").unwrap(), Some(&Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut doc = doc();
+        doc.build_index();
+        let before = doc.snapshot();
+        let stable = doc.stable_path("a.b").unwrap().unwrap();
+
+        let _ = doc.set("a.b", Value::Integer(2)).unwrap();
+        let _ = doc.delete("d.e").unwrap();
+        let after = doc.snapshot();
+
+        doc.restore(before).unwrap();
+        assert_eq!(doc.value(), self::doc().value());
+        assert_eq!(doc.read("d.e").unwrap(), Some(&Value::String(String::from("e"))));
+        assert!(is_match!(doc.resolve(&stable), Err(Error::ValueReplaced(_))));
+
+        doc.restore(after).unwrap();
+        assert_eq!(doc.read("a.b").unwrap(), Some(&Value::Integer(2)));
+
+        doc.freeze("a.b").unwrap();
+        assert!(is_match!(doc.restore(before), Err(Error::FrozenPath(_))));
+        doc.freeze("a.c").unwrap();
+        assert!(doc.restore(after).is_ok());
+
+        assert!(doc.discard_snapshot(before));
+        assert!(!doc.discard_snapshot(before));
+        assert!(is_match!(doc.restore(before), Err(Error::UnknownSnapshot(0))));
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut doc = doc().with_undo(3);
+        doc.build_index();
+        assert!(!doc.undo().unwrap());
+
+        let _ = doc.insert("a.c.[0]", Value::Integer(0)).unwrap();
+        let _ = doc.insert("x.y", Value::Integer(1)).unwrap();
+        let _ = doc.delete("d.e").unwrap();
+        *doc.read_mut("a.b").unwrap().unwrap() = Value::Integer(5);
+
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.read("a.b").unwrap(), Some(&Value::Integer(1)));
+        assert!(doc.undo().unwrap());
+        assert!(doc.undo().unwrap());
+        assert!(doc.read("x").unwrap().is_none());
+        assert_eq!(doc.read("d.e").unwrap(), Some(&Value::String(String::from("e"))));
+
+        // the first insert was dropped because of the limit
+        assert!(!doc.can_undo());
+        assert_eq!(doc.read("a.c.[0]").unwrap(), Some(&Value::Integer(0)));
+
+        assert!(doc.redo().unwrap());
+        assert_eq!(doc.read("x.y").unwrap(), Some(&Value::Integer(1)));
+
+        let _ = doc.set("a.b", Value::Integer(2)).unwrap();
+        assert!(!doc.can_redo());
+
+        doc.freeze("a.b").unwrap();
+        assert!(is_match!(doc.undo(), Err(Error::FrozenPath(_))));
+        assert_eq!(doc.generation(), 9);
+    }
+
+    #[test]
+    fn test_deprecations() {
+        let deprecations = Deprecations::new()
+            .deprecate("old", "a", "Renamed to 'a'").unwrap()
+            .deprecate("d.f", "d.e", "Renamed to 'd.e'").unwrap();
+        let mut doc = doc().with_deprecations(deprecations).with_seperator('/');
+        doc.build_index();
+
+        assert_eq!(doc.read("old/b").unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(doc.read_with_seperator("d.f", '.').unwrap(), Some(&Value::String(String::from("e"))));
+        assert_eq!(doc.read("old").unwrap(), doc.read("a").unwrap());
+        assert!(doc.read("old/x").unwrap().is_none());
+
+        *doc.read_mut("old/b").unwrap().unwrap() = Value::Integer(2);
+        assert_eq!(doc.read("a/b").unwrap(), Some(&Value::Integer(2)));
+        assert_eq!(doc.generation(), 1);
+    }
+
+    #[test]
+    fn test_path() {
+        use path::Path;
+
+        let mut doc = doc();
+        let path = Path::new().key("a").key("{b,c}");
+
+        assert!(doc.insert_path(&path, Value::Integer(1)).unwrap().is_none());
+        assert_eq!(doc.read_path(&path).unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(doc.read("a.b").unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(doc.delete_path(&path).unwrap(), Some(Value::Integer(1)));
+        assert_eq!(doc.generation(), 2);
+    }
+
+    #[test]
+    fn test_failed_modification() {
+        let mut doc = doc();
+        assert!(doc.delete("a").is_err());
+        assert_eq!(doc.generation(), 0);
+    }
+
+}
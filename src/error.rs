@@ -1,5 +1,11 @@
 /// Error types
 
+#[cfg(feature = "parser")]
+use std::ops::Range;
+
+#[cfg(feature = "parser")]
+use tokenizer::{Token, tokenize_with_spans};
+
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 #[derive(Debug, Fail)]
@@ -64,5 +70,466 @@ pub enum Error {
     #[fail(display = "Value at '{}' not there", _0)]
     NotAvailable(String),
 
+    #[fail(display = "Type Error at '{}'. Requested {}, but got {}", _0, _1, _2)]
+    TypeErrorAt(String, &'static str, &'static str),
+
+    #[fail(display = "Value at '{}' does not satisfy constraint: {}", _0, _1)]
+    ValidationFailed(String, String),
+
+    #[fail(display = "Value at '{}' was removed", _0)]
+    ValueRemoved(String),
+
+    #[fail(display = "Value at '{}' was replaced", _0)]
+    ValueReplaced(String),
+
+    #[fail(display = "Parsing the filter '{}' failed: {}", _0, _1)]
+    FilterParsingError(String, String),
+
+    #[fail(display = "Unknown function '{}'", _0)]
+    UnknownFunction(String),
+
+    #[fail(display = "Function '{}' takes {} argument(s), but got {}", _0, _1, _2)]
+    WrongNumberOfArguments(String, usize, usize),
+
+    #[fail(display = "{}", _0)]
+    Io(#[cause] ::std::io::Error),
+
+    #[fail(display = "Parse error in line {}: {}", _0, _1)]
+    StreamingParseError(usize, String),
+
+    #[fail(display = "Value at '{}' is not in the expected document", _0)]
+    ExtraValue(String),
+
+    #[fail(display = "Value at '{}' is {}, expected {}", _0, _2, _1)]
+    ValueMismatch(String, String, String),
+
+    #[fail(display = "Value at '{}' is {}, expected true/false, \"yes\"/\"no\", \"on\"/\"off\" or 0/1", _0, _1)]
+    InvalidBoolean(String, String),
+
+    #[fail(display = "Value at '{}' is frozen", _0)]
+    FrozenPath(String),
+
+    #[fail(display = "Permission denied: {} at '{}'", _0, _1)]
+    PermissionDenied(&'static str, String),
+
+    #[fail(display = "Insert exceeds the limit of {} {}: {}", _1, _0, _2)]
+    LimitExceeded(&'static str, usize, usize),
+
+    #[fail(display = "Snapshot {} does not exist", _0)]
+    UnknownSnapshot(u64),
+
+    #[fail(display = "Cannot serialize the value at '{}': {}", _0, _1)]
+    CannotSerialize(String, String),
+
+    #[fail(display = "Parsing the fragment for '{}' failed: {}", _0, _1)]
+    FragmentParseError(String, String),
+
+    #[fail(display = "Loading the reference '{}' failed: {}", _0, _1)]
+    ReferenceLoadFailed(String, String),
+
+    #[fail(display = "The reference '{}' refers to itself", _0)]
+    ReferenceCycle(String),
+
+    #[fail(display = "Fetching the secret for '{}' failed: {}", _0, _1)]
+    SecretUnavailable(String, String),
+
+    #[fail(display = "Value at '{}' is {}, which is not a number", _0, _1)]
+    InvalidNumber(String, String),
+
+    #[fail(display = "Value at '{}' is {}, which is not exactly representable as {}", _0, _1, _2)]
+    PrecisionLoss(String, String, &'static str),
+
+    #[fail(display = "Index -{} is out of bounds for an array of length {}", _0, _1)]
+    IndexFromEndOutOfBounds(usize, usize),
+
+    #[fail(display = "Index -{} counts from the end of an array and cannot be part of a path", _0)]
+    IndexFromEndInPath(usize),
+
+    #[fail(display = "Expected an array of length {}, found one of length {}", _0, _1)]
+    ArrayLengthMismatch(usize, usize),
+
+    #[fail(display = "Writing at '{}' would replace a {} with a {}", _0, _1, _2)]
+    WouldShadowTable(String, &'static str, &'static str),
+
+    #[fail(display = "Segments like '*', '{{a,b}}' and '[?(..)]' match several values, they can only be used in queries for read_all()")]
+    UnexpectedMultiMatch,
+
+    #[fail(display = "Renaming would overwrite the value at '{}'", _0)]
+    RenameConflict(String),
+
+    #[fail(display = "Query has {} segments, more than the maximum of {}", _0, _1)]
+    QueryTooDeep(usize, usize),
+
+    #[fail(display = "'{}' is not part of the basic query syntax", _0)]
+    ExtendedSyntax(String),
+
+    #[fail(display = "Resolving the reference '{}' exceeds the limit of {} references", _0, _1)]
+    ReferenceLimitExceeded(String, usize),
+
+    #[fail(display = "Parsing the regex '{}' failed: {}", _0, _1)]
+    RegexParsingError(String, String),
+
+}
+
+impl Error {
+
+    /// A stable code identifying the kind of the error
+    ///
+    /// Codes never change their meaning and are not reused, so applications can match on them
+    /// instead of on the variants of this enum. New kinds of errors get the next free code.
+    ///
+    /// | Code     | Variant |
+    /// |----------|---------|
+    /// | `TQ0001` | `QueryParsingError` |
+    /// | `TQ0002` | `EmptyQueryError` |
+    /// | `TQ0003` | `EmptyIdentifier` |
+    /// | `TQ0004` | `ArrayAccessWithoutIndex` |
+    /// | `TQ0005` | `ArrayAccessWithInvalidIndex` |
+    /// | `TQ0006` | `IdentifierNotFoundInDocument` |
+    /// | `TQ0007` | `NoIndexInTable` |
+    /// | `TQ0008` | `NoIdentifierInArray` |
+    /// | `TQ0009` | `QueryingValueAsTable` |
+    /// | `TQ0010` | `QueryingValueAsArray` |
+    /// | `TQ0011` | `CannotDeleteNonEmptyTable` |
+    /// | `TQ0012` | `CannotDeleteNonEmptyArray` |
+    /// | `TQ0013` | `CannotAccessBecauseTypeMismatch` |
+    /// | `TQ0014` | `ArrayIndexOutOfBounds` |
+    /// | `TQ0015` | `TypeError` |
+    /// | `TQ0016` | `NotAvailable` |
+    /// | `TQ0017` | `TomlSerialize` |
+    /// | `TQ0018` | `TomlDeserialize` |
+    /// | `TQ0019` | `TypeErrorAt` |
+    /// | `TQ0020` | `ValidationFailed` |
+    /// | `TQ0021` | `ValueRemoved` |
+    /// | `TQ0022` | `ValueReplaced` |
+    /// | `TQ0023` | `FilterParsingError` |
+    /// | `TQ0024` | `UnknownFunction` |
+    /// | `TQ0025` | `WrongNumberOfArguments` |
+    /// | `TQ0026` | `Io` |
+    /// | `TQ0027` | `StreamingParseError` |
+    /// | `TQ0028` | `ExtraValue` |
+    /// | `TQ0029` | `ValueMismatch` |
+    /// | `TQ0030` | `InvalidBoolean` |
+    /// | `TQ0031` | `FrozenPath` |
+    /// | `TQ0032` | `PermissionDenied` |
+    /// | `TQ0033` | `LimitExceeded` |
+    /// | `TQ0034` | `UnknownSnapshot` |
+    /// | `TQ0035` | `CannotSerialize` |
+    /// | `TQ0036` | `FragmentParseError` |
+    /// | `TQ0037` | `ReferenceLoadFailed` |
+    /// | `TQ0038` | `ReferenceCycle` |
+    /// | `TQ0039` | `SecretUnavailable` |
+    /// | `TQ0040` | `InvalidNumber` |
+    /// | `TQ0041` | `PrecisionLoss` |
+    /// | `TQ0042` | `IndexFromEndOutOfBounds` |
+    /// | `TQ0043` | `IndexFromEndInPath` |
+    /// | `TQ0044` | `ArrayLengthMismatch` |
+    /// | `TQ0045` | `WouldShadowTable` |
+    /// | `TQ0046` | `UnexpectedMultiMatch` |
+    /// | `TQ0047` | `RenameConflict` |
+    /// | `TQ0048` | `QueryTooDeep` |
+    /// | `TQ0049` | `ExtendedSyntax` |
+    /// | `TQ0050` | `ReferenceLimitExceeded` |
+    /// | `TQ0051` | `RegexParsingError` |
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Error::QueryParsingError(_)                  => "TQ0001",
+            Error::EmptyQueryError                       => "TQ0002",
+            Error::EmptyIdentifier                       => "TQ0003",
+            Error::ArrayAccessWithoutIndex               => "TQ0004",
+            Error::ArrayAccessWithInvalidIndex           => "TQ0005",
+            Error::IdentifierNotFoundInDocument(_)       => "TQ0006",
+            Error::NoIndexInTable(_)                     => "TQ0007",
+            Error::NoIdentifierInArray(_)                => "TQ0008",
+            Error::QueryingValueAsTable(_)               => "TQ0009",
+            Error::QueryingValueAsArray(_)               => "TQ0010",
+            Error::CannotDeleteNonEmptyTable(_)          => "TQ0011",
+            Error::CannotDeleteNonEmptyArray(_)          => "TQ0012",
+            Error::CannotAccessBecauseTypeMismatch(_, _) => "TQ0013",
+            Error::ArrayIndexOutOfBounds(_, _)           => "TQ0014",
+            Error::TypeError(_, _)                       => "TQ0015",
+            Error::NotAvailable(_)                       => "TQ0016",
+            #[cfg(feature = "typed")]
+            Error::TomlSerialize(_)                      => "TQ0017",
+            #[cfg(feature = "typed")]
+            Error::TomlDeserialize(_)                    => "TQ0018",
+            Error::TypeErrorAt(_, _, _)                  => "TQ0019",
+            Error::ValidationFailed(_, _)                => "TQ0020",
+            Error::ValueRemoved(_)                       => "TQ0021",
+            Error::ValueReplaced(_)                      => "TQ0022",
+            Error::FilterParsingError(_, _)              => "TQ0023",
+            Error::UnknownFunction(_)                    => "TQ0024",
+            Error::WrongNumberOfArguments(_, _, _)       => "TQ0025",
+            Error::Io(_)                                 => "TQ0026",
+            Error::StreamingParseError(_, _)             => "TQ0027",
+            Error::ExtraValue(_)                         => "TQ0028",
+            Error::ValueMismatch(_, _, _)                => "TQ0029",
+            Error::InvalidBoolean(_, _)                  => "TQ0030",
+            Error::FrozenPath(_)                         => "TQ0031",
+            Error::PermissionDenied(_, _)                => "TQ0032",
+            Error::LimitExceeded(_, _, _)                => "TQ0033",
+            Error::UnknownSnapshot(_)                    => "TQ0034",
+            Error::CannotSerialize(_, _)                 => "TQ0035",
+            Error::FragmentParseError(_, _)              => "TQ0036",
+            Error::ReferenceLoadFailed(_, _)             => "TQ0037",
+            Error::ReferenceCycle(_)                     => "TQ0038",
+            Error::SecretUnavailable(_, _)               => "TQ0039",
+            Error::InvalidNumber(_, _)                   => "TQ0040",
+            Error::PrecisionLoss(_, _, _)                => "TQ0041",
+            Error::IndexFromEndOutOfBounds(_, _)         => "TQ0042",
+            Error::IndexFromEndInPath(_)                 => "TQ0043",
+            Error::ArrayLengthMismatch(_, _)             => "TQ0044",
+            Error::WouldShadowTable(_, _, _)             => "TQ0045",
+            Error::UnexpectedMultiMatch                  => "TQ0046",
+            Error::RenameConflict(_)                     => "TQ0047",
+            Error::QueryTooDeep(_, _)                    => "TQ0048",
+            Error::ExtendedSyntax(_)                     => "TQ0049",
+            Error::ReferenceLimitExceeded(_, _)          => "TQ0050",
+            Error::RegexParsingError(_, _)               => "TQ0051",
+        }
+    }
+
+    /// A message for this error from running `query`, with `sep` as seperator, with the failing
+    /// segment underlined
+    ///
+    /// ```text
+    /// error[TQ0006]: The identfier 'c' is not present in the document
+    ///   |
+    ///   | a.b.c.d
+    ///   |     ^
+    /// ```
+    ///
+    /// The failing segment is the first one with the key or index named by the error. Errors
+    /// which do not name one are rendered without the underline. `render_in()` finds the segment
+    /// by resolving `query` in the document instead, and notes what was found there.
+    #[cfg(feature = "parser")]
+    pub fn render(&self, query: &str, sep: char) -> String {
+        let span = tokenize_with_spans(query, sep).ok().and_then(|(tokens, spans)| {
+            tokens.iter().position(|token| self.names_token(token)).map(|i| spans[i].clone())
+        });
+
+        render_message(self, query, span, &self.notes())
+    }
+
+    /// Like `render()`, with the failing segment and notes about it taken from `document`
+    ///
+    /// The failing segment is the first one which does not resolve in `document`. The notes name
+    /// the type of the value it was looked up in and the keys of that value, if it is a table. If
+    /// the whole query resolves, the last segment is underlined and the type of its value noted.
+    #[cfg(feature = "value")]
+    pub fn render_in(&self, query: &str, sep: char, document: &::toml::Value) -> String {
+        use resolver::non_mut_resolver::resolve;
+        use toml::Value;
+
+        /// How many keys of a table are listed
+        const MAX_KEYS: usize = 10;
+
+        let (tokens, spans) = match tokenize_with_spans(query, sep) {
+            Ok(t)  => t,
+            Err(_) => return render_message(self, query, None, &self.notes()),
+        };
+
+        let mut current = document;
+        let mut failed  = None;
+        for i in 0..tokens.len() {
+            match resolve(current, &tokens[i..i + 1], false) {
+                Ok(Some(value)) => current = value,
+                _               => {
+                    failed = Some(i);
+                    break;
+                },
+            }
+        }
+
+        let mut notes = vec![format!("found {}", ::util::name_of_val(current))];
+        if failed.is_some() {
+            match *current {
+                Value::Table(ref t) => {
+                    let mut keys : Vec<&str> = t.keys().take(MAX_KEYS).map(String::as_str).collect();
+                    if t.len() > MAX_KEYS {
+                        keys.push("...");
+                    }
+                    notes.push(format!("available keys: {}", keys.join(", ")));
+                },
+                Value::Array(ref a) => notes.push(format!("the array has {} element(s)", a.len())),
+                _                   => { },
+            }
+        }
+
+        let span = spans.get(failed.unwrap_or(tokens.len() - 1)).cloned();
+        render_message(self, query, span, &notes)
+    }
+
+    /// Whether this error names the key or index of `token`
+    #[cfg(feature = "parser")]
+    fn names_token(&self, token: &Token) -> bool {
+        match (self, token) {
+            (Error::IdentifierNotFoundInDocument(key), Token::Identifier { ident })      |
+            (Error::NoIdentifierInArray(key), Token::Identifier { ident })               |
+            (Error::QueryingValueAsTable(key), Token::Identifier { ident })              => key == ident,
+            (Error::NoIndexInTable(i), Token::Index { idx, .. })                         |
+            (Error::QueryingValueAsArray(i), Token::Index { idx, .. })                   |
+            (Error::ArrayIndexOutOfBounds(i, _), Token::Index { idx, from_end: false })  => i == idx,
+            (Error::IndexFromEndOutOfBounds(i, _), Token::Index { idx, from_end: true }) => i == idx,
+            (Error::ExtendedSyntax(segment), _)                                          => *segment == token.to_string(),
+            (Error::UnexpectedMultiMatch, Token::Wildcard)                               |
+            (Error::UnexpectedMultiMatch, Token::Union { .. })                           |
+            (Error::UnexpectedMultiMatch, Token::Filter { .. })                          => true,
+            _                                                                            => false,
+        }
+    }
+
+    /// What the error tells about the document, for `render()`
+    #[cfg(feature = "parser")]
+    fn notes(&self) -> Vec<String> {
+        match *self {
+            Error::NoIndexInTable(_)               => vec![String::from("found Table")],
+            Error::NoIdentifierInArray(_)          => vec![String::from("found Array")],
+            Error::QueryingValueAsTable(_)         |
+            Error::QueryingValueAsArray(_)         => vec![String::from("found a value which is neither a table nor an array")],
+            Error::ArrayIndexOutOfBounds(_, len)   |
+            Error::IndexFromEndOutOfBounds(_, len) => vec![format!("the array has {} element(s)", len)],
+            Error::TypeError(expected, found)      => vec![format!("expected {}, found {}", expected, found)],
+            _                                      => Vec::new(),
+        }
+    }
+
 }
 
+/// Render `error` for `query` with the bytes `span` of the query underlined
+#[cfg(feature = "parser")]
+fn render_message(error: &Error, query: &str, span: Option<Range<usize>>, notes: &[String]) -> String {
+    let mut lines = vec![
+        format!("error[{}]: {}", error.code(), error),
+        String::from("  |"),
+        format!("  | {}", query),
+    ];
+
+    if let Some(span) = span {
+        let offset = query[..span.start].chars().count();
+        let width  = query[span].chars().count().max(1);
+        lines.push(format!("  | {}{}", " ".repeat(offset), "^".repeat(width)));
+    }
+
+    lines.extend(notes.iter().map(|note| format!("  = {}", note)));
+    lines.join("\n")
+}
+
+/// Why a typed read did not produce a value
+///
+/// Returned by the `try_read_*` functions of `TomlValueReadTypeExt`, so that a missing value can
+/// be told apart from a value of the wrong type without matching on `Error`.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum ReadError {
+    #[fail(display = "No value at '{}'", path)]
+    Missing { path: String },
+
+    #[fail(display = "Type Error at '{}'. Requested {}, but got {}", path, expected, found)]
+    WrongType { path: String, expected: &'static str, found: &'static str },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(Error::ArrayAccessWithoutIndex.code(), "TQ0004");
+        assert_eq!(Error::NotAvailable(String::from("a")).code(), "TQ0016");
+    }
+
+    #[cfg(feature = "parser")]
+    #[test]
+    fn test_render() {
+        let err = Error::IdentifierNotFoundInDocument(String::from("c"));
+        assert_eq!(err.render("a.b.c.d", '.'), [
+            "error[TQ0006]: The identfier 'c' is not present in the document",
+            "  |",
+            "  | a.b.c.d",
+            "  |     ^",
+        ].join("\n"));
+
+        let err = Error::ArrayIndexOutOfBounds(10, 2);
+        assert_eq!(err.render(r#"ä["b c"].[10]"#, '.'), [
+            "error[TQ0014]: Cannot delete in array at 10, array has length 2",
+            "  |",
+            r#"  | ä["b c"].[10]"#,
+            "  |          ^^^^",
+            "  = the array has 2 element(s)",
+        ].join("\n"));
+
+        let err = Error::NoIndexInTable(0);
+        assert_eq!(err.render("a/b.c/[0]", '/'), [
+            "error[TQ0007]: Got an index query '[0]' but have table",
+            "  |",
+            "  | a/b.c/[0]",
+            "  |       ^^^",
+            "  = found Table",
+        ].join("\n"));
+
+        let err = Error::ArrayAccessWithoutIndex;
+        assert_eq!(err.render("a.[]", '.'), [
+            "error[TQ0004]: The passed query tries to access an array but does not specify the index",
+            "  |",
+            "  | a.[]",
+        ].join("\n"));
+    }
+
+    #[cfg(feature = "value")]
+    #[test]
+    fn test_render_in() {
+        use toml::from_str as toml_from_str;
+        use read::TomlValueReadExt;
+        use read::TomlValueReadTypeExt;
+
+        let doc : ::toml::Value = toml_from_str(r#"
+        [a.b]
+        x = 1
+        y = [ 1 ]
+        "#).unwrap();
+
+        let query = "a.b.y.c";
+        let err   = doc.read(query).unwrap_err();
+        assert_eq!(err.render_in(query, '.', &doc), [
+            "error[TQ0008]: Got an identifier query 'c' but have array",
+            "  |",
+            "  | a.b.y.c",
+            "  |       ^",
+            "  = found Array",
+            "  = the array has 1 element(s)",
+        ].join("\n"));
+
+        let query = "a.b.z.c";
+        let err   = Error::IdentifierNotFoundInDocument(String::from("z"));
+        assert_eq!(err.render_in(query, '.', &doc), [
+            "error[TQ0006]: The identfier 'z' is not present in the document",
+            "  |",
+            "  | a.b.z.c",
+            "  |     ^",
+            "  = found Table",
+            "  = available keys: x, y",
+        ].join("\n"));
+
+        let query = "a.b.x";
+        let err   = doc.read_string(query).unwrap_err();
+        assert_eq!(err.render_in(query, '.', &doc), [
+            "error[TQ0015]: Type Error. Requested String, but got Integer",
+            "  |",
+            "  | a.b.x",
+            "  |     ^",
+            "  = found Integer",
+        ].join("\n"));
+
+        let query = "a/b/z";
+        let err   = Error::IdentifierNotFoundInDocument(String::from("z"));
+        assert_eq!(err.render_in(query, '/', &doc), [
+            "error[TQ0006]: The identfier 'z' is not present in the document",
+            "  |",
+            "  | a/b/z",
+            "  |     ^",
+            "  = found Table",
+            "  = available keys: x, y",
+        ].join("\n"));
+    }
+
+}
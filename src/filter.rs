@@ -0,0 +1,615 @@
+/// Filter expressions
+///
+/// A filter expression is a predicate which is evaluated against a candidate value, for example
+/// an element of an array of tables:
+///
+/// ```text
+/// @.optional == true && (@.version >= 2 || !@.legacy)
+/// ```
+///
+/// `@` is the candidate itself, `@.a.[0]` a value below it. A path on its own checks whether the
+/// value exists. Expressions can be parsed from text with `Expr::parse()` or built directly from
+/// the `Expr` and `Operand` types.
+///
+/// Operands can be function calls, like `lower(@.name) == "serde"`. See the `function` module.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+use toml::Value;
+
+use read::TomlValueReadExt;
+use function::Functions;
+use error::{Error, Result};
+
+/// How deep parentheses, negations and function calls can be nested in a parsed expression
+///
+/// Lower than `tokenizer::DEFAULT_MAX_DEPTH`, because every level takes several stack frames of
+/// the parser.
+pub const MAX_NESTING: usize = 128;
+
+lazy_static! {
+    static ref BUILTINS: Functions = Functions::builtins();
+}
+
+/// A comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+
+    fn as_str(&self) -> &'static str {
+        match *self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+
+    /// Apply the operator to two values
+    ///
+    /// Integers and floats compare numerically, strings lexically, datetimes by their textual
+    /// representation. Values of other types can only be compared for (in)equality.
+    pub fn apply(&self, left: &Value, right: &Value) -> bool {
+        let ordering = compare(left, right);
+        match *self {
+            CompareOp::Eq => ordering == Some(Ordering::Equal) || (ordering.is_none() && left == right),
+            CompareOp::Ne => !CompareOp::Eq.apply(left, right),
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => ordering == Some(Ordering::Less) || ordering == Some(Ordering::Equal),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => ordering == Some(Ordering::Greater) || ordering == Some(Ordering::Equal),
+        }
+    }
+
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (&Value::Integer(l), &Value::Integer(r))           => Some(l.cmp(&r)),
+        (&Value::Float(l), &Value::Float(r))               => l.partial_cmp(&r),
+        (&Value::Integer(l), &Value::Float(r))             => (l as f64).partial_cmp(&r),
+        (&Value::Float(l), &Value::Integer(r))             => l.partial_cmp(&(r as f64)),
+        (&Value::String(ref l), &Value::String(ref r))     => Some(l.cmp(r)),
+        (&Value::Boolean(l), &Value::Boolean(r))           => Some(l.cmp(&r)),
+        (&Value::Datetime(ref l), &Value::Datetime(ref r)) => Some(l.to_string().cmp(&r.to_string())),
+        _ => None,
+    }
+}
+
+/// One side of a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// The value at a query relative to the candidate. The empty query is the candidate itself.
+    Path(String),
+
+    /// A constant value
+    Literal(Value),
+
+    /// The result of calling a function with the values of the operands
+    Call(String, Vec<Operand>),
+}
+
+impl Operand {
+
+    /// Get the value of the operand for `candidate`, if there is one
+    ///
+    /// Functions are only called if all of their arguments have a value.
+    pub fn evaluate<'a>(&'a self, candidate: &'a Value, functions: &Functions) -> Result<Option<Cow<'a, Value>>> {
+        match *self {
+            Operand::Path(ref query) if query.is_empty() => Ok(Some(Cow::Borrowed(candidate))),
+            Operand::Path(ref query)      => Ok(candidate.read(query).ok().and_then(|v| v).map(Cow::Borrowed)),
+            Operand::Literal(ref value)   => Ok(Some(Cow::Borrowed(value))),
+            Operand::Call(ref name, ref args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    match arg.evaluate(candidate, functions)? {
+                        Some(value) => values.push(value.into_owned()),
+                        None        => return Ok(None),
+                    }
+                }
+                functions.call(name, &values).map(|v| Some(Cow::Owned(v)))
+            },
+        }
+    }
+
+}
+
+/// A filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// True if there is a value at the query, relative to the candidate
+    Exists(String),
+
+    /// True if both operands have a value and the comparison holds
+    Compare(Operand, CompareOp, Operand),
+
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+
+    /// Parse an expression from text
+    pub fn parse(text: &str) -> Result<Expr> {
+        let tokens = lex(text).map_err(|e| Error::FilterParsingError(String::from(text), e))?;
+        let mut parser = Parser { tokens: &tokens, pos: 0, depth: 0 };
+
+        parser.or()
+            .and_then(|expr| if parser.pos == tokens.len() {
+                Ok(expr)
+            } else {
+                Err(format!("unexpected '{}'", tokens[parser.pos]))
+            })
+            .map_err(|e| Error::FilterParsingError(String::from(text), e))
+    }
+
+    /// Evaluate the expression for `candidate`, with the builtin functions
+    ///
+    /// If calling a function fails, the expression evaluates to `false`.
+    pub fn evaluate(&self, candidate: &Value) -> bool {
+        self.evaluate_with(candidate, &BUILTINS).unwrap_or(false)
+    }
+
+    /// Evaluate the expression for `candidate`, with the functions from `functions`
+    pub fn evaluate_with(&self, candidate: &Value, functions: &Functions) -> Result<bool> {
+        match *self {
+            Expr::Exists(ref query)         => {
+                Ok(query.is_empty() || candidate.read(query).ok().and_then(|v| v).is_some())
+            },
+            Expr::Compare(ref l, op, ref r) => {
+                let l = l.evaluate(candidate, functions)?;
+                let r = r.evaluate(candidate, functions)?;
+                match (l, r) {
+                    (Some(l), Some(r)) => Ok(op.apply(&l, &r)),
+                    _                  => Ok(false),
+                }
+            },
+            Expr::Not(ref e)                => e.evaluate_with(candidate, functions).map(|b| !b),
+            Expr::And(ref l, ref r)         => Ok(l.evaluate_with(candidate, functions)? &&
+                                                  r.evaluate_with(candidate, functions)?),
+            Expr::Or(ref l, ref r)          => Ok(l.evaluate_with(candidate, functions)? ||
+                                                  r.evaluate_with(candidate, functions)?),
+        }
+    }
+
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Path(ref query) if query.is_empty() => write!(f, "@"),
+            Operand::Path(ref query)                     => write!(f, "@.{}", query),
+            Operand::Literal(ref value)                  => fmt_literal(value, f),
+            Operand::Call(ref name, ref args)            => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Exists(ref query)         => write!(f, "{}", Operand::Path(query.clone())),
+            Expr::Compare(ref l, op, ref r) => write!(f, "{} {} {}", l, op.as_str(), r),
+            Expr::Not(ref e)                => write!(f, "!({})", e),
+            Expr::And(ref l, ref r)         => write!(f, "({}) && ({})", l, r),
+            Expr::Or(ref l, ref r)          => write!(f, "({}) || ({})", l, r),
+        }
+    }
+}
+
+/// Write a literal so that `lex()` reads it back
+///
+/// Strings are written with double quotes, in which only `"` and `\\` are escaped.
+fn fmt_literal(value: &Value, f: &mut fmt::Formatter) -> fmt::Result {
+    match *value {
+        Value::String(ref s) => {
+            write!(f, "\"")?;
+            for c in s.chars() {
+                if c == '"' || c == '\\' {
+                    write!(f, "\\")?;
+                }
+                write!(f, "{}", c)?;
+            }
+            write!(f, "\"")
+        },
+        ref other => write!(f, "{}", other),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Op(CompareOp),
+    Path(String),
+    Literal(Value),
+    Ident(String),
+    Comma,
+}
+
+impl fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Lexeme::LParen           => write!(f, "("),
+            Lexeme::RParen           => write!(f, ")"),
+            Lexeme::Not              => write!(f, "!"),
+            Lexeme::And              => write!(f, "&&"),
+            Lexeme::Or               => write!(f, "||"),
+            Lexeme::Op(op)           => write!(f, "{}", op.as_str()),
+            Lexeme::Path(ref q)      => write!(f, "{}", Operand::Path(q.clone())),
+            Lexeme::Literal(ref v)   => fmt_literal(v, f),
+            Lexeme::Ident(ref name)  => write!(f, "{}", name),
+            Lexeme::Comma            => write!(f, ","),
+        }
+    }
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '[' || c == ']'
+}
+
+fn lex(text: &str) -> ::std::result::Result<Vec<Lexeme>, String> {
+    let chars  = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c    = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        let (token, len) = match (c, next) {
+            (' ', _) | ('\t', _) => { i += 1; continue; },
+            ('(', _)         => (Lexeme::LParen, 1),
+            (')', _)         => (Lexeme::RParen, 1),
+            (',', _)         => (Lexeme::Comma, 1),
+            ('&', Some('&')) => (Lexeme::And, 2),
+            ('|', Some('|')) => (Lexeme::Or, 2),
+            ('=', Some('=')) => (Lexeme::Op(CompareOp::Eq), 2),
+            ('!', Some('=')) => (Lexeme::Op(CompareOp::Ne), 2),
+            ('<', Some('=')) => (Lexeme::Op(CompareOp::Le), 2),
+            ('>', Some('=')) => (Lexeme::Op(CompareOp::Ge), 2),
+            ('<', _)         => (Lexeme::Op(CompareOp::Lt), 1),
+            ('>', _)         => (Lexeme::Op(CompareOp::Gt), 1),
+            ('!', _)         => (Lexeme::Not, 1),
+            ('@', _)         => {
+                let len = chars[i + 1..].iter().take_while(|c| is_path_char(**c)).count();
+                let path = chars[i + 1..i + 1 + len].iter().collect::<String>();
+                let query = if path.is_empty() {
+                    path
+                } else if path.starts_with('.') && path.len() > 1 {
+                    String::from(&path[1..])
+                } else {
+                    return Err(format!("invalid path '@{}'", path));
+                };
+                (Lexeme::Path(query), 1 + len)
+            },
+            ('"', _) | ('\'', _) => {
+                let mut s = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None                          => return Err(String::from("unterminated string")),
+                        Some(&q) if q == c            => break,
+                        Some(&'\\') if c == '"'       => {
+                            match chars.get(j + 1) {
+                                Some(&e) => s.push(e),
+                                None     => return Err(String::from("unterminated string")),
+                            }
+                            j += 1;
+                        },
+                        Some(&other)                  => s.push(other),
+                    }
+                    j += 1;
+                }
+                (Lexeme::Literal(Value::String(s)), j + 1 - i)
+            },
+            _ if c == '-' || c.is_ascii_digit() => {
+                let len = 1 + chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.' || **c == '_')
+                    .count();
+                let number = chars[i..i + len].iter().filter(|c| **c != '_').collect::<String>();
+                let value = if number.contains('.') {
+                    number.parse().map(Value::Float).map_err(|_| format!("invalid number '{}'", number))?
+                } else {
+                    number.parse().map(Value::Integer).map_err(|_| format!("invalid number '{}'", number))?
+                };
+                (Lexeme::Literal(value), len)
+            },
+            _ if c.is_alphabetic() => {
+                let len = chars[i..].iter().take_while(|c| c.is_alphanumeric() || **c == '_').count();
+                let word = chars[i..i + len].iter().collect::<String>();
+                match word.as_ref() {
+                    "true"  => (Lexeme::Literal(Value::Boolean(true)), len),
+                    "false" => (Lexeme::Literal(Value::Boolean(false)), len),
+                    _       => (Lexeme::Ident(word), len),
+                }
+            },
+            _ => return Err(format!("unexpected character '{}'", c)),
+        };
+
+        tokens.push(token);
+        i += len;
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the lexemes
+///
+/// ```text
+/// or      := and ('||' and)*
+/// and     := unary ('&&' unary)*
+/// unary   := '!' unary | primary
+/// primary := '(' or ')' | operand (op operand)?
+/// operand := path | literal | ident '(' (operand (',' operand)*)? ')'
+/// ```
+///
+/// Parentheses, negations and function calls can be nested at most `MAX_NESTING` levels deep.
+struct Parser<'a> {
+    tokens: &'a [Lexeme],
+    pos: usize,
+    depth: usize,
+}
+
+type ParseResult<T> = ::std::result::Result<T, String>;
+
+impl<'a> Parser<'a> {
+
+    fn peek(&self) -> Option<&'a Lexeme> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> ParseResult<&'a Lexeme> {
+        let token = self.tokens.get(self.pos).ok_or_else(|| String::from("unexpected end"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Run `f` one nesting level deeper
+    fn nested<T, F>(&mut self, f: F) -> ParseResult<T>
+        where F: FnOnce(&mut Self) -> ParseResult<T>
+    {
+        if self.depth == MAX_NESTING {
+            return Err(format!("nested more than {} levels deep", MAX_NESTING));
+        }
+
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Lexeme::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Lexeme::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.peek() == Some(&Lexeme::Not) {
+            self.pos += 1;
+            return self.nested(Parser::unary).map(|e| Expr::Not(Box::new(e)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        if self.peek() == Some(&Lexeme::LParen) {
+            self.pos += 1;
+            let expr = self.nested(Parser::or)?;
+            return match *self.next()? {
+                Lexeme::RParen => Ok(expr),
+                ref other      => Err(format!("expected ')', found '{}'", other)),
+            };
+        }
+
+        let left = self.operand()?;
+        match self.peek() {
+            Some(&Lexeme::Op(op)) => {
+                self.pos += 1;
+                let right = self.operand()?;
+                Ok(Expr::Compare(left, op, right))
+            },
+            _ => match left {
+                Operand::Path(query) => Ok(Expr::Exists(query)),
+                other                => Err(format!("expected comparison after '{}'", other)),
+            },
+        }
+    }
+
+    fn operand(&mut self) -> ParseResult<Operand> {
+        match *self.next()? {
+            Lexeme::Path(ref query)    => Ok(Operand::Path(query.clone())),
+            Lexeme::Literal(ref value) => Ok(Operand::Literal(value.clone())),
+            Lexeme::Ident(ref name)    => {
+                match *self.next()? {
+                    Lexeme::LParen => {},
+                    ref other      => return Err(format!("expected '(' after '{}', found '{}'", name, other)),
+                }
+
+                let mut args = Vec::new();
+                if self.peek() == Some(&Lexeme::RParen) {
+                    self.pos += 1;
+                    return Ok(Operand::Call(name.clone(), args));
+                }
+
+                loop {
+                    args.push(self.nested(Parser::operand)?);
+                    match *self.next()? {
+                        Lexeme::Comma  => {},
+                        Lexeme::RParen => return Ok(Operand::Call(name.clone(), args)),
+                        ref other      => return Err(format!("expected ',' or ')', found '{}'", other)),
+                    }
+                }
+            },
+            ref other                  => Err(format!("expected path or value, found '{}'", other)),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn candidate() -> Value {
+        toml_from_str(r#"
+        name = "serde"
+        version = 2
+        ratio = 0.5
+        optional = true
+        features = [ "derive" ]
+        "#).unwrap()
+    }
+
+    fn eval(expr: &str) -> bool {
+        Expr::parse(expr).unwrap().evaluate(&candidate())
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(eval("@.name == \"serde\""));
+        assert!(eval("@.name != 'toml'"));
+        assert!(eval("@.version >= 2"));
+        assert!(eval("@.version < 2.5"));
+        assert!(eval("@.ratio > 0"));
+        assert!(eval("@.ratio <= 0.5"));
+        assert!(eval("@.optional == true"));
+        assert!(eval("@.features.[0] == \"derive\""));
+        assert!(!eval("@.version > 2"));
+        assert!(!eval("@.name < 1"));
+    }
+
+    #[test]
+    fn test_existence() {
+        assert!(eval("@.name"));
+        assert!(eval("@"));
+        assert!(!eval("@.missing"));
+        assert!(!eval("@.name.sub"));
+        assert!(!eval("@.missing == 1"));
+        assert!(!eval("@.missing != 1"));
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        assert!(eval("@.optional && @.version == 2"));
+        assert!(eval("@.missing || @.name == 'serde'"));
+        assert!(eval("!@.missing"));
+        assert!(eval("!(@.version == 1 || @.version == 3)"));
+        assert!(!eval("@.optional && !@.name"));
+
+        // && binds stronger than ||
+        assert!(eval("@.name == 'serde' || @.missing && @.missing"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expr in &["", "@.a ==", "@.a = 1", "(@.a", "1", "@a", "@.a == 'x", "@.a == yes", "@.a @.b"] {
+            let res = Expr::parse(expr);
+            assert!(is_match!(res, Err(Error::FilterParsingError(_, _))), "{} parsed", expr);
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let expr = format!("{}@.name{}", "(".repeat(MAX_NESTING), ")".repeat(MAX_NESTING));
+        assert!(Expr::parse(&expr).is_ok());
+
+        for expr in &[
+            format!("{}@.name{}", "(".repeat(2000), ")".repeat(2000)),
+            format!("{}@.name", "!".repeat(2000)),
+            format!("{}@.name{} == 1", "len(".repeat(2000), ")".repeat(2000)),
+        ] {
+            let res = Expr::parse(expr);
+            assert!(is_match!(res, Err(Error::FilterParsingError(_, _))));
+        }
+    }
+
+    #[test]
+    fn test_string_literal_roundtrip() {
+        for s in &["plain", "with \"quotes\"", "back\\slash", "it's", "trailing \\"] {
+            let expr = Expr::Compare(Operand::Path(String::from("name")),
+                                     CompareOp::Eq,
+                                     Operand::Literal(Value::String(String::from(*s))));
+            assert_eq!(Expr::parse(&expr.to_string()).unwrap(), expr, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        assert!(eval("len(@.features) == 1"));
+        assert!(eval("upper(@.name) == 'SERDE'"));
+        assert!(!eval("len(@.missing) == 0"));
+        assert!(!eval("len(@.version) == 0"));
+
+        let functions = Functions::new()
+            .register("twice", |args| match *::function::single_arg("twice", args)? {
+                Value::Integer(i) => Ok(Value::Integer(i * 2)),
+                _                 => Err(Error::TypeError("Integer", "other")),
+            });
+
+        let expr = Expr::parse("twice(@.version) == 4").unwrap();
+        assert!(expr.evaluate_with(&candidate(), &functions).unwrap());
+
+        let expr = Expr::parse("nope(@.version) == 4").unwrap();
+        let res  = expr.evaluate_with(&candidate(), &functions);
+        assert!(is_match!(res, Err(Error::UnknownFunction(_))));
+        assert!(!expr.evaluate(&candidate()));
+
+        let expr = Expr::parse("lower(  @.name ) == lower('SERDE')").unwrap();
+        assert_eq!(expr.to_string(), "lower(@.name) == lower(\"SERDE\")");
+    }
+
+    #[test]
+    fn test_programmatic() {
+        let expr = Expr::And(
+            Box::new(Expr::Exists(String::from("name"))),
+            Box::new(Expr::Compare(Operand::Path(String::from("version")),
+                                   CompareOp::Gt,
+                                   Operand::Literal(Value::Integer(1)))),
+        );
+
+        assert!(expr.evaluate(&candidate()));
+        assert_eq!(Expr::parse(&expr.to_string()).unwrap(), expr);
+    }
+
+}
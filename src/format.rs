@@ -0,0 +1,320 @@
+/// Configurable formatting of TOML documents
+///
+/// `to_string_with()` writes a document in a `FormatStyle`, which controls how arrays are
+/// wrapped, how they are indented, how floats are written and in which order keys are written. The output only depends
+/// on the data and the style, so tools sharing a style produce byte-identical files.
+
+use std::fmt::Write;
+
+use toml::Value;
+use toml::value::Table;
+
+use size::is_section;
+use error::{Error, Result};
+
+/// When arrays are written with one element per line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayWrapping {
+    Never,
+    Always,
+
+    /// Arrays which would be longer than this many characters when written on one line
+    LongerThan(usize),
+}
+
+/// A formatting style for `to_string_with()`
+///
+/// The default style writes arrays on one line and keys in alphabetical order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatStyle {
+    wrap_arrays: ArrayWrapping,
+    indent: usize,
+    trailing_comma: bool,
+    float_precision: Option<usize>,
+    integral_float_suffix: bool,
+    key_order: Vec<String>,
+}
+
+impl FormatStyle {
+
+    pub fn new() -> FormatStyle {
+        FormatStyle {
+            wrap_arrays: ArrayWrapping::Never,
+            indent: 4,
+            trailing_comma: true,
+            float_precision: None,
+            integral_float_suffix: true,
+            key_order: Vec::new(),
+        }
+    }
+
+    pub fn wrap_arrays(mut self, wrapping: ArrayWrapping) -> FormatStyle {
+        self.wrap_arrays = wrapping;
+        self
+    }
+
+    /// The number of spaces before the elements of wrapped arrays, per nesting level
+    pub fn indent(mut self, spaces: usize) -> FormatStyle {
+        self.indent = spaces;
+        self
+    }
+
+    /// Whether the last element of a wrapped array is followed by a comma
+    pub fn trailing_comma(mut self, trailing_comma: bool) -> FormatStyle {
+        self.trailing_comma = trailing_comma;
+        self
+    }
+
+    /// Write floats with this many digits after the decimal point
+    ///
+    /// With `None`, the default, floats are written with as many digits as needed to read them
+    /// back as the same value.
+    pub fn float_precision(mut self, digits: Option<usize>) -> FormatStyle {
+        self.float_precision = digits;
+        self
+    }
+
+    /// Whether floats without fraction are written with a trailing `.0`, as in `1.0`
+    ///
+    /// This is the default. Without the suffix, such floats are read back as integers.
+    pub fn integral_float_suffix(mut self, suffix: bool) -> FormatStyle {
+        self.integral_float_suffix = suffix;
+        self
+    }
+
+    /// Write these keys first, in this order, in every table which has them
+    ///
+    /// All other keys follow in alphabetical order. Sub tables which are written as sections
+    /// always come after the other values of their table, as TOML requires.
+    pub fn key_order<S: AsRef<str>>(mut self, keys: &[S]) -> FormatStyle {
+        self.key_order = keys.iter().map(|k| String::from(k.as_ref())).collect();
+        self
+    }
+
+    fn ordered<'a>(&self, table: &'a Table) -> Vec<(&'a String, &'a Value)> {
+        let mut entries = table.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|&(key, _)| {
+            let rank = self.key_order.iter().position(|k| k == key).unwrap_or(self.key_order.len());
+            (rank, key)
+        });
+        entries
+    }
+
+}
+
+impl Default for FormatStyle {
+    fn default() -> FormatStyle {
+        FormatStyle::new()
+    }
+}
+
+/// Write `value`, which must be a Table, as a TOML document in `style`
+///
+/// Returns `Err(Error::TypeError(_, _))` if `value` is not a Table.
+pub fn to_string_with(value: &Value, style: &FormatStyle) -> Result<String> {
+    match *value {
+        Value::Table(ref table) => {
+            let mut out = String::new();
+            write_table(&mut out, table, &mut Vec::new(), style);
+            Ok(out)
+        },
+        ref other => Err(Error::TypeError("Table", ::util::name_of_val(other))),
+    }
+}
+
+/// Write the values of `table` and then its sections, `header` being the keys of its header
+fn write_table(out: &mut String, table: &Table, header: &mut Vec<String>, style: &FormatStyle) {
+    let entries = style.ordered(table);
+
+    for &(key, value) in entries.iter().filter(|&&(_, v)| !is_section(v)) {
+        let _ = writeln!(out, "{} = {}", format_key(key), inline(value, style, 0));
+    }
+
+    for &(key, value) in entries.iter().filter(|&&(_, v)| is_section(v)) {
+        header.push(format_key(key));
+        match *value {
+            Value::Table(ref sub) => {
+                write_header(out, header, "[", "]");
+                write_table(out, sub, header, style);
+            },
+            Value::Array(ref ary) => for element in ary {
+                if let Value::Table(ref sub) = *element {
+                    write_header(out, header, "[[", "]]");
+                    write_table(out, sub, header, style);
+                }
+            },
+            _ => unreachable!(),
+        }
+        let _ = header.pop();
+    }
+}
+
+fn write_header(out: &mut String, header: &[String], open: &str, close: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    let _ = writeln!(out, "{}{}{}", open, header.join("."), close);
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        String::from(key)
+    } else {
+        Value::String(String::from(key)).to_string()
+    }
+}
+
+/// Write `value` inline, `depth` being the nesting level of wrapped arrays
+fn inline(value: &Value, style: &FormatStyle, depth: usize) -> String {
+    match *value {
+        Value::Array(ref ary) => {
+            let elements = ary.iter().map(|v| inline(v, style, depth + 1)).collect::<Vec<_>>();
+            let one_line = format!("[{}]", elements.join(", "));
+
+            let wrap = !ary.is_empty() && match style.wrap_arrays {
+                ArrayWrapping::Never         => false,
+                ArrayWrapping::Always        => true,
+                ArrayWrapping::LongerThan(n) => one_line.len() > n,
+            };
+            if !wrap {
+                return one_line;
+            }
+
+            let indent = " ".repeat(style.indent * (depth + 1));
+            let mut out = String::from("[\n");
+            for (i, element) in elements.iter().enumerate() {
+                let comma = if i + 1 < elements.len() || style.trailing_comma { "," } else { "" };
+                let _ = writeln!(out, "{}{}{}", indent, element, comma);
+            }
+            out.push_str(&" ".repeat(style.indent * depth));
+            out.push(']');
+            out
+        },
+        Value::Table(ref table) if table.is_empty() => String::from("{}"),
+        Value::Table(ref table) => {
+            let entries = style.ordered(table)
+                .into_iter()
+                .map(|(k, v)| format!("{} = {}", format_key(k), inline(v, style, depth)))
+                .collect::<Vec<_>>();
+            format!("{{ {} }}", entries.join(", "))
+        },
+        Value::Float(f) => format_float(f, style),
+        ref scalar => scalar.to_string(),
+    }
+}
+
+fn format_float(f: f64, style: &FormatStyle) -> String {
+    let default = style.float_precision.is_none() && style.integral_float_suffix;
+    if default || !f.is_finite() {
+        return Value::Float(f).to_string();
+    }
+
+    let mut out = match style.float_precision {
+        Some(digits) => format!("{:.*}", digits, f),
+        None         => format!("{}", f),
+    };
+    if style.integral_float_suffix && !out.contains('.') {
+        out.push_str(".0");
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Value {
+        toml_from_str(r#"
+        version = "0.1.0"
+        name = "app"
+        keywords = [ "a", "b" ]
+        matrix = [ [ 1, 2 ], [ 3 ] ]
+        "odd key" = { z = 1, name = 2 }
+
+        [dependencies]
+        toml = "0.4"
+
+        [[bin]]
+        path = "src/main.rs"
+        name = "app"
+
+        [[bin]]
+        name = "other"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_default_style() {
+        let text = to_string_with(&doc(), &FormatStyle::new()).unwrap();
+        assert_eq!(toml_from_str::<Value>(&text).unwrap(), doc());
+        assert_eq!(text, "\
+keywords = [\"a\", \"b\"]
+matrix = [[1, 2], [3]]
+name = \"app\"
+version = \"0.1.0\"
+
+[[bin]]
+name = \"app\"
+path = \"src/main.rs\"
+
+[[bin]]
+name = \"other\"
+
+[dependencies]
+toml = \"0.4\"
+
+[\"odd key\"]
+name = 2
+z = 1
+");
+    }
+
+    #[test]
+    fn test_custom_style() {
+        let style = FormatStyle::new()
+            .wrap_arrays(ArrayWrapping::LongerThan(12))
+            .indent(2)
+            .trailing_comma(false)
+            .key_order(&["name", "version"]);
+
+        let text = to_string_with(&doc(), &style).unwrap();
+        assert_eq!(toml_from_str::<Value>(&text).unwrap(), doc());
+        assert!(text.starts_with("\
+name = \"app\"
+version = \"0.1.0\"
+keywords = [\"a\", \"b\"]
+matrix = [
+  [1, 2],
+  [3]
+]
+"));
+        assert!(text.contains("[[bin]]\nname = \"app\"\npath"));
+        assert!(text.contains("[\"odd key\"]\nname = 2\nz = 1"));
+    }
+
+    #[test]
+    fn test_float_style() {
+        let doc : Value = toml_from_str("a = 1.0\nb = 0.1\nc = 2.345\nd = inf\n").unwrap();
+
+        let text = to_string_with(&doc, &FormatStyle::new()).unwrap();
+        assert_eq!(text, "a = 1.0\nb = 0.1\nc = 2.345\nd = inf\n");
+
+        let text = to_string_with(&doc, &FormatStyle::new().float_precision(Some(2))).unwrap();
+        assert_eq!(text, "a = 1.00\nb = 0.10\nc = 2.35\nd = inf\n");
+
+        let text = to_string_with(&doc, &FormatStyle::new().integral_float_suffix(false)).unwrap();
+        assert_eq!(text, "a = 1\nb = 0.1\nc = 2.345\nd = inf\n");
+
+        let style = FormatStyle::new().float_precision(Some(0));
+        assert_eq!(to_string_with(&doc, &style).unwrap(), "a = 1.0\nb = 0.0\nc = 2.0\nd = inf\n");
+    }
+
+    #[test]
+    fn test_not_a_table() {
+        let res = to_string_with(&Value::Integer(1), &FormatStyle::new());
+        assert!(is_match!(res, Err(Error::TypeError("Table", "Integer"))));
+    }
+
+}
@@ -0,0 +1,221 @@
+/// Functions for the query language
+///
+/// Functions can be called in filter expressions (`len(@.features) > 3`) and as the last segment
+/// of a query read with `TomlValueReadExt::read_with_options()` (`package.name.lower()`). The
+/// library only ships a few builtins, applications register their own in a `Functions` registry
+/// and pass it via `QueryOptions`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use toml::Value;
+
+use error::{Error, Result};
+use tokenizer::Syntax;
+
+/// A function callable from queries and filter expressions
+pub type Function = Box<dyn Fn(&[Value]) -> Result<Value> + Send + Sync>;
+
+/// A registry of named functions
+#[derive(Default)]
+pub struct Functions {
+    functions: BTreeMap<String, Function>,
+}
+
+impl Functions {
+
+    /// An empty registry
+    pub fn new() -> Functions {
+        Functions::default()
+    }
+
+    /// A registry with the builtin functions
+    ///
+    /// * `len(x)`: Number of characters of a string, elements of an array or entries of a table
+    /// * `lower(s)`, `upper(s)`: The string in lower / upper case
+    /// * `basename(s)`: The last component of a path
+    pub fn builtins() -> Functions {
+        Functions::new()
+            .register("len", |args| match *single_arg("len", args)? {
+                Value::String(ref s) => Ok(Value::Integer(s.chars().count() as i64)),
+                Value::Array(ref a)  => Ok(Value::Integer(a.len() as i64)),
+                Value::Table(ref t)  => Ok(Value::Integer(t.len() as i64)),
+                ref other            => Err(Error::TypeError("String, Array or Table", ::util::name_of_val(other))),
+            })
+            .register("lower", |args| string_arg("lower", args).map(|s| Value::String(s.to_lowercase())))
+            .register("upper", |args| string_arg("upper", args).map(|s| Value::String(s.to_uppercase())))
+            .register("basename", |args| {
+                let path = string_arg("basename", args)?;
+                let base = Path::new(path)
+                    .file_name()
+                    .map(|b| b.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                Ok(Value::String(base))
+            })
+    }
+
+    /// Register `function` as `name`, replacing a function with the same name
+    pub fn register<F>(mut self, name: &str, function: F) -> Functions
+        where F: Fn(&[Value]) -> Result<Value> + Send + Sync + 'static
+    {
+        let _ = self.functions.insert(String::from(name), Box::new(function));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Call the function `name` with `args`
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Value> {
+        match self.functions.get(name) {
+            Some(function) => function(args),
+            None           => Err(Error::UnknownFunction(String::from(name))),
+        }
+    }
+
+}
+
+impl fmt::Debug for Functions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.functions.keys()).finish()
+    }
+}
+
+/// Get the only argument of a call to `name`
+pub fn single_arg<'a>(name: &str, args: &'a [Value]) -> Result<&'a Value> {
+    if args.len() == 1 {
+        Ok(&args[0])
+    } else {
+        Err(Error::WrongNumberOfArguments(String::from(name), 1, args.len()))
+    }
+}
+
+/// Get the only argument of a call to `name`, which must be a string
+pub fn string_arg<'a>(name: &str, args: &'a [Value]) -> Result<&'a str> {
+    match *single_arg(name, args)? {
+        Value::String(ref s) => Ok(s),
+        ref other            => Err(Error::TypeError("String", ::util::name_of_val(other))),
+    }
+}
+
+/// Options for executing queries
+#[derive(Debug)]
+pub struct QueryOptions {
+    functions: Functions,
+    widen_integers: bool,
+    narrow_floats: bool,
+    syntax: Syntax,
+    seperator: char,
+}
+
+impl Default for QueryOptions {
+    fn default() -> QueryOptions {
+        QueryOptions {
+            functions: Functions::default(),
+            widen_integers: false,
+            narrow_floats: false,
+            syntax: Syntax::default(),
+            seperator: '.',
+        }
+    }
+}
+
+impl QueryOptions {
+
+    /// Options without any functions, not even the builtins
+    ///
+    /// Register them with `with_functions(Functions::builtins())`.
+    pub fn new() -> QueryOptions {
+        QueryOptions::default()
+    }
+
+    pub fn with_functions(mut self, functions: Functions) -> QueryOptions {
+        self.functions = functions;
+        self
+    }
+
+    pub fn functions(&self) -> &Functions {
+        &self.functions
+    }
+
+    /// Whether an Integer satisfies a request for a Float, if it converts without loss
+    pub fn with_integer_widening(mut self, enabled: bool) -> QueryOptions {
+        self.widen_integers = enabled;
+        self
+    }
+
+    pub fn integer_widening(&self) -> bool {
+        self.widen_integers
+    }
+
+    /// Whether a Float with a zero fraction satisfies a request for an Integer
+    pub fn with_float_narrowing(mut self, enabled: bool) -> QueryOptions {
+        self.narrow_floats = enabled;
+        self
+    }
+
+    pub fn float_narrowing(&self) -> bool {
+        self.narrow_floats
+    }
+
+    /// The grammar of queries, `Syntax::V2Extended` by default
+    ///
+    /// With `Syntax::V1Basic`, queries with function calls or other extended syntax fail.
+    pub fn with_syntax(mut self, syntax: Syntax) -> QueryOptions {
+        self.syntax = syntax;
+        self
+    }
+
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    /// The seperator between the segments of queries, `.` by default
+    pub fn with_seperator(mut self, seperator: char) -> QueryOptions {
+        self.seperator = seperator;
+        self
+    }
+
+    pub fn seperator(&self) -> char {
+        self.seperator
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn s(s: &str) -> Value {
+        Value::String(String::from(s))
+    }
+
+    #[test]
+    fn test_builtins() {
+        let f = Functions::builtins();
+        assert_eq!(f.call("len", &[s("abc")]).unwrap(), Value::Integer(3));
+        assert_eq!(f.call("len", &[Value::Array(vec![s("a")])]).unwrap(), Value::Integer(1));
+        assert_eq!(f.call("lower", &[s("AbC")]).unwrap(), s("abc"));
+        assert_eq!(f.call("upper", &[s("AbC")]).unwrap(), s("ABC"));
+        assert_eq!(f.call("basename", &[s("/usr/bin/cargo")]).unwrap(), s("cargo"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let f = Functions::builtins();
+        assert!(is_match!(f.call("nope", &[]), Err(Error::UnknownFunction(_))));
+        assert!(is_match!(f.call("len", &[]), Err(Error::WrongNumberOfArguments(_, 1, 0))));
+        assert!(is_match!(f.call("lower", &[Value::Integer(1)]), Err(Error::TypeError("String", "Integer"))));
+    }
+
+    #[test]
+    fn test_register() {
+        let f = Functions::new().register("answer", |_| Ok(Value::Integer(42)));
+        assert!(f.contains("answer"));
+        assert!(!f.contains("len"));
+        assert_eq!(f.call("answer", &[]).unwrap(), Value::Integer(42));
+    }
+
+}
@@ -0,0 +1,170 @@
+//! Structured input generators for fuzzing
+//!
+//! This module is only available with the `fuzzing` feature. It provides `arbitrary`-based
+//! generators for queries, documents and sequences of operations, which are used by the fuzz
+//! targets in the `fuzz/` directory of the repository.
+//!
+//! Fuzzing with completely random strings mostly exercises the error paths of the tokenizer.
+//! The generators here produce input which is close to valid, so the resolvers are reached as
+//! well.
+
+use std::collections::BTreeMap;
+
+use arbitrary::{Arbitrary, Unstructured};
+use toml::Value;
+
+use read::TomlValueReadExt;
+use set::TomlValueSetExt;
+use insert::TomlValueInsertExt;
+use delete::TomlValueDeleteExt;
+
+/// Keys used by both the query and the document generators, so that queries hit
+const KEYS: &[&str] = &["a", "b", "c", "table", "array", "key with space", ""];
+
+const MAX_DEPTH: usize = 4;
+
+/// A query which is built mostly from valid tokens
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzQuery(pub String);
+
+impl<'a> Arbitrary<'a> for FuzzQuery {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let len = u.int_in_range(0..=MAX_DEPTH + 1)?;
+        let mut query = String::new();
+
+        for i in 0..len {
+            if i != 0 {
+                // Leave out the seperator once in a while
+                if u.ratio(9u8, 10u8)? {
+                    query.push('.');
+                }
+            }
+
+            match u.int_in_range(0..=9u8)? {
+                0..=5 => query.push_str(u.choose(KEYS)?),
+                6..=8 => query.push_str(&format!("[{}]", u.int_in_range(0..=3usize)?)),
+                _     => query.push_str(<&str>::arbitrary(u)?),
+            }
+        }
+
+        Ok(FuzzQuery(query))
+    }
+}
+
+/// A document with a table at the top level, made from the same keys as `FuzzQuery`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzDocument(pub Value);
+
+impl<'a> Arbitrary<'a> for FuzzDocument {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        arbitrary_table(u, 0).map(FuzzDocument)
+    }
+}
+
+fn arbitrary_table(u: &mut Unstructured, depth: usize) -> ::arbitrary::Result<Value> {
+    let mut table = BTreeMap::new();
+    for _ in 0..u.int_in_range(0..=4u8)? {
+        let key   = String::from(*u.choose(KEYS)?);
+        let value = arbitrary_value(u, depth + 1)?;
+        let _     = table.insert(key, value);
+    }
+    Ok(Value::Table(table))
+}
+
+fn arbitrary_value(u: &mut Unstructured, depth: usize) -> ::arbitrary::Result<Value> {
+    let max_kind = if depth >= MAX_DEPTH { 4 } else { 6u8 };
+
+    match u.int_in_range(0..=max_kind)? {
+        0 => Ok(Value::String(String::arbitrary(u)?)),
+        1 => Ok(Value::Integer(i64::arbitrary(u)?)),
+        2 => Ok(Value::Float(f64::arbitrary(u)?)),
+        3 => Ok(Value::Boolean(bool::arbitrary(u)?)),
+        4 => Ok(Value::Array(Vec::new())),
+        5 => {
+            let mut ary = Vec::new();
+            for _ in 0..u.int_in_range(0..=3u8)? {
+                ary.push(arbitrary_value(u, depth + 1)?);
+            }
+            Ok(Value::Array(ary))
+        },
+        _ => arbitrary_table(u, depth),
+    }
+}
+
+/// A single call into the public API
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Read(FuzzQuery),
+    ReadMut(FuzzQuery),
+    ReadWithSeperator(FuzzQuery, char),
+    Set(FuzzQuery, Value),
+    Insert(FuzzQuery, Value),
+    Delete(FuzzQuery),
+}
+
+impl<'a> Arbitrary<'a> for Operation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let query = FuzzQuery::arbitrary(u)?;
+
+        Ok(match u.int_in_range(0..=5u8)? {
+            0 => Operation::Read(query),
+            1 => Operation::ReadMut(query),
+            2 => Operation::ReadWithSeperator(query, char::arbitrary(u)?),
+            3 => Operation::Set(query, arbitrary_value(u, 1)?),
+            4 => Operation::Insert(query, arbitrary_value(u, 1)?),
+            _ => Operation::Delete(query),
+        })
+    }
+}
+
+impl Operation {
+
+    /// Execute the operation on `document`, ignoring the result
+    ///
+    /// Errors are expected and fine, panics are bugs.
+    pub fn run(&self, document: &mut Value) {
+        match *self {
+            Operation::Read(ref q)                   => { let _ = document.read(&q.0); },
+            Operation::ReadMut(ref q)                => { let _ = document.read_mut(&q.0); },
+            Operation::ReadWithSeperator(ref q, sep) => {
+                let _ = document.read_with_seperator(&q.0, sep);
+            },
+            Operation::Set(ref q, ref v)             => { let _ = document.set(&q.0, v.clone()); },
+            Operation::Insert(ref q, ref v)          => { let _ = document.insert(&q.0, v.clone()); },
+            Operation::Delete(ref q)                 => { let _ = document.delete(&q.0); },
+        }
+    }
+
+}
+
+/// Execute all `operations` in order on `document`
+pub fn run_all(document: &mut Value, operations: &[Operation]) {
+    for op in operations {
+        op.run(document)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    quickcheck! {
+        fn test_random_operations_do_not_panic(data: Vec<u8>) -> bool {
+            let mut u = Unstructured::new(&data);
+            if let (Ok(doc), Ok(ops)) = (FuzzDocument::arbitrary(&mut u), Vec::<Operation>::arbitrary(&mut u)) {
+                let mut doc = doc.0;
+                run_all(&mut doc, &ops);
+            }
+            true
+        }
+
+        fn test_random_queries_do_not_panic(data: Vec<u8>) -> bool {
+            let doc = Value::Table(BTreeMap::new());
+            if let Ok(query) = FuzzQuery::arbitrary(&mut Unstructured::new(&data)) {
+                let _ = doc.read(&query.0);
+            }
+            true
+        }
+    }
+
+}
@@ -0,0 +1,195 @@
+/// Typo-tolerant reading
+///
+/// `TomlValueFuzzyReadExt::read_fuzzy()` resolves identifiers which are not in a table by the
+/// closest key in that table, and reports which keys were used instead of the requested ones.
+/// Meant for interactive tools, which should "just work" with minor typos but tell the user.
+
+use toml::Value;
+
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+
+/// A key which was used in place of a key that is not in the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    /// The path of the table the key is in
+    pub table: TomlPath,
+
+    /// The key from the query
+    pub requested: String,
+
+    /// The key which was used instead
+    pub used: String,
+}
+
+/// The result of `TomlValueFuzzyReadExt::read_fuzzy()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch<'doc> {
+    pub value: &'doc Value,
+
+    /// The substitutions made to find the value, empty if the query matched exactly
+    pub substitutions: Vec<Substitution>,
+}
+
+impl<'doc> FuzzyMatch<'doc> {
+
+    pub fn is_exact(&self) -> bool {
+        self.substitutions.is_empty()
+    }
+
+}
+
+pub trait TomlValueFuzzyReadExt<'doc> {
+
+    /// Read the value at `query`, using the closest key for keys which are not in the document
+    ///
+    /// The distance of two keys is their edit (Levenshtein) distance. Only keys with a distance
+    /// of at most `max_distance` are used. If several keys are equally close, the first one in
+    /// key order is used.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(None)` if there is no value at `query`, even with substitutions
+    /// * `Err(_)` in the same cases as `TomlValueReadExt::read()`
+    fn read_fuzzy(&'doc self, query: &str, max_distance: usize) -> Result<Option<FuzzyMatch<'doc>>> {
+        self.read_fuzzy_with_seperator(query, '.', max_distance)
+    }
+
+    fn read_fuzzy_with_seperator(&'doc self, query: &str, sep: char, max_distance: usize) -> Result<Option<FuzzyMatch<'doc>>>;
+
+}
+
+impl<'doc> TomlValueFuzzyReadExt<'doc> for Value {
+
+    fn read_fuzzy_with_seperator(&'doc self, query: &str, sep: char, max_distance: usize) -> Result<Option<FuzzyMatch<'doc>>> {
+        let mut value         = self;
+        let mut walked        = TomlPath::new();
+        let mut substitutions = Vec::new();
+
+        for segment in TomlPath::from_query(query, sep)?.segments() {
+            let next = match (value, segment) {
+                (&Value::Table(ref t), &Segment::Key(ref key)) => match t.get(key) {
+                    Some(sub) => Some((key.clone(), sub)),
+                    None      => closest(t.keys(), key, max_distance).map(|k| {
+                        substitutions.push(Substitution {
+                            table: walked.clone(),
+                            requested: key.clone(),
+                            used: k.clone(),
+                        });
+                        (k.clone(), &t[k])
+                    }),
+                },
+                (&Value::Array(ref a), &Segment::Index(idx))   => a.get(idx).map(|sub| (String::new(), sub)),
+                (&Value::Table(_), &Segment::Index(idx))       => return Err(Error::NoIndexInTable(idx)),
+                (&Value::Array(_), &Segment::Key(ref key))     => return Err(Error::NoIdentifierInArray(key.clone())),
+                (_, &Segment::Key(ref key))                    => return Err(Error::QueryingValueAsTable(key.clone())),
+                (_, &Segment::Index(idx))                      => return Err(Error::QueryingValueAsArray(idx)),
+            };
+
+            match next {
+                Some((key, sub)) => {
+                    walked.push(match *segment {
+                        Segment::Key(_)     => Segment::Key(key),
+                        Segment::Index(idx) => Segment::Index(idx),
+                    });
+                    value = sub;
+                },
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(FuzzyMatch { value, substitutions }))
+    }
+
+}
+
+/// The key closest to `key`, if its distance is at most `max_distance`
+fn closest<'a, I>(keys: I, key: &str, max_distance: usize) -> Option<&'a String>
+    where I: Iterator<Item = &'a String>
+{
+    keys.map(|k| (distance(k, key), k))
+        .filter(|&(d, _)| d <= max_distance)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, k)| k)
+}
+
+/// The Levenshtein distance of `a` and `b`, in characters
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = ::std::cmp::min(substitute, ::std::cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Value {
+        toml_from_str(r#"
+        [package]
+        name = "toml-query"
+        version = "0.8.0"
+
+        [[bin]]
+        name = "a"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("name", "name"), 0);
+        assert_eq!(distance("name", "nmae"), 2);
+        assert_eq!(distance("version", "verison"), 2);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_exact() {
+        let doc = doc();
+        let m = doc.read_fuzzy("package.name", 2).unwrap().unwrap();
+        assert!(m.is_exact());
+        assert_eq!(m.value, &Value::String(String::from("toml-query")));
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let doc = doc();
+        let m = doc.read_fuzzy("pakage.verison", 2).unwrap().unwrap();
+        assert_eq!(m.value, &Value::String(String::from("0.8.0")));
+        assert_eq!(m.substitutions, vec![
+            Substitution { table: TomlPath::new(), requested: String::from("pakage"), used: String::from("package") },
+            Substitution {
+                table: TomlPath::from(vec![Segment::Key(String::from("package"))]),
+                requested: String::from("verison"),
+                used: String::from("version"),
+            },
+        ]);
+
+        let m = doc.read_fuzzy("bins.[0].nam", 1).unwrap().unwrap();
+        assert_eq!(m.substitutions.len(), 2);
+        assert_eq!(m.substitutions[1].table.to_string(), "bin.[0]");
+    }
+
+    #[test]
+    fn test_too_far() {
+        let doc = doc();
+        assert!(doc.read_fuzzy("package.verison", 1).unwrap().is_none());
+        assert!(doc.read_fuzzy("bin.[1]", 5).unwrap().is_none());
+        assert!(is_match!(doc.read_fuzzy("package.[0]", 1), Err(Error::NoIndexInTable(0))));
+    }
+
+}
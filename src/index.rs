@@ -0,0 +1,140 @@
+/// Indexes for constant time lookups of arbitrary paths
+///
+/// A `DocumentIndex` maps every path in a document to the value at that path, so reading a path
+/// is a hash map lookup instead of a walk through the document. Building it visits the whole
+/// document once, which pays off for read-heavy workloads.
+///
+/// A `DocumentIndex` borrows the document, so it cannot be kept across modifications. To keep an
+/// index while modifying a document, use `Document::build_index()`, which patches the index on
+/// every modification done through the `Document`.
+
+use std::collections::{HashMap, HashSet};
+
+use toml::Value;
+
+use read::TomlValueReadExt;
+use path::{TomlPath, value_at};
+use walk::walk;
+use error::Result;
+
+/// An index from the paths of a document to its values
+#[derive(Debug, Clone)]
+pub struct DocumentIndex<'doc> {
+    root: &'doc Value,
+    values: HashMap<TomlPath, &'doc Value>,
+}
+
+impl<'doc> DocumentIndex<'doc> {
+
+    /// Index all values below `value`
+    pub fn build(value: &'doc Value) -> DocumentIndex<'doc> {
+        let mut values = HashMap::new();
+        walk(value, None, &mut |path, sub| {
+            let _ = values.insert(path.clone(), sub);
+        });
+
+        DocumentIndex { root: value, values }
+    }
+
+    /// The number of indexed values
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Get the value at `path`
+    pub fn get(&self, path: &TomlPath) -> Option<&'doc Value> {
+        self.values.get(path).cloned()
+    }
+
+    /// Read the value at `query`
+    ///
+    /// Same as `TomlValueReadExt::read()` on the indexed document: queries which do not resolve
+    /// return the same `Ok(None)` or error.
+    pub fn read(&self, query: &str) -> Result<Option<&'doc Value>> {
+        self.read_with_seperator(query, '.')
+    }
+
+    /// Read the value at `query`, using a custom seperator
+    pub fn read_with_seperator(&self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
+        let path = TomlPath::from_query(query, sep)?;
+        match self.get(&path) {
+            Some(value) => Ok(Some(value)),
+            None        => self.root.read_with_seperator(query, sep),
+        }
+    }
+
+}
+
+/// The paths of the values of a document, see `Document::build_index()`
+///
+/// Only the paths are kept, a value is looked up along its path in the document, so the index
+/// does not borrow the document it belongs to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PathIndex(HashSet<TomlPath>);
+
+impl PathIndex {
+
+    pub(crate) fn build(value: &Value) -> PathIndex {
+        let mut index = PathIndex::default();
+        index.patch(value, &TomlPath::new());
+        index
+    }
+
+    /// Replace the entries below `path`, `value` being the value at `path`
+    ///
+    /// The entry for `path` itself is kept.
+    pub(crate) fn patch(&mut self, value: &Value, path: &TomlPath) {
+        self.remove_below(path);
+
+        let paths = &mut self.0;
+        walk(value, None, &mut |sub_path, _| {
+            let mut full = path.clone();
+            for segment in sub_path.segments() {
+                full.push(segment.clone());
+            }
+            let _ = paths.insert(full);
+        });
+    }
+
+    /// Remove the entries below `path`
+    pub(crate) fn remove_below(&mut self, path: &TomlPath) {
+        self.0.retain(|p| p.len() <= path.len() || !p.starts_with(path));
+    }
+
+    /// Get the value at `path` in `value`, the indexed value, if `path` is indexed
+    pub(crate) fn get<'a>(&self, value: &'a Value, path: &TomlPath) -> Option<&'a Value> {
+        if self.0.contains(path) {
+            value_at(value, path.segments())
+        } else {
+            None
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::Error;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_read() {
+        let doc: Value = toml_from_str(r#"
+        [a]
+        b = [ { c = 1 }, { c = 2 } ]
+        "#).unwrap();
+        let index = DocumentIndex::build(&doc);
+
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.read("a.b.[1].c").unwrap(), Some(&Value::Integer(2)));
+        assert_eq!(index.read_with_seperator("a/b/[0]/c", '/').unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(index.read("a.x").unwrap(), None);
+        assert!(is_match!(index.read("a.b.x"), Err(Error::NoIdentifierInArray(_))));
+    }
+
+}
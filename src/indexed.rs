@@ -0,0 +1,657 @@
+/// An arena based document representation for bulk editing
+///
+/// `IndexedDocument` stores every table, array and value of a document as a node in an arena and
+/// keeps an index from paths to nodes. Looking up an existing path is a single hash map lookup
+/// instead of a walk through the tree, which pays off when applying thousands of modifications at
+/// arbitrary paths to a large document. Convert it back with `IndexedDocument::to_value()`.
+///
+/// The set, insert and delete extension traits are implemented with the same semantics as for
+/// `toml::Value`. `TomlValueReadExt` is not, as there is no `toml::Value` inside the arena a
+/// reference could point to. Use `IndexedDocument::read_owned()` instead.
+
+use std::collections::{BTreeMap, HashMap};
+
+use toml::Value;
+
+use set::TomlValueSetExt;
+use insert::TomlValueInsertExt;
+use delete::{self, TomlValueDeleteExt};
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+
+/// The identifier of a node in an `IndexedDocument`
+///
+/// A node keeps its identifier until it is removed, or replaced by a table or an array. Setting
+/// a scalar in place of a scalar keeps the identifier, and so does moving an array element
+/// because an element before it was inserted or deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    slot: usize,
+    generation: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Table(BTreeMap<String, NodeId>),
+    Array(Vec<NodeId>),
+    Scalar(Value),
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u64,
+    node: Option<Node>,
+}
+
+/// A TOML document stored in an arena, with an index from paths to nodes
+#[derive(Debug, Clone)]
+pub struct IndexedDocument {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    index: HashMap<TomlPath, NodeId>,
+    root: NodeId,
+}
+
+impl IndexedDocument {
+
+    pub fn new(value: Value) -> IndexedDocument {
+        let mut doc = IndexedDocument {
+            slots: Vec::new(),
+            free: Vec::new(),
+            index: HashMap::new(),
+            root: NodeId { slot: 0, generation: 0 },
+        };
+        doc.root = doc.alloc(value, &mut TomlPath::new());
+        doc
+    }
+
+    /// Build the `toml::Value` this document represents
+    pub fn to_value(&self) -> Value {
+        self.value_of(self.root)
+    }
+
+    /// The number of tables, arrays and values in the document, including the document itself
+    pub fn node_count(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Get the identifier of the node at `query`
+    pub fn node(&self, query: &str) -> Result<Option<NodeId>> {
+        self.node_with_seperator(query, '.')
+    }
+
+    /// Get the identifier of the node at `query`, using a custom seperator
+    pub fn node_with_seperator(&self, query: &str, sep: char) -> Result<Option<NodeId>> {
+        let path = self.path_of(query, sep)?;
+        self.resolve(&path, false)
+    }
+
+    /// Parse `query`, counting indices from the end of the arrays in the document
+    fn path_of(&self, query: &str, sep: char) -> Result<TomlPath> {
+        TomlPath::from_query_counting(query, sep, |path| {
+            self.index.get(path).and_then(|id| match *self.node_ref(*id) {
+                Node::Array(ref a) => Some(a.len()),
+                _                  => None,
+            })
+        })
+    }
+
+    /// Get a copy of the value at `query`
+    pub fn read_owned(&self, query: &str) -> Result<Option<Value>> {
+        self.read_owned_with_seperator(query, '.')
+    }
+
+    /// Get a copy of the value at `query`, using a custom seperator
+    pub fn read_owned_with_seperator(&self, query: &str, sep: char) -> Result<Option<Value>> {
+        self.node_with_seperator(query, sep)
+            .map(|id| id.map(|id| self.value_of(id)))
+    }
+
+    /// Get a copy of the value of the node `id`
+    ///
+    /// Returns `None` if the node was removed or replaced.
+    pub fn get(&self, id: NodeId) -> Option<Value> {
+        self.slots
+            .get(id.slot)
+            .and_then(|slot| if slot.generation == id.generation { slot.node.as_ref() } else { None })
+            .map(|_| self.value_of(id))
+    }
+
+    fn node_ref(&self, id: NodeId) -> &Node {
+        self.slots[id.slot].node.as_ref().unwrap() // safe because ids in the tree are alive
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.slots[id.slot].node.as_mut().unwrap() // safe because ids in the tree are alive
+    }
+
+    fn value_of(&self, id: NodeId) -> Value {
+        match *self.node_ref(id) {
+            Node::Table(ref t)  => Value::Table(t.iter().map(|(k, c)| (k.clone(), self.value_of(*c))).collect()),
+            Node::Array(ref a)  => Value::Array(a.iter().map(|c| self.value_of(*c)).collect()),
+            Node::Scalar(ref v) => v.clone(),
+        }
+    }
+
+    /// Store `value` at `path`, returning the id of the new node
+    fn alloc(&mut self, value: Value, path: &mut TomlPath) -> NodeId {
+        let node = match value {
+            Value::Table(t) => Node::Table(t.into_iter()
+                .map(|(key, sub)| {
+                    path.push(Segment::Key(key.clone()));
+                    let id = self.alloc(sub, path);
+                    let _ = path.pop();
+                    (key, id)
+                })
+                .collect()),
+
+            Value::Array(a) => Node::Array(a.into_iter()
+                .enumerate()
+                .map(|(idx, sub)| {
+                    path.push(Segment::Index(idx));
+                    let id = self.alloc(sub, path);
+                    let _ = path.pop();
+                    id
+                })
+                .collect()),
+
+            other => Node::Scalar(other),
+        };
+
+        let id = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot].node = Some(node);
+                NodeId { slot, generation: self.slots[slot].generation }
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, node: Some(node) });
+                NodeId { slot: self.slots.len() - 1, generation: 0 }
+            },
+        };
+
+        let _ = self.index.insert(path.clone(), id);
+        id
+    }
+
+    /// Remove the node `id` at `path` and everything below it, returning its value
+    fn release(&mut self, id: NodeId, path: &mut TomlPath) -> Value {
+        let node = {
+            let slot = &mut self.slots[id.slot];
+            slot.generation += 1;
+            slot.node.take().unwrap() // safe because ids in the tree are alive
+        };
+        self.free.push(id.slot);
+        let _ = self.index.remove(path);
+
+        match node {
+            Node::Table(t) => Value::Table(t.into_iter()
+                .map(|(key, sub)| {
+                    path.push(Segment::Key(key.clone()));
+                    let value = self.release(sub, path);
+                    let _ = path.pop();
+                    (key, value)
+                })
+                .collect()),
+
+            Node::Array(a) => Value::Array(a.into_iter()
+                .enumerate()
+                .map(|(idx, sub)| {
+                    path.push(Segment::Index(idx));
+                    let value = self.release(sub, path);
+                    let _ = path.pop();
+                    value
+                })
+                .collect()),
+
+            Node::Scalar(v) => v,
+        }
+    }
+
+    /// Add (or remove) the index entries of the node `id` at `path` and everything below it
+    fn reindex(&mut self, id: NodeId, path: &mut TomlPath, add: bool) {
+        if add {
+            let _ = self.index.insert(path.clone(), id);
+        } else {
+            let _ = self.index.remove(path);
+        }
+
+        let children = match *self.node_ref(id) {
+            Node::Table(ref t)  => t.iter().map(|(k, c)| (Segment::Key(k.clone()), *c)).collect(),
+            Node::Array(ref a)  => a.iter().cloned().enumerate().map(|(i, c)| (Segment::Index(i), c)).collect(),
+            Node::Scalar(_)     => Vec::new(),
+        };
+
+        for (segment, child) in children {
+            path.push(segment);
+            self.reindex(child, path, add);
+            let _ = path.pop();
+        }
+    }
+
+    /// Move the index entries of the elements of the array `array` at `path`, starting at `from`,
+    /// by one position, while `f` modifies the array
+    fn shift<F, T>(&mut self, array: NodeId, path: &TomlPath, from: usize, f: F) -> T
+        where F: FnOnce(&mut IndexedDocument) -> T
+    {
+        let elements = |doc: &IndexedDocument| match *doc.node_ref(array) {
+            Node::Array(ref a) => a.iter().cloned().enumerate().skip(from).collect::<Vec<_>>(),
+            _                  => Vec::new(),
+        };
+
+        for (idx, element) in elements(self) {
+            self.reindex(element, &mut child_path(path.segments(), Segment::Index(idx)), false);
+        }
+        let result = f(self);
+        for (idx, element) in elements(self) {
+            self.reindex(element, &mut child_path(path.segments(), Segment::Index(idx)), true);
+        }
+        result
+    }
+
+    /// Find the node at `path`
+    ///
+    /// Follows the semantics of the resolvers: a missing key or index is an error only if
+    /// `error_if_not_found` is set, a type mismatch always is.
+    fn resolve(&self, path: &TomlPath, error_if_not_found: bool) -> Result<Option<NodeId>> {
+        if let Some(id) = self.index.get(path) {
+            return Ok(Some(*id));
+        }
+
+        let mut current = self.root;
+        for segment in path.segments() {
+            let next = match (self.node_ref(current), segment) {
+                (&Node::Table(ref t), &Segment::Key(ref key)) => {
+                    t.get(key).cloned().ok_or_else(|| Error::IdentifierNotFoundInDocument(key.clone()))
+                },
+                (&Node::Array(ref a), &Segment::Index(idx)) => {
+                    a.get(idx).cloned().ok_or(Error::ArrayIndexOutOfBounds(idx, a.len()))
+                },
+                (&Node::Table(_), &Segment::Index(idx))       => return Err(Error::NoIndexInTable(idx)),
+                (&Node::Array(_), &Segment::Key(ref key))     => return Err(Error::NoIdentifierInArray(key.clone())),
+                (&Node::Scalar(_), &Segment::Key(ref key))    => return Err(Error::QueryingValueAsTable(key.clone())),
+                (&Node::Scalar(_), &Segment::Index(idx))      => return Err(Error::QueryingValueAsArray(idx)),
+            };
+
+            match next {
+                Ok(id)                      => current = id,
+                Err(e) if error_if_not_found => return Err(e),
+                Err(_)                      => return Ok(None),
+            }
+        }
+
+        Ok(Some(current))
+    }
+
+    /// Find the node at `path`, creating missing tables on the way
+    fn resolve_creating(&mut self, path: &TomlPath) -> Result<NodeId> {
+        if let Some(id) = self.index.get(path) {
+            return Ok(*id);
+        }
+
+        let mut current = self.root;
+        let mut walked  = TomlPath::new();
+        for segment in path.segments() {
+            walked.push(segment.clone());
+            let next = match (self.node_ref(current), segment) {
+                (&Node::Table(ref t), &Segment::Key(ref key)) => t.get(key).cloned(),
+                (&Node::Array(ref a), &Segment::Index(idx)) => match a.get(idx) {
+                    Some(id) => Some(*id),
+                    None     => return Err(Error::ArrayIndexOutOfBounds(idx, a.len())),
+                },
+                (&Node::Table(_), &Segment::Index(idx))       => return Err(Error::NoIndexInTable(idx)),
+                (&Node::Array(_), &Segment::Key(ref key))     => return Err(Error::NoIdentifierInArray(key.clone())),
+                (&Node::Scalar(_), &Segment::Key(ref key))    => return Err(Error::QueryingValueAsTable(key.clone())),
+                (&Node::Scalar(_), &Segment::Index(idx))      => return Err(Error::QueryingValueAsArray(idx)),
+            };
+
+            current = match next {
+                Some(id) => id,
+                None     => {
+                    let table = self.alloc(Value::Table(BTreeMap::new()), &mut walked);
+                    self.attach(current, segment, table);
+                    table
+                },
+            };
+        }
+
+        Ok(current)
+    }
+
+    /// Make `child` the value for `segment` in the table or array `parent`
+    ///
+    /// Indices past the end of an array append `child`.
+    fn attach(&mut self, parent: NodeId, segment: &Segment, child: NodeId) {
+        match (self.node_mut(parent), segment) {
+            (&mut Node::Table(ref mut t), &Segment::Key(ref key)) => {
+                let _ = t.insert(key.clone(), child);
+            },
+            (&mut Node::Array(ref mut a), &Segment::Index(idx)) => {
+                if idx < a.len() {
+                    a[idx] = child;
+                } else {
+                    a.push(child);
+                }
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Replace the value of the node `id`, which is the `segment` of `parent`
+    fn replace(&mut self, parent: NodeId, segment: &Segment, id: NodeId, path: &mut TomlPath, value: Value) -> Value {
+        let in_place = match (self.node_ref(id), &value) {
+            (_, &Value::Table(_)) | (_, &Value::Array(_)) => false,
+            (&Node::Scalar(_), _)                          => true,
+            _                                              => false,
+        };
+
+        if in_place {
+            match *self.node_mut(id) {
+                Node::Scalar(ref mut old) => ::std::mem::replace(old, value),
+                _                         => unreachable!(),
+            }
+        } else {
+            let old = self.release(id, path);
+            let new = self.alloc(value, path);
+            self.attach(parent, segment, new);
+            old
+        }
+    }
+
+    /// The table or array `path` is in, and the last segment of `path`
+    fn parent_of(&mut self, path: &TomlPath, creating: bool) -> Result<(NodeId, Segment)> {
+        let mut parent = path.clone();
+        let last = match parent.pop() {
+            Some(last) => last,
+            None       => return Err(Error::EmptyQueryError),
+        };
+
+        let id = if creating {
+            self.resolve_creating(&parent)?
+        } else {
+            self.resolve(&parent, true)?.unwrap() // safe because of resolve() guarantees
+        };
+        Ok((id, last))
+    }
+
+    fn set_path(&mut self, mut path: TomlPath, value: Value) -> Result<Option<Value>> {
+        let (parent, last) = self.parent_of(&path, false)?;
+
+        let existing = match (self.node_ref(parent), &last) {
+            (&Node::Table(ref t), &Segment::Key(ref key))   => t.get(key).cloned(),
+            (&Node::Array(ref a), &Segment::Index(idx))     => a.get(idx).cloned(),
+            (&Node::Table(_), &Segment::Index(idx))         => return Err(Error::NoIndexInTable(idx)),
+            (&Node::Array(_), &Segment::Key(ref key))       => return Err(Error::NoIdentifierInArray(key.clone())),
+            (&Node::Scalar(_), &Segment::Key(ref key))      => return Err(Error::QueryingValueAsTable(key.clone())),
+            (&Node::Scalar(_), &Segment::Index(idx))        => return Err(Error::QueryingValueAsArray(idx)),
+        };
+
+        match existing {
+            Some(id) => Ok(Some(self.replace(parent, &last, id, &mut path, value))),
+            None     => {
+                let last = self.append_position(parent, last);
+                let id = self.alloc(value, &mut child_path(&path.segments()[..path.len() - 1], last.clone()));
+                self.attach(parent, &last, id);
+                Ok(None)
+            },
+        }
+    }
+
+    fn insert_path(&mut self, mut path: TomlPath, value: Value) -> Result<Option<Value>> {
+        let (parent, last) = self.parent_of(&path, true)?;
+
+        let existing = match (self.node_ref(parent), &last) {
+            (&Node::Table(ref t), &Segment::Key(ref key)) => t.get(key).cloned(),
+            (&Node::Array(_), &Segment::Index(_))         => None,
+            (_, &Segment::Key(ref key))                   => return Err(Error::NoIdentifierInArray(key.clone())),
+            (_, &Segment::Index(idx))                     => return Err(Error::NoIndexInTable(idx)),
+        };
+
+        if let Some(id) = existing {
+            return Ok(Some(self.replace(parent, &last, id, &mut path, value)));
+        }
+
+        let last = self.append_position(parent, last);
+        let array_path = TomlPath::from(path.segments()[..path.len() - 1].to_vec());
+        match last {
+            Segment::Index(idx) => self.shift(parent, &array_path, idx, |doc| {
+                let id = doc.alloc(value, &mut child_path(array_path.segments(), Segment::Index(idx)));
+                if let Node::Array(ref mut a) = *doc.node_mut(parent) {
+                    a.insert(idx, id);
+                }
+            }),
+            Segment::Key(_) => {
+                let id = self.alloc(value, &mut path);
+                self.attach(parent, &last, id);
+            },
+        }
+
+        Ok(None)
+    }
+
+    fn delete_path(&mut self, mut path: TomlPath) -> Result<Option<Value>> {
+        let top_level = path.len() == 1;
+        let (parent, last) = self.parent_of(&path, false)?;
+
+        let id = match (self.node_ref(parent), &last) {
+            (&Node::Table(ref t), &Segment::Key(ref key)) => match t.get(key) {
+                Some(id) => *id,
+                None     => return Ok(None),
+            },
+            (&Node::Array(ref a), &Segment::Index(idx)) => match a.get(idx) {
+                Some(id) => *id,
+                None     => return Err(Error::ArrayIndexOutOfBounds(idx, a.len())),
+            },
+            (&Node::Table(_), &Segment::Index(_)) if top_level => return Ok(None),
+            (&Node::Table(_), &Segment::Index(idx))     => return Err(Error::NoIndexInTable(idx)),
+            (&Node::Array(_), &Segment::Key(ref key))   => return Err(Error::NoIdentifierInArray(key.clone())),
+            (&Node::Scalar(_), &Segment::Key(ref key))  => return Err(Error::QueryingValueAsTable(key.clone())),
+            (&Node::Scalar(_), &Segment::Index(idx))    => return Err(Error::QueryingValueAsArray(idx)),
+        };
+
+        let key = match last {
+            Segment::Key(ref key) => Some(key.clone()),
+            Segment::Index(_)     => None,
+        };
+        match *self.node_ref(id) {
+            Node::Table(ref t) if !t.is_empty() => return Err(Error::CannotDeleteNonEmptyTable(key)),
+            Node::Array(ref a) if !a.is_empty() => return Err(Error::CannotDeleteNonEmptyArray(key)),
+            _ => { },
+        }
+
+        match last {
+            Segment::Key(ref key) => {
+                if let Node::Table(ref mut t) = *self.node_mut(parent) {
+                    let _ = t.remove(key);
+                }
+                Ok(Some(self.release(id, &mut path)))
+            },
+            Segment::Index(idx) => {
+                let array_path = TomlPath::from(path.segments()[..path.len() - 1].to_vec());
+                self.shift(parent, &array_path, idx, |doc| {
+                    if let Node::Array(ref mut a) = *doc.node_mut(parent) {
+                        let _ = a.remove(idx);
+                    }
+                    Ok(Some(doc.release(id, &mut path)))
+                })
+            },
+        }
+    }
+
+    /// The segment a new child of `parent` is stored at: indices past the end of an array are
+    /// appended
+    fn append_position(&self, parent: NodeId, segment: Segment) -> Segment {
+        match (self.node_ref(parent), segment) {
+            (&Node::Array(ref a), Segment::Index(idx)) => Segment::Index(::std::cmp::min(idx, a.len())),
+            (_, segment)                               => segment,
+        }
+    }
+
+}
+
+impl From<Value> for IndexedDocument {
+    fn from(value: Value) -> IndexedDocument {
+        IndexedDocument::new(value)
+    }
+}
+
+fn child_path(parent: &[Segment], segment: Segment) -> TomlPath {
+    let mut path = TomlPath::from(parent.to_vec());
+    path.push(segment);
+    path
+}
+
+impl TomlValueSetExt for IndexedDocument {
+
+    fn set_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        instrument!("set", query, self.path_of(query, sep).and_then(|p| self.set_path(p, value)))
+    }
+
+}
+
+impl TomlValueInsertExt for IndexedDocument {
+
+    fn insert_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        instrument!("insert", query, self.path_of(query, sep).and_then(|p| self.insert_path(p, value)))
+    }
+
+}
+
+impl TomlValueDeleteExt for IndexedDocument {
+
+    fn delete_with_seperator(&mut self, query: &str, sep: char) -> Result<Option<Value>> {
+        instrument!("delete", query, self.path_of(query, sep).and_then(|p| self.delete_path(p)))
+    }
+
+    fn prune_at(&mut self, query: &str) -> Result<Vec<TomlPath>> {
+        let mut path = self.path_of(query, '.')?;
+        let id = match self.resolve(&path, false)? {
+            Some(id) => id,
+            None     => return Ok(Vec::new()),
+        };
+
+        let mut value = self.value_of(id);
+        let removed   = delete::prune(&mut value);
+        if !removed.is_empty() {
+            let (parent, last) = self.parent_of(&path, false)?;
+            let _ = self.replace(parent, &last, id, &mut path, value);
+        }
+
+        Ok(removed
+            .into_iter()
+            .map(|p| {
+                let mut full = path.clone();
+                for segment in p.segments() {
+                    full.push(segment.clone());
+                }
+                full
+            })
+            .collect())
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use read::TomlValueReadExt;
+    use toml::from_str as toml_from_str;
+
+    fn document() -> Value {
+        toml_from_str(r#"
+        [a]
+        b = 1
+        c = [ 1, 2, 3 ]
+        d = [ { e = 1 }, { e = 2 } ]
+
+        [f]
+        g = "g"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let doc = IndexedDocument::new(document());
+        assert_eq!(doc.to_value(), document());
+        assert_eq!(doc.node_count(), 14);
+    }
+
+    #[test]
+    fn test_read_owned() {
+        let doc = IndexedDocument::new(document());
+        assert_eq!(doc.read_owned("a.d.[1].e").unwrap(), Some(Value::Integer(2)));
+        assert_eq!(doc.read_owned("a.x").unwrap(), None);
+        assert_eq!(doc.read_owned("a.c.[7]").unwrap(), None);
+        assert!(is_match!(doc.read_owned("a.b.x"), Err(Error::QueryingValueAsTable(_))));
+        assert!(is_match!(doc.read_owned("a.[0]"), Err(Error::NoIndexInTable(0))));
+    }
+
+    #[test]
+    fn test_same_results_as_value() {
+        let mut value = document();
+        let mut doc   = IndexedDocument::new(value.clone());
+
+        macro_rules! both {
+            ($op:ident($($arg:expr),*)) => {{
+                let expected = value.$op($($arg.clone()),*);
+                let actual   = doc.$op($($arg),*);
+                assert_eq!(format!("{:?}", actual), format!("{:?}", expected), "{}", stringify!($op($($arg),*)));
+                assert_eq!(doc.to_value(), value);
+            }}
+        }
+
+        both!(set("a.b", Value::Integer(2)));
+        both!(set("a.c.[1]", Value::Integer(5)));
+        both!(set("a.c.[9]", Value::Integer(9)));
+        both!(set("a.new", Value::Array(vec![Value::Integer(1)])));
+        both!(set("a.b", document()));
+        both!(set("x.y", Value::Integer(1)));
+        both!(set("a.c.x", Value::Integer(1)));
+        both!(insert("h.i.j", Value::Integer(1)));
+        both!(insert("a.c.[0]", Value::Integer(0)));
+        both!(insert("a.c.[100]", Value::Integer(100)));
+        both!(insert("a.d.[1].e", Value::Integer(3)));
+        both!(insert("a.d.[5].e", Value::Integer(3)));
+        both!(insert("a.c.x", Value::Integer(1)));
+        both!(set("a.c.[-1]", Value::Integer(7)));
+        both!(set("a.c.[last]", Value::Integer(8)));
+        both!(set("a.c.[-20]", Value::Integer(8)));
+        both!(insert("a.c.[+]", Value::Integer(9)));
+        both!(insert("a.c.[-2]", Value::Integer(6)));
+        both!(insert("a.n.[+]", Value::Integer(1)));
+        both!(set("f.g.[-1]", Value::Integer(1)));
+        both!(delete("a.c.[-1]"));
+        both!(delete("a.x.[-1]"));
+        both!(delete("a.c.[2]"));
+        both!(delete("a.c.[20]"));
+        both!(delete("a.d"));
+        both!(delete("a.x"));
+        both!(delete("f.g"));
+        both!(delete("f"));
+        both!(prune_at("a"));
+
+        // the index follows the shifted elements
+        for query in &["a.c.[0]", "a.c.[1]", "a.c.[2]", "a.c.[3]", "a.d.[0].e", "a.d.[1].e", "a.new.[0]"] {
+            assert_eq!(doc.read_owned(query).unwrap(), value.read(query).unwrap().cloned(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_stable_ids() {
+        let mut doc = IndexedDocument::new(document());
+        let b     = doc.node("a.b").unwrap().unwrap();
+        let last  = doc.node("a.c.[2]").unwrap().unwrap();
+        let table = doc.node("a.d.[1]").unwrap().unwrap();
+        let e     = doc.node("a.d.[1].e").unwrap().unwrap();
+
+        let _ = doc.set("a.b", Value::Integer(2)).unwrap();
+        let _ = doc.delete("a.c.[0]").unwrap();
+        let _ = doc.set("a.d.[1]", toml_from_str::<Value>("e = 3").unwrap()).unwrap();
+
+        assert_eq!(doc.get(b), Some(Value::Integer(2)));
+        assert_eq!(doc.get(last), Some(Value::Integer(3)));
+        assert_eq!(doc.node("a.c.[1]").unwrap(), Some(last));
+        assert_eq!(doc.get(table), None);
+        assert_eq!(doc.get(e), None);
+    }
+
+}
@@ -6,6 +6,8 @@ use toml::Value;
 
 use tokenizer::Token;
 use tokenizer::tokenize_with_seperator;
+use read::TomlValueReadExt;
+use path::TomlPath;
 use error::{Error, Result};
 
 pub trait TomlValueInsertExt {
@@ -25,7 +27,8 @@ pub trait TomlValueInsertExt {
     /// If an Array exists, but the specified index is larger than the last index, the array will
     /// be expanded by one element: If the array has a length of 3, but the query string specifies
     /// that the element should be put at 1000, the function ignores the large index and simply
-    /// appends the value to the index.
+    /// appends the value to the index. To append explicitly, use the pseudo-index `[+]`, like in
+    /// `"a.b.[+]"`.
     ///
     /// If a Value is inserted into an Array, the array indexes are shifted. Semantically this is
     /// the same as doing a `array.insert(4, _)` (see the standard library).
@@ -87,6 +90,140 @@ pub trait TomlValueInsertExt {
         self.insert_with_seperator(query, '.', value)
     }
 
+    /// Insert `value` at `path`
+    ///
+    /// Unlike a query, the keys of `path` are never parsed, see `path::Path`. Otherwise like
+    /// `TomlValueInsertExt::insert_with_seperator`.
+    fn insert_path(&mut self, path: &TomlPath, value: Value) -> Result<Option<Value>> {
+        self.insert_with_seperator(&path.to_string(), '.', value)
+    }
+
+    /// Insert `value` at `query` like `insert_with_seperator()`, but only if the insert stays
+    /// within `limits`
+    ///
+    /// If it does not, `Err(Error::LimitExceeded(_, _, _))` is returned and the document is not
+    /// changed.
+    fn insert_with_limits(&mut self, query: &str, sep: char, value: Value, limits: &InsertLimits) -> Result<Option<Value>>
+        where Self: for<'doc> TomlValueReadExt<'doc>
+    {
+        let path = TomlPath::from_query(query, sep)?;
+
+        // Tables are created for all missing segments before the last one
+        let mut created = 0;
+        let mut prefix  = TomlPath::new();
+        for (i, segment) in path.segments().iter().take(path.len() - 1).enumerate() {
+            prefix.push(segment.clone());
+            if self.read(&prefix.to_string())?.is_none() {
+                created = path.len() - 1 - i;
+                break;
+            }
+        }
+
+        let mut growth = if created == 0 && !path.is_empty() {
+            let parent = path.parent().unwrap_or_default();
+            let parent_is_array = if parent.is_empty() {
+                false
+            } else {
+                is_match!(self.read(&parent.to_string())?, Some(&Value::Array(_)))
+            };
+            if parent_is_array { 1 } else { 0 }
+        } else {
+            0
+        };
+
+        let nodes = created + count_nodes(&value, &mut growth);
+        limits.check("created nodes", limits.max_created_nodes, nodes)?;
+        limits.check("array growth", limits.max_array_growth, growth)?;
+
+        self.insert_with_seperator(query, sep, value)
+    }
+
+    /// Insert `value` at `query` like `insert_with_seperator()`, but refuse to replace a
+    /// non-empty table with another kind of value, or another value with a table
+    ///
+    /// In these cases `Err(Error::WouldShadowTable(_, _, _))` is returned and the document is not
+    /// changed. Inserts into arrays never replace a value. `insert_with_seperator()` is the
+    /// variant which replaces the value anyways.
+    fn insert_checked(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>>
+        where Self: for<'doc> TomlValueReadExt<'doc>
+    {
+        let tokens   = tokenize_with_seperator(query, sep)?;
+        let replaces = is_match!(tokens.last(), Some(&Token::Identifier { .. }));
+
+        if replaces {
+            ::util::check_shadowing(query, self.read_with_seperator(query, sep)?, &value)?;
+        }
+        self.insert_with_seperator(query, sep, value)
+    }
+
+    /// Put the table `value` into the array of tables at `query`, replacing the table with the
+    /// same value for `key_field`
+    ///
+    /// If no table in the array matches, `value` is appended. If there is no value at `query`, an
+    /// array with `value` is inserted. The order of the other tables is kept.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(Some(old))` with the replaced table
+    /// * `Err(Error::TypeError(_, _))` if `value` is not a Table or the value at `query` is not an
+    ///   Array
+    /// * `Err(Error::NotAvailable(_))` if `value` has no `key_field`
+    fn upsert_in_array(&mut self, query: &str, key_field: &str, value: Value) -> Result<Option<Value>>
+        where Self: for<'doc> TomlValueReadExt<'doc>
+    {
+        let key_value = match value {
+            Value::Table(ref t) => t.get(key_field)
+                .cloned()
+                .ok_or_else(|| Error::NotAvailable(String::from(key_field)))?,
+            ref other           => return Err(Error::TypeError("Table", ::util::name_of_val(other))),
+        };
+
+        match self.read_mut(query)? {
+            Some(&mut Value::Array(ref mut a)) => {
+                let position = a.iter().position(|element| match *element {
+                    Value::Table(ref t) => t.get(key_field) == Some(&key_value),
+                    _                   => false,
+                });
+
+                match position {
+                    Some(idx) => return Ok(Some(::std::mem::replace(&mut a[idx], value))),
+                    None      => {
+                        a.push(value);
+                        return Ok(None);
+                    },
+                }
+            },
+            Some(other) => return Err(Error::TypeError("Array", ::util::name_of_val(other))),
+            None        => { },
+        }
+
+        self.insert(query, Value::Array(vec![value])).map(|_| None)
+    }
+
+    /// Parse `toml_text` as a TOML document and put its root table at `query`
+    ///
+    /// If there is a table at `query` already, the fragment is merged into it: tables are merged
+    /// recursively, all other values of the fragment replace the values with the same key.
+    /// Otherwise the fragment is inserted like with `insert()`.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::FragmentParseError(_, _))` if `toml_text` is not valid TOML
+    /// * `Err(_)` in the same cases as `insert()`
+    fn insert_fragment(&mut self, query: &str, toml_text: &str) -> Result<()>
+        where Self: for<'doc> TomlValueReadExt<'doc>
+    {
+        let fragment = ::toml::from_str::<Value>(toml_text)
+            .map_err(|e| Error::FragmentParseError(String::from(query), e.to_string()))?;
+
+        if let Some(existing @ &mut Value::Table(_)) = self.read_mut(query)? {
+            merge_fragment(existing, fragment);
+            return Ok(());
+        }
+
+        self.insert(query, fragment).map(|_| ())
+    }
+
     /// A convenience method for inserting any arbitrary serializable value.
     #[cfg(feature = "typed")]
     fn insert_serialized<S: Serialize>(&mut self, query: &str, value: S) -> Result<Option<Value>> {
@@ -96,47 +233,126 @@ pub trait TomlValueInsertExt {
 
 }
 
-impl TomlValueInsertExt for Value {
+/// Limits for `TomlValueInsertExt::insert_with_limits()`
+///
+/// Protects documents from untrusted inserts which would create huge structures.
+#[derive(Debug, Clone, Default)]
+pub struct InsertLimits {
+    max_created_nodes: Option<usize>,
+    max_array_growth: Option<usize>,
+}
 
-    fn insert_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
-        use resolver::mut_creating_resolver::resolve;
+impl InsertLimits {
 
-        let mut tokens = try!(tokenize_with_seperator(query, sep));
-        let (val, last) = match tokens.pop_last() {
-            None       => (self, Box::new(tokens)),
-            Some(last) => (try!(resolve(self, &tokens)), last),
+    /// No limits
+    pub fn new() -> InsertLimits {
+        InsertLimits::default()
+    }
 
-        };
+    /// The maximum number of values an insert may add: the tables created on the way, the
+    /// inserted value and all values in it
+    pub fn max_created_nodes(mut self, max: usize) -> InsertLimits {
+        self.max_created_nodes = Some(max);
+        self
+    }
 
-        match *last {
-            Token::Identifier { ident, .. } => {
-                match val {
-                    &mut Value::Table(ref mut t) => {
-                        Ok(t.insert(ident, value))
-                    },
-                    _ => Err(Error::NoIdentifierInArray(ident.clone()))
-                }
-            },
+    /// The maximum number of elements an insert may add to a single array, including the
+    /// arrays in the inserted value
+    pub fn max_array_growth(mut self, max: usize) -> InsertLimits {
+        self.max_array_growth = Some(max);
+        self
+    }
 
-            Token::Index { idx , .. } => {
-                match val {
-                    &mut Value::Array(ref mut a) => {
-                        if a.len() > idx {
-                            a.insert(idx, value);
-                            Ok(None)
-                        } else {
-                            a.push(value);
-                            Ok(None)
-                        }
-                    },
-                    _ => Err(Error::NoIndexInTable(idx))
-                }
-            },
+    fn check(&self, what: &'static str, limit: Option<usize>, actual: usize) -> Result<()> {
+        match limit {
+            Some(limit) if actual > limit => Err(Error::LimitExceeded(what, limit, actual)),
+            _                             => Ok(()),
         }
     }
 
 }
 
+/// Count `value` and all values in it, and track the length of the longest array
+fn count_nodes(value: &Value, longest_array: &mut usize) -> usize {
+    1 + match *value {
+        Value::Table(ref t) => t.values().map(|v| count_nodes(v, longest_array)).sum(),
+        Value::Array(ref a) => {
+            *longest_array = ::std::cmp::max(*longest_array, a.len());
+            a.iter().map(|v| count_nodes(v, longest_array)).sum()
+        },
+        _ => 0,
+    }
+}
+
+/// Merge `fragment` into `target`, see `TomlValueInsertExt::insert_fragment()`
+fn merge_fragment(target: &mut Value, fragment: Value) {
+    match (target, fragment) {
+        (&mut Value::Table(ref mut target), Value::Table(fragment)) => for (key, value) in fragment {
+            match target.get_mut(&key) {
+                Some(existing @ &mut Value::Table(_)) if is_match!(value, Value::Table(_)) => {
+                    merge_fragment(existing, value);
+                    continue;
+                },
+                _ => { },
+            }
+            let _ = target.insert(key, value);
+        },
+        (target, fragment) => *target = fragment,
+    }
+}
+
+impl TomlValueInsertExt for Value {
+
+    fn insert_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
+        instrument!("insert", query, {
+            tokenize_with_seperator(query, sep).and_then(|tokens| insert_impl(self, &tokens, value))
+        })
+    }
+
+    fn insert_path(&mut self, path: &TomlPath, value: Value) -> Result<Option<Value>> {
+        let query = path.to_string();
+        instrument!("insert", &query, path.to_tokens().and_then(|tokens| insert_impl(self, &tokens, value)))
+    }
+
+}
+
+pub(crate) fn insert_impl(document: &mut Value, tokens: &[Token], value: Value) -> Result<Option<Value>> {
+    use resolver::mut_creating_resolver::resolve;
+    use resolver::array_index;
+
+    let (last, parents) = tokens.split_last().ok_or(Error::EmptyQueryError)?;
+    let val = try!(resolve(document, parents));
+
+    match *last {
+        Token::Identifier { ref ident, .. } => {
+            match val {
+                &mut Value::Table(ref mut t) => {
+                    Ok(t.insert(ident.to_string(), value))
+                },
+                _ => Err(Error::NoIdentifierInArray(ident.to_string()))
+            }
+        },
+
+        Token::Index { idx, from_end, .. } => {
+            match val {
+                &mut Value::Array(ref mut a) => {
+                    let idx = array_index(idx, from_end, a.len())?;
+                    if a.len() > idx {
+                        a.insert(idx, value);
+                        Ok(None)
+                    } else {
+                        a.push(value);
+                        Ok(None)
+                    }
+                },
+                _ => Err(Error::NoIndexInTable(idx))
+            }
+        },
+
+        Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -170,6 +386,112 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_insert_pseudo_indices() {
+        use set::TomlValueSetExt;
+
+        let mut toml : Value = toml_from_str(r#"
+        values = [ 1, 2 ]
+        "#).unwrap();
+
+        assert!(toml.insert("values.[+]", Value::Integer(4)).unwrap().is_none());
+        assert!(toml.insert("values.[last]", Value::Integer(3)).unwrap().is_none());
+        assert_eq!(toml.read("values.[last]").unwrap(), Some(&Value::Integer(4)));
+        assert_eq!(toml["values"], Value::Array((1..5).map(Value::Integer).collect()));
+
+        let _ = toml.set("values.[last]", Value::Integer(5)).unwrap();
+        assert_eq!(toml.read("values.[3]").unwrap(), Some(&Value::Integer(5)));
+        assert_eq!(toml.read("values.[+]").unwrap(), None);
+    }
+
+    #[test]
+    fn test_upsert_in_array() {
+        let mut toml : Value = toml_from_str(r#"
+        name = "cluster"
+
+        [[servers]]
+        name = "a"
+        port = 80
+
+        [[servers]]
+        name = "b"
+        port = 81
+        "#).unwrap();
+
+        let server = |name: &str, port: i64| {
+            let mut t = ::toml::value::Table::new();
+            let _ = t.insert(String::from("name"), Value::from(name));
+            let _ = t.insert(String::from("port"), Value::from(port));
+            Value::Table(t)
+        };
+
+        let old = toml.upsert_in_array("servers", "name", server("a", 8080)).unwrap();
+        assert_eq!(old, Some(server("a", 80)));
+        assert!(toml.upsert_in_array("servers", "name", server("c", 82)).unwrap().is_none());
+        assert_eq!(toml["servers"], Value::Array(vec![server("a", 8080), server("b", 81), server("c", 82)]));
+
+        assert!(toml.upsert_in_array("backup.servers", "name", server("d", 83)).unwrap().is_none());
+        assert_eq!(toml["backup"]["servers"], Value::Array(vec![server("d", 83)]));
+
+        let res = toml.upsert_in_array("servers", "id", server("e", 84));
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+        let res = toml.upsert_in_array("name", "name", server("e", 84));
+        assert!(is_match!(res, Err(Error::TypeError("Array", "String"))));
+        let res = toml.upsert_in_array("servers", "name", Value::from(1));
+        assert!(is_match!(res, Err(Error::TypeError("Table", "Integer"))));
+    }
+
+    #[test]
+    fn test_insert_checked() {
+        let mut toml : Value = toml_from_str(r#"
+        values = [ 1, 2 ]
+
+        [server]
+        port = 80
+        "#).unwrap();
+
+        let res = toml.insert_checked("server", '.', Value::Integer(1));
+        assert!(is_match!(res, Err(Error::WouldShadowTable(ref q, "Table", "Integer")) if q == "server"));
+        let res = toml.insert_checked("server.port", '.', Value::Table(Default::default()));
+        assert!(is_match!(res, Err(Error::WouldShadowTable(ref q, "Integer", "Table")) if q == "server.port"));
+
+        assert!(toml.insert_checked("values.[0]", '.', Value::Integer(0)).is_ok());
+        assert!(toml.insert_checked("client.port", '.', Value::Integer(80)).is_ok());
+        assert!(toml.insert("server", Value::Integer(1)).is_ok());
+    }
+
+    #[test]
+    fn test_insert_quoted_keys() {
+        use read::TomlValueReadExt;
+        use delete::TomlValueDeleteExt;
+
+        let mut toml : Value = toml_from_str(r#"
+        [dependencies]
+        "serde.derive" = "1"
+        "#).unwrap();
+
+        let version = toml.read(r#"dependencies."serde.derive""#).unwrap();
+        assert_eq!(version, Some(&Value::String(String::from("1"))));
+
+        let _ = toml.insert(r#"dependencies.'a b'."[0]""#, Value::Integer(1)).unwrap();
+        assert_eq!(toml["dependencies"]["a b"]["[0]"], Value::Integer(1));
+
+        let old = toml.delete(r#"dependencies."serde.derive""#).unwrap();
+        assert_eq!(old, Some(Value::String(String::from("1"))));
+        assert!(toml["dependencies"].get("serde.derive").is_none());
+    }
+
+    #[test]
+    fn test_insert_escaped_seperator() {
+        use read::TomlValueReadExt;
+
+        let mut toml : Value = toml_from_str("").unwrap();
+        let _ = toml.insert(r"hosts.example\.com.port", Value::Integer(80)).unwrap();
+        assert_eq!(toml["hosts"]["example.com"]["port"], Value::Integer(80));
+        assert_eq!(toml.read(r"hosts.example\.com.port").unwrap(), Some(&Value::Integer(80)));
+        assert_eq!(toml.read(r#"hosts."example.com".port"#).unwrap(), Some(&Value::Integer(80)));
+    }
+
     #[test]
     fn test_insert_with_seperator_into_table() {
         let mut toml : Value = toml_from_str(r#"
@@ -419,5 +741,81 @@ mod test {
         }
     }
 
-}
+    #[test]
+    fn test_insert_below_nonexistent_array_element() {
+        let mut toml : Value = toml_from_str(r#"
+        array = [ { a = 1 } ]
+        "#).unwrap();
+
+        let res = toml.insert_with_seperator(&String::from("array.[3].a"), '.', Value::Integer(6));
+        assert!(is_match!(res, Err(Error::ArrayIndexOutOfBounds(3, 1))));
+    }
+
+    #[test]
+    fn test_insert_with_limits() {
+        let mut toml : Value = toml_from_str(r#"
+        array = [ 1, 2 ]
+        "#).unwrap();
+
+        let value = toml_from_str::<Value>("list = [ 1, 2, 3 ]").unwrap();
+        let limits = InsertLimits::new().max_created_nodes(7).max_array_growth(3);
+
+        assert!(toml.insert_with_limits("a.b.c", '.', value.clone(), &limits).is_ok());
+        assert!(toml.insert_with_limits("array.[0]", '.', Value::Integer(0), &limits).is_ok());
+
+        let res = toml.insert_with_limits("x.y.z.w", '.', value.clone(), &limits);
+        assert!(is_match!(res, Err(Error::LimitExceeded("created nodes", 7, 8))));
+
+        let res = toml.insert_with_limits("x", '.', value, &InsertLimits::new().max_array_growth(2));
+        assert!(is_match!(res, Err(Error::LimitExceeded("array growth", 2, 3))));
+        assert!(toml.read("x").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_insert_fragment() {
+        let mut toml : Value = toml_from_str(r#"
+        [server]
+        host = "localhost"
+        port = 80
 
+        [server.tls]
+        cert = "a.pem"
+        "#).unwrap();
+
+        toml.insert_fragment("server", r#"
+        port = 8080
+
+        [tls]
+        key = "a.key"
+        "#).unwrap();
+        toml.insert_fragment("log.file", "path = 'app.log'").unwrap();
+
+        let expected : Value = toml_from_str(r#"
+        [server]
+        host = "localhost"
+        port = 8080
+
+        [server.tls]
+        cert = "a.pem"
+        key = "a.key"
+
+        [log.file]
+        path = "app.log"
+        "#).unwrap();
+        assert_eq!(toml, expected);
+
+        let res = toml.insert_fragment("server", "port = ");
+        assert!(is_match!(res, Err(Error::FragmentParseError(ref q, _)) if q == "server"));
+    }
+
+    #[test]
+    fn test_insert_below_value() {
+        let mut toml : Value = toml_from_str(r#"
+        val = 1
+        "#).unwrap();
+
+        let res = toml.insert_with_seperator(&String::from("val.a.b"), '.', Value::Integer(6));
+        assert!(is_match!(res, Err(Error::QueryingValueAsTable(_))));
+    }
+
+}
@@ -0,0 +1,38 @@
+//! Instrumentation of the public operations
+//!
+//! With the `tracing` feature, every operation runs inside a `debug` level span named after it
+//! (`toml_query::read`, `toml_query::set`, ...), with the query and the outcome as fields. With
+//! the `logging` feature, the outcome is logged as well, and with the `metrics` feature, it is
+//! counted in the `metrics` module.
+
+/// Run `$body`, the operation `$op` with the query `$query`, and report the outcome
+macro_rules! instrument {
+    ($op:expr, $query:expr, $body:expr) => {{
+        #[cfg(feature = "tracing")]
+        let span = ::tracing::debug_span!(concat!("toml_query::", $op),
+                                          query = $query,
+                                          outcome = ::tracing::field::Empty,
+                                          error = ::tracing::field::Empty);
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        let result = $body;
+
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(_) => {
+                span.record("outcome", &"ok");
+            },
+            Err(ref e) => {
+                span.record("outcome", &"error");
+                span.record("error", &::tracing::field::display(e));
+            },
+        }
+
+        #[cfg(feature = "metrics")]
+        ::metrics::record($op, result.is_err());
+
+        ::util::log_outcome($op, $query, &result);
+        result
+    }};
+}
@@ -8,11 +8,12 @@
 
 // external crates
 
-#[macro_use] extern crate is_match;
-#[macro_use] extern crate lazy_static;
+#[cfg_attr(any(test, feature = "value"), macro_use)] extern crate is_match;
+#[cfg_attr(feature = "parser", macro_use)] extern crate lazy_static;
 #[macro_use] extern crate failure_derive;
 extern crate failure;
 extern crate regex;
+#[cfg(feature = "value")]
 extern crate toml;
 
 #[cfg(feature = "log")]
@@ -21,6 +22,13 @@ extern crate toml;
 #[cfg(feature = "typed")]
 extern crate serde;
 
+#[cfg(feature = "fuzzing")]
+extern crate arbitrary;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
 #[cfg(all(test, feature = "typed"))]
 #[macro_use]
 extern crate serde_derive;
@@ -28,26 +36,118 @@ extern crate serde_derive;
 #[cfg(test)]
 #[macro_use] extern crate quickcheck;
 
+#[cfg(all(not(test), feature = "testutils"))]
+extern crate quickcheck;
+
 // public modules
 
 #[cfg(not(feature = "log"))]
 #[macro_use] pub mod log;
 
+#[cfg(feature = "value")]
+#[macro_use] mod instrument;
+
+#[cfg(feature = "value")]
 extern crate toml_query_derive;
 
+#[cfg(feature = "value")]
 #[doc(hidden)]
 pub use toml_query_derive::*;
 
 pub mod error;
+#[cfg(feature = "value")]
 pub mod read;
+#[cfg(feature = "value")]
 pub mod set;
+#[cfg(feature = "value")]
 pub mod insert;
+#[cfg(feature = "value")]
 pub mod delete;
+#[cfg(feature = "value")]
 pub mod value;
+#[cfg(feature = "value")]
+pub mod schema;
+#[cfg(feature = "value")]
+pub mod validate;
+#[cfg(feature = "parser")]
+pub mod path;
+#[cfg(feature = "parser")]
+pub mod tokenizer;
+#[cfg(feature = "value")]
+pub mod walk;
+#[cfg(feature = "value")]
+pub mod template;
+#[cfg(feature = "value")]
+pub mod size;
+#[cfg(feature = "value")]
+pub mod cursor;
+#[cfg(feature = "value")]
+pub mod document;
+#[cfg(feature = "value")]
+pub mod filter;
+#[cfg(feature = "value")]
+pub mod function;
+#[cfg(feature = "value")]
+pub mod streaming;
+#[cfg(feature = "value")]
+pub mod indexed;
+#[cfg(feature = "value")]
+pub mod index;
+#[cfg(feature = "value")]
+pub mod builder;
+#[cfg(feature = "value")]
+pub mod diff;
+#[cfg(feature = "value")]
+pub mod merge;
+#[cfg(feature = "value")]
+pub mod fuzzy;
+#[cfg(feature = "value")]
+pub mod comments;
+#[cfg(feature = "value")]
+pub mod format;
+#[cfg(feature = "value")]
+pub mod reference;
+#[cfg(feature = "value")]
+pub mod secrets;
+#[cfg(feature = "value")]
+pub mod normalize;
+#[cfg(feature = "value")]
+pub mod deprecation;
+#[cfg(feature = "value")]
+pub mod cache;
+#[cfg(feature = "value")]
+pub mod rename;
+#[cfg(feature = "value")]
+pub mod query;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+
+#[cfg(feature = "testutils")]
+#[macro_use] pub mod testutils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "value")]
 mod util;
 
 // private modules
 
-mod tokenizer;
+#[cfg(feature = "value")]
 mod resolver;
+#[cfg(feature = "value")]
+mod types;
+
+#[cfg(feature = "value")]
+pub use types::Type;
+
+/// The map type of TOML tables
+///
+/// It is a `BTreeMap`, so `walk::paths()`, `TomlValueReadExt::keys_at()`,
+/// `TomlValueReadExt::iter_at()` and all other functions which iterate over tables visit the keys
+/// in sorted order, independent of their order in the document.
+#[cfg(feature = "value")]
+pub use toml::value::Table as Map;
 
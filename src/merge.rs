@@ -0,0 +1,235 @@
+/// Three-way merges of TOML documents
+///
+/// `merge3()` combines the modifications two parties made to the same base document, e.g. a
+/// user edited configuration and an updated template it was created from. Tables are merged key
+/// by key, and arrays of the same length index by index. A value which both parties changed in
+/// different ways is a `Conflict`. Volatile values, like timestamps, can be excluded from
+/// conflicts with `merge3_ignoring()`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use toml::Value;
+
+use path::{PathPattern, Segment, TomlPath};
+
+/// A value which was changed differently in both documents
+///
+/// `None` means the value was removed (or not added).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: TomlPath,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn show(v: &Option<Value>) -> String {
+            v.as_ref().map(|v| v.to_string()).unwrap_or_else(|| String::from("(removed)"))
+        }
+
+        write!(f, "Conflict at '{}': {} vs. {}", self.path, show(&self.ours), show(&self.theirs))
+    }
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`
+///
+/// A value is taken from `theirs` if it is unchanged in `ours`, and from `ours` otherwise. If it
+/// changed in both and the changes differ, it is a conflict, unless it is a table in both
+/// documents (or an array of the same length in all three), in which case its contents are merged.
+///
+/// # Return value
+///
+/// * `Ok(merged)` if there are no conflicts
+/// * `Err(conflicts)` otherwise, ordered by path
+pub fn merge3(base: &Value, ours: &Value, theirs: &Value) -> Result<Value, Vec<Conflict>> {
+    merge3_ignoring(base, ours, theirs, &[])
+}
+
+/// Merge like `merge3()`, but take the values which match one of the `ignore` patterns (and
+/// everything below them) from `ours`, without ever reporting a conflict for them
+pub fn merge3_ignoring(base: &Value, ours: &Value, theirs: &Value, ignore: &[PathPattern])
+    -> Result<Value, Vec<Conflict>>
+{
+    let mut conflicts = Vec::new();
+    let mut merger    = Merger { ignore, conflicts: &mut conflicts };
+    let merged = merger.merge(Some(base), Some(ours), Some(theirs), &mut TomlPath::new());
+
+    if conflicts.is_empty() {
+        Ok(merged.unwrap_or_else(|| Value::Table(BTreeMap::new())))
+    } else {
+        Err(conflicts)
+    }
+}
+
+struct Merger<'a> {
+    ignore: &'a [PathPattern],
+    conflicts: &'a mut Vec<Conflict>,
+}
+
+impl<'a> Merger<'a> {
+
+    fn merge(&mut self, base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>, path: &mut TomlPath)
+        -> Option<Value>
+    {
+        if ours == theirs || theirs == base || self.ignore.iter().any(|p| p.matches(path)) {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+
+        match (base, ours, theirs) {
+            (_, Some(&Value::Table(ref o)), Some(&Value::Table(ref t))) => {
+                let b = match base {
+                    Some(&Value::Table(ref b)) => Some(b),
+                    _                          => None,
+                };
+
+                let mut keys = o.keys().chain(t.keys()).collect::<Vec<_>>();
+                keys.sort();
+                keys.dedup();
+
+                let mut merged = BTreeMap::new();
+                for key in keys {
+                    path.push(Segment::Key(key.clone()));
+                    let base = b.and_then(|b| b.get(key));
+                    if let Some(value) = self.merge(base, o.get(key), t.get(key), path) {
+                        let _ = merged.insert(key.clone(), value);
+                    }
+                    let _ = path.pop();
+                }
+                Some(Value::Table(merged))
+            },
+
+            (Some(&Value::Array(ref b)), Some(&Value::Array(ref o)), Some(&Value::Array(ref t)))
+                if b.len() == o.len() && o.len() == t.len() =>
+            {
+                let mut merged = Vec::with_capacity(o.len());
+                for idx in 0..o.len() {
+                    path.push(Segment::Index(idx));
+                    if let Some(value) = self.merge(b.get(idx), o.get(idx), t.get(idx), path) {
+                        merged.push(value);
+                    }
+                    let _ = path.pop();
+                }
+                Some(Value::Array(merged))
+            },
+
+            _ => {
+                self.conflicts.push(Conflict {
+                    path: path.clone(),
+                    ours: ours.cloned(),
+                    theirs: theirs.cloned(),
+                });
+                ours.cloned()
+            },
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn base() -> Value {
+        toml_from_str(r#"
+        name = "app"
+        ports = [ 80, 443 ]
+
+        [log]
+        level = "info"
+        file = "app.log"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let ours: Value = toml_from_str(r#"
+        name = "my-app"
+        ports = [ 8080, 443 ]
+
+        [log]
+        level = "info"
+        "#).unwrap();
+        let theirs: Value = toml_from_str(r#"
+        name = "app"
+        ports = [ 80, 8443 ]
+        workers = 4
+
+        [log]
+        level = "info"
+        file = "app.log"
+        format = "json"
+        "#).unwrap();
+
+        let expected: Value = toml_from_str(r#"
+        name = "my-app"
+        ports = [ 8080, 8443 ]
+        workers = 4
+
+        [log]
+        level = "info"
+        format = "json"
+        "#).unwrap();
+
+        assert_eq!(merge3(&base(), &ours, &theirs).unwrap(), expected);
+        assert_eq!(merge3(&base(), &ours, &ours).unwrap(), ours);
+    }
+
+    #[test]
+    fn test_ignoring() {
+        let ours: Value = toml_from_str(r#"
+        name = "mine"
+        ports = [ 80, 443 ]
+
+        [log]
+        level = "info"
+        file = "app.log"
+        "#).unwrap();
+        let theirs: Value = toml_from_str(r#"
+        name = "theirs"
+        ports = [ 80, 443 ]
+
+        [log]
+        level = "warn"
+        file = "app.log"
+        "#).unwrap();
+
+        let ignore = vec![PathPattern::new("name").unwrap()];
+        let merged = merge3_ignoring(&base(), &ours, &theirs, &ignore).unwrap();
+        assert_eq!(merged.get("name"), Some(&Value::from("mine")));
+        assert_eq!(merged["log"].get("level"), Some(&Value::from("warn")));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let ours: Value = toml_from_str(r#"
+        name = "mine"
+        ports = [ 80 ]
+
+        [log]
+        level = "debug"
+        "#).unwrap();
+        let theirs: Value = toml_from_str(r#"
+        name = "theirs"
+        ports = [ 80, 443, 8443 ]
+
+        [log]
+        file = "other.log"
+        "#).unwrap();
+
+        let conflicts = merge3(&base(), &ours, &theirs).unwrap_err();
+        let conflicts = conflicts.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(conflicts, vec![
+            "Conflict at 'log.file': (removed) vs. \"other.log\"",
+            "Conflict at 'log.level': \"debug\" vs. (removed)",
+            "Conflict at 'name': \"mine\" vs. \"theirs\"",
+            "Conflict at 'ports': [80] vs. [80, 443, 8443]",
+        ]);
+    }
+
+}
@@ -0,0 +1,97 @@
+/// Counters of the operations on documents
+///
+/// With the `metrics` feature, every read, set, insert and delete is counted in a global
+/// registry, as well as the operations which failed and the tables which inserts created on the
+/// way. `metrics()` returns a snapshot of the counters, which the application can export to its
+/// monitoring system.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static READS: AtomicUsize          = AtomicUsize::new(0);
+static SETS: AtomicUsize           = AtomicUsize::new(0);
+static INSERTS: AtomicUsize        = AtomicUsize::new(0);
+static DELETES: AtomicUsize        = AtomicUsize::new(0);
+static FAILURES: AtomicUsize       = AtomicUsize::new(0);
+static CREATED_TABLES: AtomicUsize = AtomicUsize::new(0);
+
+/// A snapshot of the counters
+///
+/// `reads` counts `read`, `read_mut`, `read_all` and `read_all_mut` operations. Every operation is counted once,
+/// also if it went through a wrapper like `Document`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metrics {
+    pub reads: usize,
+    pub sets: usize,
+    pub inserts: usize,
+    pub deletes: usize,
+    pub failures: usize,
+    pub created_tables: usize,
+}
+
+/// The current values of the counters
+pub fn metrics() -> Metrics {
+    Metrics {
+        reads: READS.load(Ordering::Relaxed),
+        sets: SETS.load(Ordering::Relaxed),
+        inserts: INSERTS.load(Ordering::Relaxed),
+        deletes: DELETES.load(Ordering::Relaxed),
+        failures: FAILURES.load(Ordering::Relaxed),
+        created_tables: CREATED_TABLES.load(Ordering::Relaxed),
+    }
+}
+
+/// Set all counters to zero
+pub fn reset() {
+    for counter in &[&READS, &SETS, &INSERTS, &DELETES, &FAILURES, &CREATED_TABLES] {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Count the operation `operation`, and whether it failed
+pub(crate) fn record(operation: &'static str, failed: bool) {
+    let counter = match operation {
+        "read" | "read_mut" | "read_all" |
+        "read_all_mut"                   => &READS,
+        "set"                            => &SETS,
+        "insert"                         => &INSERTS,
+        "delete"                         => &DELETES,
+        _                                => return,
+    };
+    let _ = counter.fetch_add(1, Ordering::Relaxed);
+
+    if failed {
+        let _ = FAILURES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Count a table which was created as an intermediate of an insert
+pub(crate) fn record_created_table() {
+    let _ = CREATED_TABLES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::Value;
+    use toml::from_str as toml_from_str;
+    use read::TomlValueReadExt;
+    use insert::TomlValueInsertExt;
+
+    #[test]
+    fn test_metrics() {
+        // other tests run in parallel, so only check that the counters grew
+        let before = metrics();
+
+        let mut toml : Value = toml_from_str("[a]").unwrap();
+        let _ = toml.read("a").unwrap();
+        let _ = toml.read("a.[0]").unwrap_err();
+        let _ = toml.insert("b.c.d", Value::Integer(1)).unwrap();
+
+        let after = metrics();
+        assert!(after.reads >= before.reads + 2);
+        assert!(after.inserts > before.inserts);
+        assert!(after.failures > before.failures);
+        assert!(after.created_tables >= before.created_tables + 2);
+    }
+
+}
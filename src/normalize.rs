@@ -0,0 +1,122 @@
+/// Reading with normalized keys
+///
+/// `TomlValueNormalizedReadExt::read_normalized()` compares the keys of the query and the keys
+/// of the tables after normalizing both, so with `snake_case()` the query `max-size` finds the
+/// key `max_size` and the other way round. Meant for configs which mix naming styles.
+
+use toml::Value;
+
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+
+/// Normalizes keys for `TomlValueNormalizedReadExt::read_normalized()`
+///
+/// Keys are equivalent if they normalize to the same string.
+pub trait KeyNormalizer {
+
+    fn normalize(&self, key: &str) -> String;
+
+}
+
+impl<F> KeyNormalizer for F
+    where F: Fn(&str) -> String
+{
+    fn normalize(&self, key: &str) -> String {
+        self(key)
+    }
+}
+
+/// A `KeyNormalizer` which makes `kebab-case` and `snake_case` keys equivalent
+pub fn snake_case(key: &str) -> String {
+    key.replace('-', "_")
+}
+
+pub trait TomlValueNormalizedReadExt<'doc> {
+
+    /// Read the value at `query`, comparing keys after normalizing them with `normalizer`
+    ///
+    /// A key which is in the table as it is takes precedence. Otherwise, if several keys of a
+    /// table are equivalent to the key from the query, the first one in key order is used.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(None)` if there is no value at `query`
+    /// * `Err(_)` in the same cases as `TomlValueReadExt::read()`
+    fn read_normalized<N: KeyNormalizer>(&'doc self, query: &str, normalizer: &N) -> Result<Option<&'doc Value>> {
+        self.read_normalized_with_seperator(query, '.', normalizer)
+    }
+
+    fn read_normalized_with_seperator<N: KeyNormalizer>(&'doc self, query: &str, sep: char, normalizer: &N)
+        -> Result<Option<&'doc Value>>;
+
+}
+
+impl<'doc> TomlValueNormalizedReadExt<'doc> for Value {
+
+    fn read_normalized_with_seperator<N: KeyNormalizer>(&'doc self, query: &str, sep: char, normalizer: &N)
+        -> Result<Option<&'doc Value>>
+    {
+        let mut value = self;
+
+        for segment in TomlPath::from_query(query, sep)?.segments() {
+            let next = match (value, segment) {
+                (&Value::Table(ref t), &Segment::Key(ref key)) => t.get(key).or_else(|| {
+                    let normalized = normalizer.normalize(key);
+                    t.iter().find(|&(k, _)| normalizer.normalize(k) == normalized).map(|(_, v)| v)
+                }),
+                (&Value::Array(ref a), &Segment::Index(idx))   => a.get(idx),
+                (&Value::Table(_), &Segment::Index(idx))       => return Err(Error::NoIndexInTable(idx)),
+                (&Value::Array(_), &Segment::Key(ref key))     => return Err(Error::NoIdentifierInArray(key.clone())),
+                (_, &Segment::Key(ref key))                    => return Err(Error::QueryingValueAsTable(key.clone())),
+                (_, &Segment::Index(idx))                      => return Err(Error::QueryingValueAsArray(idx)),
+            };
+
+            match next {
+                Some(sub) => value = sub,
+                None      => return Ok(None),
+            }
+        }
+
+        Ok(Some(value))
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Value {
+        toml_from_str(r#"
+        [build-options]
+        max_size = 1
+        opt-level = 2
+        "both-ways" = 3
+        both_ways = 4
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_snake_case() {
+        let doc = doc();
+        let read = |q| doc.read_normalized(q, &snake_case).unwrap().cloned();
+        assert_eq!(read("build_options.max-size"), Some(Value::Integer(1)));
+        assert_eq!(read("build-options.opt_level"), Some(Value::Integer(2)));
+        assert_eq!(read("build-options.both-ways"), Some(Value::Integer(3)));
+        assert_eq!(read("build-options.both_ways"), Some(Value::Integer(4)));
+        assert_eq!(read("build-options.missing"), None);
+    }
+
+    #[test]
+    fn test_custom_normalizer() {
+        let doc = doc();
+        let normalizer = |key: &str| key.to_lowercase().replace(&['-', '_'][..], "");
+        let value = doc.read_normalized("Build_Options.MaxSize", &normalizer).unwrap();
+        assert_eq!(value, Some(&Value::Integer(1)));
+
+        let res = doc.read_normalized("build_options.[0]", &normalizer);
+        assert!(is_match!(res, Err(Error::NoIndexInTable(0))));
+    }
+
+}
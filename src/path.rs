@@ -0,0 +1,562 @@
+/// Paths into TOML documents
+///
+/// A `TomlPath` is the resolved form of a query: a list of table keys and array indices. It is
+/// what the library hands out when it reports locations in a document.
+///
+/// Paths can also be built directly, e.g. `Path::new().key("a").key("weird.key").index(0)`, and
+/// be passed to `read_path()`, `set_path()`, `insert_path()` and `delete_path()`. Their keys are
+/// never parsed, so they can contain seperators, quotes and anything else.
+///
+/// A `PathPattern` describes a set of paths, e.g. `*.checksum`, for selecting values without
+/// listing all their paths.
+
+#[cfg(feature = "value")]
+use std::borrow::Cow;
+use std::fmt;
+
+#[cfg(feature = "value")]
+use toml::Value;
+
+use tokenizer::{Part, Token, split_query, tokenize_with_seperator};
+#[cfg(feature = "value")]
+use tokenizer::Tokens;
+use error::{Error, Result};
+
+/// A single step in a `TomlPath`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Keys which would not be read back as the same key are written in double quotes
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Segment::Key(ref key)   => write_key(f, key),
+            Segment::Index(idx)     => write!(f, "[{}]", idx),
+        }
+    }
+}
+
+/// Write `key` so that it is read back as the same key, in double quotes if necessary
+pub(crate) fn write_key(f: &mut fmt::Formatter, key: &str) -> fmt::Result {
+    if needs_quotes(key) {
+        write!(f, "\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        write!(f, "{}", key)
+    }
+}
+
+fn needs_quotes(key: &str) -> bool {
+    key.is_empty()
+        || key == "*"
+        || key.contains('.')
+        || key.contains('\\')
+        || key.starts_with('"')
+        || key.starts_with('\'')
+        || key.starts_with('[')
+        || key.contains("[\"")
+        || key.contains("['")
+        || (key.starts_with('{') && key.ends_with('}'))
+}
+
+/// A path into a TOML document
+///
+/// The `Display` implementation renders the path as a query string with `.` as seperator, so it
+/// can be passed to `read()` and friends again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct TomlPath(Vec<Segment>);
+
+/// `TomlPath` under the name used for building paths, see `TomlPath::key()`
+pub type Path = TomlPath;
+
+impl TomlPath {
+
+    /// The empty path, pointing to the document itself
+    pub fn new() -> TomlPath {
+        TomlPath::default()
+    }
+
+    /// Fails for indices counted from the end and for wildcards, they only name values together
+    /// with a document
+    pub(crate) fn from_tokens(tokens: &[Token]) -> Result<TomlPath> {
+        let mut path = TomlPath::new();
+
+        for token in tokens {
+            match *token {
+                Token::Identifier { ref ident, .. }       => path.push(Segment::Key(ident.to_string())),
+                Token::Index { idx, from_end: false, .. } => path.push(Segment::Index(idx)),
+                Token::Index { idx, from_end: true, .. }  => return Err(Error::IndexFromEndInPath(idx)),
+                Token::Wildcard { .. } |
+                Token::Union { .. } |
+                Token::Filter { .. }                      => return Err(Error::UnexpectedMultiMatch),
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// The tokens for this path, for resolving it without parsing a query
+    ///
+    /// Fails for the empty path, like the empty query.
+    #[cfg(feature = "value")]
+    pub(crate) fn to_tokens(&self) -> Result<Tokens<'_>> {
+        if self.is_empty() {
+            return Err(Error::EmptyQueryError);
+        }
+
+        Ok(self.0.iter()
+            .map(|segment| match *segment {
+                Segment::Key(ref key) => Token::Identifier { ident: Cow::Borrowed(key) },
+                Segment::Index(idx)   => Token::Index { idx, from_end: false },
+            })
+            .collect())
+    }
+
+    /// Parse `query`, with `sep` as seperator
+    pub fn from_query(query: &str, sep: char) -> Result<TomlPath> {
+        tokenize_with_seperator(query, sep).and_then(|t| TomlPath::from_tokens(&t))
+    }
+
+    /// Parse `query`, with `sep` as seperator, counting indices from the end of the arrays in
+    /// `value`
+    ///
+    /// `[-1]` and `[last]` become the index of the last element, `[+]` the index after it. Fails
+    /// for wildcards like `from_query()`. If there is no array to count from, the index is kept
+    /// as it is, so resolving the path fails like resolving the query.
+    #[cfg(feature = "value")]
+    pub(crate) fn from_query_in(query: &str, sep: char, value: &Value) -> Result<TomlPath> {
+        TomlPath::from_query_counting(query, sep, |path| {
+            value_at(value, path.segments()).and_then(Value::as_array).map(Vec::len)
+        })
+    }
+
+    /// Parse `query` like `from_query_in()`, with `array_len` telling the length of the array at
+    /// a path, if there is one
+    #[cfg(feature = "value")]
+    pub(crate) fn from_query_counting<F>(query: &str, sep: char, array_len: F) -> Result<TomlPath>
+        where F: Fn(&TomlPath) -> Option<usize>
+    {
+        let mut path = TomlPath::new();
+
+        for token in tokenize_with_seperator(query, sep)?.iter() {
+            match *token {
+                Token::Index { idx, from_end: true } => match array_len(&path) {
+                    Some(len) if idx <= len => path.push(Segment::Index(len - idx)),
+                    Some(len)               => return Err(Error::IndexFromEndOutOfBounds(idx, len)),
+                    None                    => path.push(Segment::Index(idx)),
+                },
+                ref other => path.0.extend(TomlPath::from_tokens(::std::slice::from_ref(other))?.0),
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// This path with the key `key` appended
+    ///
+    /// The key is used as it is, it is not parsed as a query.
+    pub fn key<K: Into<String>>(mut self, key: K) -> TomlPath {
+        self.push(Segment::Key(key.into()));
+        self
+    }
+
+    /// This path with the array index `idx` appended
+    pub fn index(mut self, idx: usize) -> TomlPath {
+        self.push(Segment::Index(idx));
+        self
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `prefix` is a prefix of this path, or equal to it
+    pub fn starts_with(&self, prefix: &TomlPath) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// The path without `prefix`, or `None` if `prefix` is not a prefix of this path
+    pub fn strip_prefix(&self, prefix: &TomlPath) -> Option<TomlPath> {
+        if self.starts_with(prefix) {
+            Some(TomlPath(self.0[prefix.len()..].to_vec()))
+        } else {
+            None
+        }
+    }
+
+    /// The path of the table or array containing the value at this path
+    ///
+    /// Returns `None` for the empty path.
+    pub fn parent(&self) -> Option<TomlPath> {
+        self.0.split_last().map(|(_, parent)| TomlPath(parent.to_vec()))
+    }
+
+    /// The key or index of the value at this path in its table or array
+    pub fn last(&self) -> Option<&Segment> {
+        self.0.last()
+    }
+
+    /// This path with `segment` appended
+    pub fn join(&self, segment: Segment) -> TomlPath {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// Whether this path matches `pattern`, see `PathPattern`
+    ///
+    /// Invalid patterns match no path. To match many paths against the same pattern, or to get
+    /// the parse error, use `PathPattern::new()` and `PathPattern::matches()`.
+    pub fn matches(&self, pattern: &str) -> bool {
+        PathPattern::new(pattern).map(|p| p.matches(self)).unwrap_or(false)
+    }
+
+    pub fn push(&mut self, segment: Segment) {
+        self.0.push(segment)
+    }
+
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.0.pop()
+    }
+
+}
+
+/// The value at the path `segments` below `value`
+#[cfg(feature = "value")]
+pub(crate) fn value_at<'a>(mut value: &'a Value, segments: &[Segment]) -> Option<&'a Value> {
+    for segment in segments {
+        value = match (value, segment) {
+            (&Value::Table(ref t), &Segment::Key(ref key)) => t.get(key)?,
+            (&Value::Array(ref a), &Segment::Index(idx))   => a.get(idx)?,
+            _                                              => return None,
+        };
+    }
+    Some(value)
+}
+
+#[cfg(feature = "value")]
+pub(crate) fn value_at_mut<'a>(mut value: &'a mut Value, segments: &[Segment]) -> Option<&'a mut Value> {
+    for segment in segments {
+        value = match (value, segment) {
+            (&mut Value::Table(ref mut t), &Segment::Key(ref key)) => t.get_mut(key)?,
+            (&mut Value::Array(ref mut a), &Segment::Index(idx))   => a.get_mut(idx)?,
+            _                                                      => return None,
+        };
+    }
+    Some(value)
+}
+
+impl From<Vec<Segment>> for TomlPath {
+    fn from(segments: Vec<Segment>) -> TomlPath {
+        TomlPath(segments)
+    }
+}
+
+impl fmt::Display for TomlPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single step in a `PathPattern`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PatternSegment {
+    Key(String),
+    Index(usize),
+
+    /// `*`, any key
+    AnyKey,
+
+    /// `[*]`, any index
+    AnyIndex,
+
+    /// `**`, any number of segments, including none
+    AnyDepth,
+}
+
+/// A pattern for `TomlPath`s
+///
+/// Patterns are written like queries, with `*` matching any key, `[*]` any index and `**` any
+/// number of keys and indices. For example, `*.checksum` matches `a.checksum` but not
+/// `a.b.checksum`, while `**.checksum` matches both. Keys are quoted and escaped like in queries,
+/// so `"*"` matches only the key `*`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathPattern(Vec<PatternSegment>);
+
+impl PathPattern {
+
+    pub fn new(pattern: &str) -> Result<PathPattern> {
+        PathPattern::with_seperator(pattern, '.')
+    }
+
+    pub fn with_seperator(pattern: &str, sep: char) -> Result<PathPattern> {
+        if pattern.is_empty() {
+            return Err(Error::EmptyQueryError);
+        }
+
+        split_query(pattern, sep)?
+            .into_iter()
+            .map(|(part, _)| match part {
+                Part::Quoted(key) => Ok(PatternSegment::Key(key.into_owned())),
+                Part::Plain(part) => pattern_segment(&part),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(PathPattern)
+    }
+
+
+    pub fn segments(&self) -> &[PatternSegment] {
+        &self.0
+    }
+
+    /// Whether `path` matches the pattern
+    pub fn matches(&self, path: &TomlPath) -> bool {
+        matches(&self.0, path.segments())
+    }
+
+    /// Whether `path`, or one of the paths above it, matches the pattern
+    pub fn matches_at_or_above(&self, path: &TomlPath) -> bool {
+        (0..=path.len()).any(|len| matches(&self.0, &path.segments()[..len]))
+    }
+
+}
+
+/// The pattern segment for an unquoted part of a pattern
+fn pattern_segment(part: &str) -> Result<PatternSegment> {
+    match part {
+        ""    => Err(Error::EmptyIdentifier),
+        "**"  => Ok(PatternSegment::AnyDepth),
+        "*"   => Ok(PatternSegment::AnyKey),
+        "[*]" => Ok(PatternSegment::AnyIndex),
+        _ if part.starts_with('[') => {
+            if !part.ends_with(']') {
+                return Err(Error::ArrayAccessWithoutIndex);
+            }
+            part[1..part.len() - 1]
+                .parse()
+                .map(PatternSegment::Index)
+                .map_err(|_| Error::ArrayAccessWithInvalidIndex)
+        },
+        _ => Ok(PatternSegment::Key(String::from(part))),
+    }
+}
+
+fn matches(pattern: &[PatternSegment], path: &[Segment]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None)                             => true,
+        (Some(&PatternSegment::AnyDepth), _)     => {
+            matches(&pattern[1..], path) || (!path.is_empty() && matches(pattern, &path[1..]))
+        },
+        (None, Some(_)) | (Some(_), None)        => false,
+        (Some(p), Some(s))                       => {
+            let matching = match (p, s) {
+                (&PatternSegment::Key(ref k), &Segment::Key(ref key)) => k == key,
+                (&PatternSegment::Index(i), &Segment::Index(idx))    => i == idx,
+                (&PatternSegment::AnyKey, &Segment::Key(_))          => true,
+                (&PatternSegment::AnyIndex, &Segment::Index(_))      => true,
+                _                                                    => false,
+            };
+            matching && matches(&pattern[1..], &path[1..])
+        },
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ".")?;
+            }
+            match *segment {
+                PatternSegment::Key(ref key) if key == "**" => write!(f, "\"**\"")?,
+                PatternSegment::Key(ref key) => write_key(f, key)?,
+                PatternSegment::Index(idx)   => write!(f, "[{}]", idx)?,
+                PatternSegment::AnyKey       => write!(f, "*")?,
+                PatternSegment::AnyIndex     => write!(f, "[*]")?,
+                PatternSegment::AnyDepth     => write!(f, "**")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let path = TomlPath::from(vec![
+            Segment::Key(String::from("a")),
+            Segment::Index(1),
+            Segment::Key(String::from("b")),
+        ]);
+
+        assert_eq!(path.to_string(), "a.[1].b");
+    }
+
+    #[test]
+    fn test_display_quoted() {
+        let path = TomlPath::from(vec![
+            Segment::Key(String::from("serde.derive")),
+            Segment::Key(String::from("[0]")),
+            Segment::Key(String::from(r#""a\b""#)),
+            Segment::Key(String::new()),
+            Segment::Key(String::from("a b")),
+            Segment::Key(String::from(r#"x["y"]"#)),
+        ]);
+
+        assert_eq!(path.to_string(), r#""serde.derive"."[0]"."\"a\\b\""."".a b."x[\"y\"]""#);
+        assert_eq!(TomlPath::from_query(&path.to_string(), '.').unwrap(), path);
+    }
+
+    #[test]
+    fn test_builder() {
+        let path = Path::new().key("a").key("weird.key").index(0).key("*").key("{b,c}").key(r"d\");
+
+        assert_eq!(path.len(), 6);
+        assert_eq!(path.segments()[2], Segment::Index(0));
+        assert_eq!(path.to_string(), r#"a."weird.key".[0]."*"."{b,c}"."d\\""#);
+        assert_eq!(TomlPath::from_query(&path.to_string(), '.').unwrap(), path);
+    }
+
+    #[cfg(feature = "value")]
+    #[test]
+    fn test_to_tokens() {
+        let path = Path::new().key("a").key("weird.key").index(0).key("*");
+        assert_eq!(TomlPath::from_tokens(&path.to_tokens().unwrap()).unwrap(), path);
+        assert!(is_match!(Path::new().to_tokens(), Err(Error::EmptyQueryError)));
+    }
+
+    #[cfg(feature = "value")]
+    #[test]
+    fn test_from_query_in() {
+        let value : Value = ::toml::from_str("a = [ [ 1, 2 ], [ 3 ] ]").unwrap();
+        let path = |q: &str| TomlPath::from_query_in(q, '.', &value).map(|p| p.to_string());
+
+        assert_eq!(path("a.[-1].[last]").unwrap(), "a.[1].[0]");
+        assert_eq!(path("a.[0].[+]").unwrap(), "a.[0].[2]");
+        assert!(is_match!(path("a.[-3]"), Err(Error::IndexFromEndOutOfBounds(3, 2))));
+        assert_eq!(path("b.[-1]").unwrap(), "b.[1]");
+        assert!(is_match!(path("a.*"), Err(Error::UnexpectedMultiMatch)));
+    }
+
+    #[cfg(feature = "value")]
+    #[test]
+    fn test_value_operations() {
+        use read::TomlValueReadExt;
+        use set::TomlValueSetExt;
+        use insert::TomlValueInsertExt;
+        use delete::TomlValueDeleteExt;
+
+        let mut toml = Value::Table(Default::default());
+        let path = Path::new().key("weird.key").key("*");
+
+        assert!(toml.insert_path(&path, Value::Integer(1)).unwrap().is_none());
+        assert_eq!(toml.read_path(&path).unwrap(), Some(&Value::Integer(1)));
+        assert!(toml.read("weird").unwrap().is_none());
+
+        assert_eq!(toml.set_path(&path, Value::Integer(2)).unwrap(), Some(Value::Integer(1)));
+        *toml.read_path_mut(&path).unwrap().unwrap() = Value::Integer(3);
+        assert_eq!(toml.delete_path(&path).unwrap(), Some(Value::Integer(3)));
+        assert!(toml.read_path(&path).unwrap().is_none());
+        assert!(is_match!(toml.read_path(&Path::new()), Err(Error::EmptyQueryError)));
+    }
+
+    #[test]
+    fn test_from_tokens() {
+        let tokens = tokenize_with_seperator("a.[1].b", '.').unwrap();
+        let path   = TomlPath::from_tokens(&tokens).unwrap();
+
+        assert_eq!(path.len(), 3);
+        assert_eq!(path.to_string(), "a.[1].b");
+
+        let tokens = tokenize_with_seperator("a.[-1]", '.').unwrap();
+        assert!(is_match!(TomlPath::from_tokens(&tokens), Err(Error::IndexFromEndInPath(1))));
+    }
+
+    #[test]
+    fn test_starts_with() {
+        let path   = TomlPath::from_tokens(&tokenize_with_seperator("a.[1].b", '.').unwrap()).unwrap();
+        let prefix = TomlPath::from_tokens(&tokenize_with_seperator("a.[1]", '.').unwrap()).unwrap();
+
+        assert!(path.starts_with(&prefix));
+        assert!(path.starts_with(&path));
+        assert!(path.starts_with(&TomlPath::new()));
+        assert!(!prefix.starts_with(&path));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let path = TomlPath::from_query("a.[1].b", '.').unwrap();
+        let parent = path.parent().unwrap();
+
+        assert_eq!(parent.to_string(), "a.[1]");
+        assert_eq!(path.last(), Some(&Segment::Key(String::from("b"))));
+        assert_eq!(parent.join(Segment::Key(String::from("b"))), path);
+        assert_eq!(path.strip_prefix(&parent).unwrap().to_string(), "b");
+        assert_eq!(path.strip_prefix(&TomlPath::new()), Some(path.clone()));
+        assert!(parent.strip_prefix(&path).is_none());
+        assert!(TomlPath::new().parent().is_none());
+        assert!(TomlPath::new().last().is_none());
+    }
+
+    #[test]
+    fn test_pattern() {
+        let path = |q: &str| TomlPath::from_query(q, '.').unwrap();
+        let pattern = |p: &str| PathPattern::new(p).unwrap();
+
+        assert!(pattern("*.checksum").matches(&path("a.checksum")));
+        assert!(!pattern("*.checksum").matches(&path("a.b.checksum")));
+        assert!(pattern("**.checksum").matches(&path("a.b.checksum")));
+        assert!(pattern("**.checksum").matches(&path("checksum")));
+        assert!(pattern("bin.[*].name").matches(&path("bin.[3].name")));
+        assert!(!pattern("bin.*.name").matches(&path("bin.[3].name")));
+        assert!(pattern("bin.[3]").matches(&path("bin.[3]")));
+        assert!(pattern("meta").matches_at_or_above(&path("meta.generated_at")));
+        assert!(!pattern("meta").matches(&path("meta.generated_at")));
+
+        assert!(path("a.[0].b").matches("a.[*].*"));
+        assert!(path("a.[0].b").matches("**"));
+        assert!(!path("a.[0].b").matches("a..b"));
+
+        assert_eq!(pattern("a.*.[*].**").to_string(), "a.*.[*].**");
+        assert!(is_match!(PathPattern::new("a..b"), Err(Error::EmptyIdentifier)));
+        assert!(is_match!(PathPattern::new("a.[x]"), Err(Error::ArrayAccessWithInvalidIndex)));
+    }
+
+    #[test]
+    fn test_pattern_quoted_keys() {
+        let path = |q: &str| TomlPath::from_query(q, '.').unwrap();
+
+        let quoted = PathPattern::new(r#"*."a.b".'*'"#).unwrap();
+        assert_eq!(quoted.segments().len(), 3);
+        assert!(quoted.matches(&path(r#"x."a.b"."*""#)));
+        assert!(!quoted.matches(&path(r#"x."a.b".c"#)));
+        assert_eq!(PathPattern::new(&quoted.to_string()).unwrap(), quoted);
+
+        let escaped = PathPattern::with_seperator(r"a\/b/**", '/').unwrap();
+        assert_eq!(escaped.segments()[0], PatternSegment::Key(String::from("a/b")));
+        assert!(escaped.matches(&path(r#""a/b".c.d"#)));
+
+        let bracketed = PathPattern::new(r#"a["b.c"].[*]"#).unwrap();
+        assert!(bracketed.matches(&path(r#"a."b.c".[0]"#)));
+    }
+
+}
@@ -0,0 +1,142 @@
+/// Precompiled queries
+///
+/// A `CompiledQuery` is tokenized once and can then be run against many documents, without
+/// parsing the query string again for every document.
+
+use std::fmt;
+use std::str::FromStr;
+
+use toml::Value;
+
+use tokenizer::{Tokens, tokenize_with_max_depth, DEFAULT_MAX_DEPTH};
+use path::TomlPath;
+use error::{Error, Result};
+
+/// A tokenized query
+///
+/// The operations behave like the ones of the `Ext` traits for `toml::Value` with the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledQuery {
+    query: String,
+    tokens: Tokens<'static>,
+}
+
+impl CompiledQuery {
+
+    pub fn new(query: &str) -> Result<CompiledQuery> {
+        CompiledQuery::with_seperator(query, '.')
+    }
+
+    pub fn with_seperator(query: &str, sep: char) -> Result<CompiledQuery> {
+        CompiledQuery::with_max_depth(query, sep, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Compile `query`, which may have up to `max_depth` segments
+    pub fn with_max_depth(query: &str, sep: char, max_depth: usize) -> Result<CompiledQuery> {
+        tokenize_with_max_depth(query, sep, max_depth).map(|tokens| CompiledQuery {
+            query: String::from(query),
+            tokens: tokens.into_owned(),
+        })
+    }
+
+    /// The query this was compiled from
+    pub fn as_str(&self) -> &str {
+        &self.query
+    }
+
+    /// See `TomlValueReadExt::read()`
+    pub fn read<'doc>(&self, document: &'doc Value) -> Result<Option<&'doc Value>> {
+        use resolver::non_mut_resolver::resolve;
+
+        instrument!("read", &self.query, resolve(document, &self.tokens, false))
+    }
+
+    /// See `TomlValueReadExt::read_mut()`
+    pub fn read_mut<'doc>(&self, document: &'doc mut Value) -> Result<Option<&'doc mut Value>> {
+        use resolver::mut_resolver::resolve;
+
+        instrument!("read_mut", &self.query, resolve(document, &self.tokens, false))
+    }
+
+    /// See `TomlValueReadAllExt::read_all()`
+    pub fn read_all<'doc>(&self, document: &'doc Value) -> Result<Vec<(TomlPath, &'doc Value)>> {
+        use resolver::multi_resolver::resolve;
+
+        instrument!("read_all", &self.query, {
+            let mut results = Vec::new();
+            resolve(document, &self.tokens, &mut TomlPath::new(), false, &mut results).map(|_| results)
+        })
+    }
+
+    /// See `TomlValueSetExt::set()`
+    pub fn set(&self, document: &mut Value, value: Value) -> Result<Option<Value>> {
+        instrument!("set", &self.query, ::set::set_impl(document, &self.tokens, value))
+    }
+
+    /// See `TomlValueInsertExt::insert()`
+    pub fn insert(&self, document: &mut Value, value: Value) -> Result<Option<Value>> {
+        instrument!("insert", &self.query, ::insert::insert_impl(document, &self.tokens, value))
+    }
+
+    /// See `TomlValueDeleteExt::delete()`
+    pub fn delete(&self, document: &mut Value) -> Result<Option<Value>> {
+        instrument!("delete", &self.query, ::delete::delete_impl(document, &self.tokens))
+    }
+
+}
+
+impl FromStr for CompiledQuery {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<CompiledQuery> {
+        CompiledQuery::new(query)
+    }
+}
+
+impl fmt::Display for CompiledQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_compiled_query() {
+        let query : CompiledQuery = "table.a".parse().unwrap();
+
+        for i in 0..3 {
+            let mut doc : Value = toml_from_str(&format!("[table]\na = {}", i)).unwrap();
+            assert_eq!(query.read(&doc).unwrap(), Some(&Value::Integer(i)));
+            assert_eq!(query.set(&mut doc, Value::Integer(10)).unwrap(), Some(Value::Integer(i)));
+            assert_eq!(query.delete(&mut doc).unwrap(), Some(Value::Integer(10)));
+            assert!(query.read(&doc).unwrap().is_none());
+            assert!(query.insert(&mut doc, Value::Integer(20)).unwrap().is_none());
+            assert_eq!(query.read_mut(&mut doc).unwrap(), Some(&mut Value::Integer(20)));
+        }
+
+        assert_eq!(query.to_string(), "table.a");
+        assert!(is_match!(CompiledQuery::new(""), Err(Error::EmptyQueryError)));
+        assert!(is_match!(CompiledQuery::with_max_depth("a.b", '.', 1), Err(Error::QueryTooDeep(2, 1))));
+    }
+
+    #[test]
+    fn test_compiled_query_all() {
+        let query = CompiledQuery::with_seperator("deps/*/version", '/').unwrap();
+        let doc : Value = toml_from_str(r#"
+        [deps.a]
+        version = "1"
+        [deps.b]
+        version = "2"
+        "#).unwrap();
+
+        let found = query.read_all(&doc).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].0.to_string(), "deps.b.version");
+        assert!(is_match!(query.read(&doc), Err(Error::UnexpectedMultiMatch)));
+    }
+
+}
@@ -1,14 +1,21 @@
 /// The Toml Read extensions
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 #[cfg(feature = "typed")]
 use std::fmt::Debug;
 
 #[cfg(feature = "typed")]
 use serde::{Serialize, Deserialize};
 use toml::Value;
+use toml::value::Datetime;
 
-use tokenizer::tokenize_with_seperator;
-use error::{Error, Result};
+use tokenizer::{tokenize_with_seperator, tokenize_with_syntax, Syntax};
+use path::{Segment, TomlPath};
+use cursor::Cursor;
+use function::QueryOptions;
+use format::FormatStyle;
+use error::{Error, ReadError, Result};
 
 pub trait TomlValueReadExt<'doc> {
 
@@ -30,6 +37,220 @@ pub trait TomlValueReadExt<'doc> {
         self.read_mut_with_seperator(query, '.')
     }
 
+    /// Read the value at `query` together with its type
+    ///
+    /// For dispatching on the type of values which are not known in advance, without matching on
+    /// `Value`. `Ok(None)` if there is no value at `query`.
+    fn read_typed(&'doc self, query: &str) -> Result<Option<(Type, &'doc Value)>> {
+        self.read(query).map(|o| o.map(|v| (Type::from(v), v)))
+    }
+
+    /// Read the value at `path`
+    ///
+    /// Unlike a query, the keys of `path` are never parsed, see `path::Path`.
+    fn read_path(&'doc self, path: &TomlPath) -> Result<Option<&'doc Value>> {
+        self.read_with_seperator(&path.to_string(), '.')
+    }
+
+    /// Read the value at `path` mutably
+    ///
+    /// See documentation of `TomlValueReadExt::read_path`
+    fn read_path_mut(&'doc mut self, path: &TomlPath) -> Result<Option<&'doc mut Value>> {
+        self.read_mut_with_seperator(&path.to_string(), '.')
+    }
+
+    /// Read the value at `query`, where the last segment may be a function call
+    ///
+    /// A query like `package.name.lower()` reads `package.name` and calls the function `lower`
+    /// from `options` with the value. Without a function call, the value is returned as is. A
+    /// call needs a value to be called with, so a call on its own like `len()` fails with
+    /// `Error::EmptyQueryError`. The segments of `query` are separated by
+    /// `QueryOptions::seperator()`.
+    ///
+    /// Only the functions registered in `options` can be called. Unlike in filter expressions,
+    /// the builtins are not available unless registered with
+    /// `QueryOptions::with_functions(Functions::builtins())`.
+    fn read_with_options(&'doc self, query: &str, options: &QueryOptions) -> Result<Option<Value>> {
+        let call = Some(query).filter(|q| q.ends_with("()")).and_then(|q| {
+            let q = &q[..q.len() - 2];
+            let (prefix, name) = match q.rfind(options.seperator()) {
+                Some(pos) => (&q[..pos], &q[pos + options.seperator().len_utf8()..]),
+                None      => ("", q),
+            };
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                Some((prefix, name))
+            } else {
+                None
+            }
+        });
+
+        if options.syntax() == Syntax::V1Basic {
+            if let Some((_, name)) = call {
+                return Err(Error::ExtendedSyntax(format!("{}()", name)));
+            }
+            let _ = tokenize_with_syntax(query, options.seperator(), Syntax::V1Basic)?;
+        }
+
+        match call {
+            Some(("", _)) => Err(Error::EmptyQueryError),
+            Some((prefix, name)) => match self.read_with_seperator(prefix, options.seperator())? {
+                Some(v) => options.functions().call(name, ::std::slice::from_ref(v)).map(Some),
+                None    => Ok(None),
+            },
+            None => self.read_with_seperator(query, options.seperator()).map(|o| o.cloned()),
+        }
+    }
+
+    /// Get a `Cursor` for the value at `query`
+    ///
+    /// Reads through the cursor are relative to the value and do not resolve `query` again.
+    fn cursor(&'doc self, query: &str) -> Result<Option<Cursor<'doc>>> {
+        self.read(query).map(|o| o.map(Cursor::new))
+    }
+
+    /// List the paths of all values below the value at `query`, up to `max_depth` levels deep
+    ///
+    /// The returned paths start with the segments of `query`, so they can be used to query the
+    /// document again. See `walk::walk()` for the order of the paths.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * An empty list if the value at `query` is neither a Table nor an Array
+    fn keys_at(&'doc self, query: &str, max_depth: usize) -> Result<Vec<TomlPath>> {
+        let prefix = path_in(self, query, '.')?;
+        let value  = self.read(query)?
+            .ok_or_else(|| Error::NotAvailable(String::from(query)))?;
+
+        let mut paths = Vec::new();
+        ::walk::walk(value, Some(max_depth), &mut |path, _| {
+            let mut full = prefix.clone();
+            for segment in path.segments() {
+                full.push(segment.clone());
+            }
+            paths.push(full);
+        });
+
+        Ok(paths)
+    }
+
+    /// Approximate the memory used by the value at `query` and everything below it, in bytes
+    ///
+    /// See `size::memory_footprint()`. Returns `Err(Error::NotAvailable(_))` if there is no value
+    /// at `query`.
+    fn memory_footprint(&'doc self, query: &str) -> Result<usize> {
+        self.read(query)?
+            .map(::size::memory_footprint)
+            .ok_or_else(|| Error::NotAvailable(String::from(query)))
+    }
+
+    /// Find the table in the array at `query` whose `key_field` is `key_value`
+    ///
+    /// For example, `find_in_array("bin", "name", &Value::from("server"))` finds the `[[bin]]`
+    /// with `name = "server"`. If several tables match, the first one is returned. Elements which
+    /// are not tables are skipped.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(None)` if there is no value at `query` or no table matches
+    /// * `Err(Error::TypeError(_, _))` if the value at `query` is not an Array
+    fn find_in_array(&'doc self, query: &str, key_field: &str, key_value: &Value) -> Result<Option<&'doc Value>> {
+        match self.read(query)? {
+            Some(&Value::Array(ref a)) => Ok(a.iter().find(|element| match **element {
+                Value::Table(ref t) => t.get(key_field) == Some(key_value),
+                _                   => false,
+            })),
+            Some(other) => Err(Error::TypeError("Array", ::util::name_of_val(other))),
+            None        => Ok(None),
+        }
+    }
+
+    /// Serialize the value at `query` as a standalone TOML document
+    ///
+    /// A table becomes the root table of the document. Any other value is put into a root table,
+    /// with the last key of `query` as its key.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * `Err(Error::TypeErrorAt(_, _, _))` if the value is not a Table and `query` ends with an
+    ///   index, so there is no key for it
+    /// * `Err(Error::CannotSerialize(_, _))` if the value cannot be represented as TOML
+    fn serialize_at(&'doc self, query: &str) -> Result<String> {
+        let root = standalone(self.read(query)?, query)?;
+        ::toml::to_string(&*root).map_err(|e| Error::CannotSerialize(String::from(query), e.to_string()))
+    }
+
+    /// Serialize the value at `query` like `serialize_at()`, but in `style`
+    fn serialize_at_with(&'doc self, query: &str, style: &FormatStyle) -> Result<String> {
+        let root = standalone(self.read(query)?, query)?;
+        ::format::to_string_with(&root, style)
+    }
+
+    /// Iterate over the entries of the table or array at `query`
+    ///
+    /// Table entries are yielded in sorted key order (see `Map`) with `Segment::Key`, array
+    /// entries in index order with `Segment::Index`.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * `Err(Error::TypeError(_, _))` if the value at `query` is neither a Table nor an Array
+    fn iter_at(&'doc self, query: &str) -> Result<Entries<'doc>> {
+        match self.read(query)? {
+            Some(&Value::Table(ref t)) => Ok(Entries(EntriesInner::Table(t.iter()))),
+            Some(&Value::Array(ref a)) => Ok(Entries(EntriesInner::Array(a.iter().enumerate()))),
+            Some(other) => Err(Error::TypeError("Table or Array", ::util::name_of_val(other))),
+            None        => Err(Error::NotAvailable(String::from(query))),
+        }
+    }
+
+    /// Read the fields `fields` of every table in the array at `query`
+    ///
+    /// Fields are queries relative to the tables, so they may be nested (`source.git`). The
+    /// returned maps use them as keys.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`, or one of the tables has
+    ///   no value for one of the fields. The error contains the path to the missing value.
+    /// * `Err(Error::TypeError(_, _))` if the value at `query` is not an Array
+    /// * `Err(Error::TypeErrorAt(_, _, _))` with the path of the first element which is not a
+    ///   Table
+    fn project(&'doc self, query: &str, fields: &[&str]) -> Result<Vec<BTreeMap<String, Value>>> {
+        let elements = match self.read(query)? {
+            Some(&Value::Array(ref a)) => a,
+            Some(other) => return Err(Error::TypeError("Array", ::util::name_of_val(other))),
+            None        => return Err(Error::NotAvailable(String::from(query))),
+        };
+
+        let prefix = path_in(self, query, '.')?;
+        elements
+            .iter()
+            .enumerate()
+            .map(|(idx, element)| {
+                let path = prefix.clone().index(idx);
+                if !is_match!(*element, Value::Table(_)) {
+                    return Err(Error::TypeErrorAt(path.to_string(), "Table", ::util::name_of_val(element)));
+                }
+
+                fields
+                    .iter()
+                    .map(|field| match element.read(field)? {
+                        Some(value) => Ok((String::from(*field), value.clone())),
+                        None        => {
+                            let mut missing = path.clone();
+                            for segment in path_in(element, field, '.')?.segments() {
+                                missing.push(segment.clone());
+                            }
+                            Err(Error::NotAvailable(missing.to_string()))
+                        },
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
     #[cfg(feature = "typed")]
     fn read_deserialized<'de, D: Deserialize<'de>>(&'doc self, query: &str) -> Result<Option<D>> {
         let raw = self.read(query)?;
@@ -49,6 +270,95 @@ pub trait TomlValueReadExt<'doc> {
     }
 }
 
+/// The root table of the document `TomlValueReadExt::serialize_at()` writes for `value`
+fn standalone<'doc>(value: Option<&'doc Value>, query: &str) -> Result<Cow<'doc, Value>> {
+    match value {
+        Some(table @ &Value::Table(_)) => Ok(Cow::Borrowed(table)),
+        Some(value) => match TomlPath::from_query(query, '.')?.last() {
+            Some(&Segment::Key(ref key)) => {
+                let mut root = BTreeMap::new();
+                let _ = root.insert(key.clone(), value.clone());
+                Ok(Cow::Owned(Value::Table(root)))
+            },
+            _ => Err(Error::TypeErrorAt(String::from(query), "Table", ::util::name_of_val(value))),
+        },
+        None => Err(Error::NotAvailable(String::from(query))),
+    }
+}
+
+/// An iterator over the entries of a table or array, see `TomlValueReadExt::iter_at()`
+#[derive(Debug, Clone)]
+pub struct Entries<'doc>(EntriesInner<'doc>);
+
+#[derive(Debug, Clone)]
+enum EntriesInner<'doc> {
+    Table(::std::collections::btree_map::Iter<'doc, String, Value>),
+    Array(::std::iter::Enumerate<::std::slice::Iter<'doc, Value>>),
+}
+
+impl<'doc> Iterator for Entries<'doc> {
+    type Item = (Segment, &'doc Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            EntriesInner::Table(ref mut iter) => iter.next().map(|(k, v)| (Segment::Key(k.clone()), v)),
+            EntriesInner::Array(ref mut iter) => iter.next().map(|(i, v)| (Segment::Index(i), v)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.0 {
+            EntriesInner::Table(ref iter) => iter.size_hint(),
+            EntriesInner::Array(ref iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'doc> ExactSizeIterator for Entries<'doc> {}
+
+/// An iterator over the entries of an owned table or array, see `TomlValueDeleteExt::drain()`
+#[derive(Debug)]
+pub struct IntoEntries(IntoEntriesInner);
+
+#[derive(Debug)]
+enum IntoEntriesInner {
+    Table(::std::collections::btree_map::IntoIter<String, Value>),
+    Array(::std::iter::Enumerate<::std::vec::IntoIter<Value>>),
+}
+
+impl IntoEntries {
+
+    /// Iterate over the entries of `value`, or return it if it is neither a Table nor an Array
+    pub(crate) fn new(value: Value) -> ::std::result::Result<IntoEntries, Value> {
+        match value {
+            Value::Table(t) => Ok(IntoEntries(IntoEntriesInner::Table(t.into_iter()))),
+            Value::Array(a) => Ok(IntoEntries(IntoEntriesInner::Array(a.into_iter().enumerate()))),
+            other           => Err(other),
+        }
+    }
+
+}
+
+impl Iterator for IntoEntries {
+    type Item = (Segment, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IntoEntriesInner::Table(ref mut iter) => iter.next().map(|(k, v)| (Segment::Key(k), v)),
+            IntoEntriesInner::Array(ref mut iter) => iter.next().map(|(i, v)| (Segment::Index(i), v)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.0 {
+            IntoEntriesInner::Table(ref iter) => iter.size_hint(),
+            IntoEntriesInner::Array(ref iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for IntoEntries {}
+
 /// Describes a _part_ of a document
 #[cfg(feature = "typed")]
 pub trait Partial<'a> {
@@ -65,28 +375,375 @@ impl<'doc> TomlValueReadExt<'doc> for Value {
     fn read_with_seperator(&'doc self, query: &str, sep: char) -> Result<Option<&'doc Value>> {
         use resolver::non_mut_resolver::resolve;
 
-        tokenize_with_seperator(query, sep).and_then(move |tokens| resolve(self, &tokens, false))
+        instrument!("read", query, {
+            tokenize_with_seperator(query, sep).and_then(move |tokens| resolve(self, &tokens, false))
+        })
     }
 
     fn read_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Option<&'doc mut Value>> {
         use resolver::mut_resolver::resolve;
 
-        tokenize_with_seperator(query, sep).and_then(move |tokens| resolve(self, &tokens, false))
+        instrument!("read_mut", query, {
+            tokenize_with_seperator(query, sep).and_then(move |tokens| resolve(self, &tokens, false))
+        })
+    }
+
+    fn read_path(&'doc self, path: &TomlPath) -> Result<Option<&'doc Value>> {
+        use resolver::non_mut_resolver::resolve;
+
+        let query = path.to_string();
+        instrument!("read", &query, {
+            path.to_tokens().and_then(move |tokens| resolve(self, &tokens, false))
+        })
+    }
+
+    fn read_path_mut(&'doc mut self, path: &TomlPath) -> Result<Option<&'doc mut Value>> {
+        use resolver::mut_resolver::resolve;
+
+        let query = path.to_string();
+        instrument!("read_mut", &query, {
+            path.to_tokens().and_then(move |tokens| resolve(self, &tokens, false))
+        })
+    }
+
+}
+
+/// Reading several values with one query
+///
+/// Separate from `TomlValueReadExt`, so implementors of it do not have to implement queries with
+/// `*`, `{a,b}` and filter segments.
+pub trait TomlValueReadAllExt<'doc> {
+
+    /// Read all values matching `query`, using a custom seperator
+    ///
+    /// Plain `*` segments in `query` match every key of a table, so `dependencies.*.version`
+    /// matches the `version` of every dependency. Segments like `{name,version}` match the listed
+    /// keys of a table. Filter segments like `[?(@.optional == true)]` match the elements of an
+    /// array for which the filter expression holds, see the `filter` module. The values are
+    /// returned together with their paths, in the order of the keys in the table and in the
+    /// segment, or of the elements in the array.
+    ///
+    /// Up to the first of these segments, the query is resolved like with `read_with_seperator()` and
+    /// errors are returned. Below it, values which do not match the rest of the query are
+    /// skipped.
+    fn read_all_with_seperator(&'doc self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc Value)>>;
+
+    /// Read all values matching `query`
+    ///
+    /// See documentation of `TomlValueReadAllExt::read_all_with_seperator`
+    fn read_all(&'doc self, query: &str) -> Result<Vec<(TomlPath, &'doc Value)>> {
+        self.read_all_with_seperator(query, '.')
+    }
+
+    /// Read all values matching `query` mutably, using a custom seperator
+    ///
+    /// See documentation of `TomlValueReadAllExt::read_all_with_seperator`
+    fn read_all_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc mut Value)>>;
+
+    /// Read all values matching `query` mutably
+    ///
+    /// See documentation of `TomlValueReadAllExt::read_all_with_seperator`
+    fn read_all_mut(&'doc mut self, query: &str) -> Result<Vec<(TomlPath, &'doc mut Value)>> {
+        self.read_all_mut_with_seperator(query, '.')
+    }
+
+}
+
+impl<'doc> TomlValueReadAllExt<'doc> for Value {
+
+    fn read_all_with_seperator(&'doc self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc Value)>> {
+        use resolver::multi_resolver::resolve;
+
+        instrument!("read_all", query, {
+            tokenize_with_seperator(query, sep).and_then(|tokens| {
+                let mut results = Vec::new();
+                resolve(self, &tokens, &mut TomlPath::new(), false, &mut results).map(|_| results)
+            })
+        })
+    }
+
+    fn read_all_mut_with_seperator(&'doc mut self, query: &str, sep: char) -> Result<Vec<(TomlPath, &'doc mut Value)>> {
+        use resolver::mut_multi_resolver::resolve;
+
+        instrument!("read_all_mut", query, {
+            tokenize_with_seperator(query, sep).and_then(move |tokens| {
+                let mut results = Vec::new();
+                resolve(self, &tokens, &mut TomlPath::new(), false, &mut results).map(|_| results)
+            })
+        })
     }
 
 }
 
+pub use types::Type;
+
 pub trait TomlValueReadTypeExt<'doc> : TomlValueReadExt<'doc> {
     fn read_string(&'doc self, query: &str) -> Result<Option<String>>;
     fn read_int(&'doc self, query: &str)    -> Result<Option<i64>>;
     fn read_float(&'doc self, query: &str)  -> Result<Option<f64>>;
     fn read_bool(&'doc self, query: &str)   -> Result<Option<bool>>;
+
+    /// Read a String, like `TomlValueReadTypeExt::read_string`, but report a missing value or a
+    /// value of another type as `ReadError`
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(Err(ReadError::Missing { .. }))` if there is no value at `query`
+    /// * `Ok(Err(ReadError::WrongType { .. }))` if the value at `query` is not a String
+    /// * `Err(_)` if the query could not be parsed or resolved
+    fn try_read_string(&'doc self, query: &str) -> Result<::std::result::Result<String, ReadError>>;
+
+    /// Read an Integer, see `TomlValueReadTypeExt::try_read_string`
+    fn try_read_int(&'doc self, query: &str)    -> Result<::std::result::Result<i64, ReadError>>;
+
+    /// Read a Float, see `TomlValueReadTypeExt::try_read_string`
+    fn try_read_float(&'doc self, query: &str)  -> Result<::std::result::Result<f64, ReadError>>;
+
+    /// Read a Boolean, see `TomlValueReadTypeExt::try_read_string`
+    fn try_read_bool(&'doc self, query: &str)   -> Result<::std::result::Result<bool, ReadError>>;
+
+    /// Read a boolean which may also be given as `"yes"`/`"no"`, `"on"`/`"off"`,
+    /// `"true"`/`"false"` (ignoring case) or `1`/`0`
+    ///
+    /// Other values result in `Err(Error::InvalidBoolean(_, _))`, which lists the accepted forms.
+    fn read_bool_lenient(&'doc self, query: &str) -> Result<Option<bool>>;
+
+    /// Read an integer which may also be given as a String
+    ///
+    /// Strings are parsed like TOML integers, independent of the locale: an optional sign,
+    /// followed by decimal digits or by hexadecimal, octal or binary digits with a `0x`, `0o` or
+    /// `0b` prefix. Underscores are allowed between digits. Whitespace is not allowed.
+    ///
+    /// Other values, and strings which do not fit into an `i64`, result in
+    /// `Err(Error::InvalidNumber(_, _))`.
+    fn read_int_from_str(&'doc self, query: &str) -> Result<Option<i64>>;
+
+    /// Read a float which may also be given as a String
+    ///
+    /// Strings are parsed like TOML floats, independent of the locale: an optional sign, decimal
+    /// digits with an optional fraction after a `.` and an optional exponent, or `inf` or `nan`.
+    /// Underscores are allowed between digits. Integers, and strings without fraction or
+    /// exponent, are converted to floats.
+    ///
+    /// Other values result in `Err(Error::InvalidNumber(_, _))`.
+    fn read_float_from_str(&'doc self, query: &str) -> Result<Option<f64>>;
+
+    /// Read an integer, like `TomlValueReadTypeExt::read_int`, but with `options`
+    ///
+    /// With `QueryOptions::with_float_narrowing()`, a Float is accepted as well if it has no
+    /// fraction and fits into an `i64`. Other Floats result in
+    /// `Err(Error::PrecisionLoss(_, _, "Integer"))`.
+    fn read_int_with_options(&'doc self, query: &str, options: &QueryOptions) -> Result<Option<i64>>;
+
+    /// Read a float, like `TomlValueReadTypeExt::read_float`, but with `options`
+    ///
+    /// With `QueryOptions::with_integer_widening()`, an Integer is accepted as well if it is
+    /// exactly representable as an `f64`, which all integers up to 2^53 are. Other Integers
+    /// result in `Err(Error::PrecisionLoss(_, _, "Float"))`.
+    fn read_float_with_options(&'doc self, query: &str, options: &QueryOptions) -> Result<Option<f64>>;
+
+    /// Read a list of strings, given either as an Array of Strings or as a String with
+    /// comma seperated elements
+    ///
+    /// See `TomlValueReadTypeExt::read_string_list_with_delimiter`.
+    fn read_string_list(&'doc self, query: &str) -> Result<Option<Vec<String>>> {
+        self.read_string_list_with_delimiter(query, ',')
+    }
+
+    /// Read a list of strings, given either as an Array of Strings or as a String with
+    /// elements seperated by `delimiter`
+    ///
+    /// Elements of a String are trimmed, and empty elements are skipped, so `"a, b,"` and
+    /// `["a", "b"]` are the same list. Elements of an Array are returned as they are.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::TypeError(_, _))` if the value is neither a String nor an Array
+    /// * `Err(Error::TypeErrorAt(_, _, _))` with the path of the first element of the Array which
+    ///   is not a String
+    fn read_string_list_with_delimiter(&'doc self, query: &str, delimiter: char) -> Result<Option<Vec<String>>>;
+
+    /// Convert every value of the table at `query`
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * `Err(Error::TypeError(_, _))` if the value at `query` is not a Table
+    /// * `Err(Error::TypeErrorAt(_, _, _))` with the path of the first entry which has the wrong
+    ///   type. Other errors of `FromValue::from_value()` are returned as they are.
+    fn read_table_of<T: FromValue>(&'doc self, query: &str) -> Result<BTreeMap<String, T>> {
+        self.read_table_of_with_seperator(query, '.')
+    }
+
+    /// Convert every value of the table at `query`, using a custom seperator
+    ///
+    /// See documentation of `TomlValueReadTypeExt::read_table_of`
+    fn read_table_of_with_seperator<T: FromValue>(&'doc self, query: &str, sep: char) -> Result<BTreeMap<String, T>>;
+
+    /// Convert the value at `query` with `FromValue`
+    ///
+    /// With the implementations for fixed-size arrays and tuples, this checks the length of an
+    /// Array as well: `read_as::<[f64; 3]>("color.rgb")`.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::ArrayLengthMismatch(_, _))` if an Array has the wrong length
+    /// * Other errors of `FromValue::from_value()`, as they are
+    fn read_as<T: FromValue>(&'doc self, query: &str) -> Result<Option<T>>;
+
+    /// Check many boolean flags at once
+    ///
+    /// Returns one entry per query, `false` for flags which do not exist. Queries which share a
+    /// parent, like `features.a` and `features.b`, resolve the parent only once.
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::TypeErrorAt(_, "Boolean", _))` with the query of the first flag which is not
+    ///   a Boolean
+    /// * `Err(_)` with the error of resolving a query, e.g. if a parent is not a Table
+    fn flags_enabled(&'doc self, queries: &[&str]) -> Result<Vec<bool>> {
+        self.flags_enabled_with_seperator(queries, '.')
+    }
+
+    /// Check many boolean flags at once, using a custom seperator
+    fn flags_enabled_with_seperator(&'doc self, queries: &[&str], sep: char) -> Result<Vec<bool>> {
+        let mut parents : HashMap<TomlPath, Option<&'doc Value>> = HashMap::new();
+        let mut flags   = Vec::with_capacity(queries.len());
+
+        for query in queries {
+            let path = path_in(self, query, sep)?;
+            let flag = match (path.parent(), path.last()) {
+                (Some(ref parent), Some(last)) if !parent.is_empty() => {
+                    if !parents.contains_key(parent) {
+                        let value = self.read_path(parent)?;
+                        let _ = parents.insert(parent.clone(), value);
+                    }
+
+                    match (parents[parent], last) {
+                        (None, _)                                             => None,
+                        (Some(&Value::Table(ref t)), &Segment::Key(ref key))  => t.get(key),
+                        (Some(&Value::Array(ref a)), &Segment::Index(idx))    => a.get(idx),
+                        (Some(&Value::Table(_)), &Segment::Index(idx))        => return Err(Error::NoIndexInTable(idx)),
+                        (Some(&Value::Array(_)), &Segment::Key(ref key))      => return Err(Error::NoIdentifierInArray(key.clone())),
+                        (Some(_), &Segment::Key(ref key))                     => return Err(Error::QueryingValueAsTable(key.clone())),
+                        (Some(_), &Segment::Index(idx))                       => return Err(Error::QueryingValueAsArray(idx)),
+                    }
+                },
+                _ => self.read_with_seperator(query, sep)?,
+            };
+
+            flags.push(match flag {
+                None                     => false,
+                Some(&Value::Boolean(b)) => b,
+                Some(other)              => {
+                    let found = ::util::name_of_val(other);
+                    return Err(Error::TypeErrorAt(String::from(*query), "Boolean", found));
+                },
+            });
+        }
+
+        Ok(flags)
+    }
+}
+
+/// Conversion of a `toml::Value` into a Rust type
+///
+/// Implementations return `Error::TypeError` if the value has the wrong type.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self>;
+}
+
+macro_rules! make_from_value {
+    ($rettype:ty, $typename:expr, $matcher:pat => $implementation:expr) => {
+        impl FromValue for $rettype {
+            fn from_value(value: &Value) -> Result<$rettype> {
+                match *value {
+                    $matcher  => Ok($implementation),
+                    ref other => Err(Error::TypeError($typename, ::util::name_of_val(other))),
+                }
+            }
+        }
+    };
+}
+
+make_from_value!(String, "String", Value::String(ref obj) => obj.clone());
+make_from_value!(i64, "Integer", Value::Integer(obj) => obj);
+make_from_value!(f64, "Float", Value::Float(obj) => obj);
+make_from_value!(bool, "Boolean", Value::Boolean(obj) => obj);
+make_from_value!(Datetime, "Datetime", Value::Datetime(ref obj) => obj.clone());
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Value> {
+        Ok(value.clone())
+    }
 }
 
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Vec<T>> {
+        match *value {
+            Value::Array(ref a) => a.iter().map(T::from_value).collect(),
+            ref other           => Err(Error::TypeError("Array", ::util::name_of_val(other))),
+        }
+    }
+}
+
+macro_rules! make_array_from_value {
+    ($len:expr => $($idx:expr),+) => {
+        impl<T: FromValue> FromValue for [T; $len] {
+            fn from_value(value: &Value) -> Result<[T; $len]> {
+                match *value {
+                    Value::Array(ref a) if a.len() == $len => Ok([$(T::from_value(&a[$idx])?),+]),
+                    Value::Array(ref a) => Err(Error::ArrayLengthMismatch($len, a.len())),
+                    ref other           => Err(Error::TypeError("Array", ::util::name_of_val(other))),
+                }
+            }
+        }
+    };
+}
+
+make_array_from_value!(1 => 0);
+make_array_from_value!(2 => 0, 1);
+make_array_from_value!(3 => 0, 1, 2);
+make_array_from_value!(4 => 0, 1, 2, 3);
+make_array_from_value!(5 => 0, 1, 2, 3, 4);
+make_array_from_value!(6 => 0, 1, 2, 3, 4, 5);
+make_array_from_value!(7 => 0, 1, 2, 3, 4, 5, 6);
+make_array_from_value!(8 => 0, 1, 2, 3, 4, 5, 6, 7);
+make_array_from_value!(9 => 0, 1, 2, 3, 4, 5, 6, 7, 8);
+make_array_from_value!(10 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9);
+make_array_from_value!(11 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+make_array_from_value!(12 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11);
+make_array_from_value!(13 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12);
+make_array_from_value!(14 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13);
+make_array_from_value!(15 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14);
+make_array_from_value!(16 => 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+
+macro_rules! make_tuple_from_value {
+    ($len:expr => $($name:ident),+) => {
+        impl<$($name: FromValue),+> FromValue for ($($name,)+) {
+            fn from_value(value: &Value) -> Result<($($name,)+)> {
+                match *value {
+                    Value::Array(ref a) if a.len() == $len => {
+                        let mut elements = a.iter();
+                        Ok(($($name::from_value(elements.next().unwrap())?,)+))
+                    },
+                    Value::Array(ref a) => Err(Error::ArrayLengthMismatch($len, a.len())),
+                    ref other           => Err(Error::TypeError("Array", ::util::name_of_val(other))),
+                }
+            }
+        }
+    };
+}
+
+make_tuple_from_value!(1 => A);
+make_tuple_from_value!(2 => A, B);
+make_tuple_from_value!(3 => A, B, C);
+make_tuple_from_value!(4 => A, B, C, D);
+
 macro_rules! make_type_getter {
     ($fnname:ident, $rettype:ty, $typename:expr, $matcher:pat => $implementation:expr) => {
         fn $fnname(&'doc self, query: &str) -> Result<Option<$rettype>> {
-            self.read_with_seperator(query, '.').and_then(|o| match o {
+            self.read(query).and_then(|o| match o {
                 $matcher => Ok(Some($implementation)),
                 Some(o)  => Err(Error::TypeError($typename, ::util::name_of_val(&o)).into()),
                 None     => Ok(None),
@@ -95,6 +752,22 @@ macro_rules! make_type_getter {
     };
 }
 
+macro_rules! make_try_getter {
+    ($fnname:ident, $rettype:ty, $typename:expr, $matcher:pat => $implementation:expr) => {
+        fn $fnname(&'doc self, query: &str) -> Result<::std::result::Result<$rettype, ReadError>> {
+            self.read(query).map(|o| match o {
+                $matcher => Ok($implementation),
+                Some(o)  => Err(ReadError::WrongType {
+                    path: String::from(query),
+                    expected: $typename,
+                    found: ::util::name_of_val(&o),
+                }),
+                None     => Err(ReadError::Missing { path: String::from(query) }),
+            })
+        }
+    };
+}
+
 impl<'doc, T> TomlValueReadTypeExt<'doc> for T
     where T: TomlValueReadExt<'doc>
 {
@@ -102,6 +775,225 @@ impl<'doc, T> TomlValueReadTypeExt<'doc> for T
     make_type_getter!(read_int, i64, "Integer", Some(&Value::Integer(obj)) => obj);
     make_type_getter!(read_float, f64, "Float", Some(&Value::Float(obj)) => obj);
     make_type_getter!(read_bool, bool, "Boolean", Some(&Value::Boolean(obj)) => obj);
+
+    make_try_getter!(try_read_string, String, "String", Some(&Value::String(ref obj)) => obj.clone());
+    make_try_getter!(try_read_int, i64, "Integer", Some(&Value::Integer(obj)) => obj);
+    make_try_getter!(try_read_float, f64, "Float", Some(&Value::Float(obj)) => obj);
+    make_try_getter!(try_read_bool, bool, "Boolean", Some(&Value::Boolean(obj)) => obj);
+
+    fn read_bool_lenient(&'doc self, query: &str) -> Result<Option<bool>> {
+        let value = match self.read(query)? {
+            Some(value) => value,
+            None        => return Ok(None),
+        };
+
+        let b = match *value {
+            Value::Boolean(b) => Some(b),
+            Value::Integer(1) => Some(true),
+            Value::Integer(0) => Some(false),
+            Value::String(ref s) => match s.to_lowercase().as_str() {
+                "yes" | "on" | "true"  => Some(true),
+                "no" | "off" | "false" => Some(false),
+                _                      => None,
+            },
+            _ => None,
+        };
+
+        b.map(Some).ok_or_else(|| Error::InvalidBoolean(String::from(query), value.to_string()))
+    }
+
+    fn read_int_from_str(&'doc self, query: &str) -> Result<Option<i64>> {
+        let value = match self.read(query)? {
+            Some(value) => value,
+            None        => return Ok(None),
+        };
+
+        let i = match *value {
+            Value::Integer(i)    => Some(i),
+            Value::String(ref s) => parse_int(s),
+            _                    => None,
+        };
+
+        i.map(Some).ok_or_else(|| Error::InvalidNumber(String::from(query), value.to_string()))
+    }
+
+    fn read_float_from_str(&'doc self, query: &str) -> Result<Option<f64>> {
+        let value = match self.read(query)? {
+            Some(value) => value,
+            None        => return Ok(None),
+        };
+
+        let f = match *value {
+            Value::Float(f)      => Some(f),
+            Value::Integer(i)    => Some(i as f64),
+            Value::String(ref s) => parse_float(s),
+            _                    => None,
+        };
+
+        f.map(Some).ok_or_else(|| Error::InvalidNumber(String::from(query), value.to_string()))
+    }
+
+    fn read_int_with_options(&'doc self, query: &str, options: &QueryOptions) -> Result<Option<i64>> {
+        match self.read_with_options(query, options)? {
+            Some(Value::Integer(i)) => Ok(Some(i)),
+            Some(Value::Float(f)) if options.float_narrowing() => {
+                // 2^63 is exactly representable, `i64::MAX as f64` rounds up to it
+                let limit = 9_223_372_036_854_775_808.0;
+                if f.fract() == 0.0 && -limit <= f && f < limit {
+                    Ok(Some(f as i64))
+                } else {
+                    Err(Error::PrecisionLoss(String::from(query), Value::Float(f).to_string(), "Integer"))
+                }
+            },
+            Some(other) => Err(Error::TypeError("Integer", ::util::name_of_val(&other))),
+            None        => Ok(None),
+        }
+    }
+
+    fn read_float_with_options(&'doc self, query: &str, options: &QueryOptions) -> Result<Option<f64>> {
+        match self.read_with_options(query, options)? {
+            Some(Value::Float(f)) => Ok(Some(f)),
+            Some(Value::Integer(i)) if options.integer_widening() => {
+                let f = i as f64;
+                if f < 9_223_372_036_854_775_808.0 && f as i64 == i {
+                    Ok(Some(f))
+                } else {
+                    Err(Error::PrecisionLoss(String::from(query), i.to_string(), "Float"))
+                }
+            },
+            Some(other) => Err(Error::TypeError("Float", ::util::name_of_val(&other))),
+            None        => Ok(None),
+        }
+    }
+
+    fn read_string_list_with_delimiter(&'doc self, query: &str, delimiter: char) -> Result<Option<Vec<String>>> {
+        match self.read(query)? {
+            Some(&Value::String(ref s)) => {
+                let list = s.split(delimiter)
+                    .map(str::trim)
+                    .filter(|e| !e.is_empty())
+                    .map(String::from)
+                    .collect();
+                Ok(Some(list))
+            },
+            Some(&Value::Array(ref a)) => a.iter()
+                .enumerate()
+                .map(|(idx, element)| match *element {
+                    Value::String(ref s) => Ok(s.clone()),
+                    ref other            => {
+                        let path = format!("{}.[{}]", query, idx);
+                        Err(Error::TypeErrorAt(path, "String", ::util::name_of_val(other)))
+                    },
+                })
+                .collect::<Result<Vec<String>>>()
+                .map(Some),
+            Some(other) => Err(Error::TypeError("String or Array", ::util::name_of_val(other))),
+            None        => Ok(None),
+        }
+    }
+
+    fn read_as<V: FromValue>(&'doc self, query: &str) -> Result<Option<V>> {
+        match self.read(query)? {
+            Some(value) => V::from_value(value).map(Some),
+            None        => Ok(None),
+        }
+    }
+
+    fn read_table_of_with_seperator<V: FromValue>(&'doc self, query: &str, sep: char) -> Result<BTreeMap<String, V>> {
+        match self.read_with_seperator(query, sep)? {
+            Some(&Value::Table(ref t)) => t.iter()
+                .map(|(key, value)| {
+                    V::from_value(value)
+                        .map(|v| (key.clone(), v))
+                        .or_else(|e| match e {
+                            Error::TypeError(expected, found) => {
+                                let path = path_in(self, query, sep)?.key(key.as_str());
+                                Err(Error::TypeErrorAt(path.to_string(), expected, found))
+                            },
+                            other => Err(other),
+                        })
+                })
+                .collect(),
+            Some(other) => Err(Error::TypeError("Table", ::util::name_of_val(other))),
+            None        => Err(Error::NotAvailable(String::from(query))),
+        }
+    }
+}
+
+/// Parse `query` into a path, counting indices like `[-1]` from the end of the arrays in `doc`
+fn path_in<'doc, D>(doc: &'doc D, query: &str, sep: char) -> Result<TomlPath>
+    where D: TomlValueReadExt<'doc> + ?Sized
+{
+    TomlPath::from_query_counting(query, sep, |path| match doc.read_path(path) {
+        Ok(Some(value)) => value.as_array().map(Vec::len),
+        _               => None,
+    })
+}
+
+/// Remove the underscores from `digits`, which are only allowed between two digits of `radix`
+fn strip_underscores(digits: &str, radix: u32) -> Option<String> {
+    let chars = digits.chars().collect::<Vec<_>>();
+    let mut stripped = String::with_capacity(chars.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c != '_' {
+            stripped.push(c);
+            continue;
+        }
+
+        let is_digit = |idx: Option<usize>| idx.and_then(|idx| chars.get(idx)).map_or(false, |c| c.is_digit(radix));
+        if !is_digit(i.checked_sub(1)) || !is_digit(Some(i + 1)) {
+            return None;
+        }
+    }
+    Some(stripped)
+}
+
+/// Split an optional sign off `s`, returning whether the number is negative
+fn split_sign(s: &str) -> (bool, &str) {
+    if s.starts_with('-') {
+        (true, &s[1..])
+    } else if s.starts_with('+') {
+        (false, &s[1..])
+    } else {
+        (false, s)
+    }
+}
+
+fn parse_int(s: &str) -> Option<i64> {
+    let (negative, s) = split_sign(s);
+    let (radix, digits) = match s.get(..2) {
+        Some("0x") => (16, &s[2..]),
+        Some("0o") => (8, &s[2..]),
+        Some("0b") => (2, &s[2..]),
+        _          => (10, s),
+    };
+
+    let digits = strip_underscores(digits, radix)?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+
+    let sign = if negative { "-" } else { "" };
+    i64::from_str_radix(&format!("{}{}", sign, digits), radix).ok()
+}
+
+fn parse_float(s: &str) -> Option<f64> {
+    let (negative, unsigned) = split_sign(s);
+    let f = match unsigned {
+        "inf" => ::std::f64::INFINITY,
+        "nan" => ::std::f64::NAN,
+        _     => {
+            let digits = strip_underscores(unsigned, 10)?;
+            let valid  = digits.starts_with(|c: char| c.is_ascii_digit())
+                && !digits.contains(".e") && !digits.contains(".E") && !digits.ends_with('.')
+                && digits.chars().all(|c| c.is_ascii_digit() || ".eE+-".contains(c));
+            if !valid {
+                return None;
+            }
+            digits.parse::<f64>().ok()?
+        },
+    };
+    Some(if negative { -f } else { f })
 }
 
 #[cfg(test)]
@@ -186,6 +1078,17 @@ mod test {
         assert!(is_match!(err, Error::NoIndexInTable(_)));
     }
 
+    #[test]
+    fn test_read_array_index_out_of_bounds() {
+        let mut toml : Value = toml_from_str(r#"
+        array = [ 1 ]
+        "#).unwrap();
+
+        assert!(toml.read("array.[1]").unwrap().is_none());
+        assert!(toml.read("array.[1].a").unwrap().is_none());
+        assert!(toml.read_mut("array.[5]").unwrap().is_none());
+    }
+
     ///
     ///
     /// Querying without specifying the seperator
@@ -268,6 +1171,139 @@ mod test {
         assert!(is_match!(err, Error::NoIndexInTable(_)));
     }
 
+    #[test]
+    fn test_find_in_array() {
+        let toml : Value = toml_from_str(r#"
+        name = "pkg"
+
+        [[bin]]
+        name = "client"
+        path = "src/client.rs"
+
+        [[bin]]
+        name = "server"
+        path = "src/server.rs"
+        "#).unwrap();
+
+        let server = toml.find_in_array("bin", "name", &Value::from("server")).unwrap().unwrap();
+        assert_eq!(server["path"], Value::from("src/server.rs"));
+
+        assert_eq!(toml.find_in_array("bin", "name", &Value::from("other")).unwrap(), None);
+        assert_eq!(toml.find_in_array("bin", "missing", &Value::from("server")).unwrap(), None);
+        assert_eq!(toml.find_in_array("lib", "name", &Value::from("server")).unwrap(), None);
+        assert!(is_match!(toml.find_in_array("name", "name", &Value::from("pkg")), Err(Error::TypeError("Array", "String"))));
+    }
+
+    #[test]
+    fn test_read_all() {
+        let toml : Value = toml_from_str(r#"
+        [dependencies]
+        log = "0.4"
+        serde = { version = "1.0", features = [ "derive" ] }
+        toml = { version = "0.4" }
+        local = { path = "../local" }
+
+        [[bin]]
+        name = "a"
+        "#).unwrap();
+
+        let versions = toml.read_all("dependencies.*.version").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].0.to_string(), "dependencies.serde.version");
+        assert_eq!(versions[0].1, &Value::String(String::from("1.0")));
+        assert_eq!(versions[1].0.to_string(), "dependencies.toml.version");
+
+        let features = toml.read_all("dependencies.*.features.[-1]").unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].0.to_string(), "dependencies.serde.features.[0]");
+
+        assert_eq!(toml.read_all("*").unwrap().len(), 2);
+        assert_eq!(toml.read_all("dependencies.log").unwrap().len(), 1);
+        assert!(toml.read_all("missing.*").unwrap().is_empty());
+
+        assert!(is_match!(toml.read_all("bin.*"), Err(Error::NoIdentifierInArray(_))));
+        assert!(is_match!(toml.read("dependencies.*"), Err(Error::UnexpectedMultiMatch)));
+    }
+
+    #[test]
+    fn test_read_all_union() {
+        let mut toml : Value = toml_from_str(r#"
+        [package]
+        name = "toml-query"
+        version = "0.8.0"
+        authors = [ "a" ]
+
+        [dependencies]
+        log = { version = "0.4" }
+        toml = { version = "0.4" }
+        "#).unwrap();
+
+        let values = toml.read_all("package.{version, name, missing}").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].0.to_string(), "package.version");
+        assert_eq!(values[1].1, &Value::from("toml-query"));
+
+        assert!(is_match!(toml.read_all("package.authors.{a,b}"), Err(Error::NoIdentifierInArray(_))));
+        assert!(is_match!(toml.read_mut("package.{name}"), Err(Error::UnexpectedMultiMatch)));
+
+        for (_, value) in toml.read_all_mut("dependencies.{toml,log}.version").unwrap() {
+            *value = Value::from("1.0");
+        }
+        for (_, value) in toml.read_all_mut("package.*").unwrap() {
+            *value = Value::from("x");
+        }
+        assert_eq!(toml.read("dependencies.log.version").unwrap(), Some(&Value::from("1.0")));
+        assert_eq!(toml.read("dependencies.toml.version").unwrap(), Some(&Value::from("1.0")));
+        assert_eq!(toml.read("package.authors").unwrap(), Some(&Value::from("x")));
+    }
+
+    #[test]
+    fn test_read_all_filter() {
+        let mut toml : Value = toml_from_str(r#"
+        [[dependencies]]
+        name = "serde"
+        optional = true
+
+        [[dependencies]]
+        name = "log"
+
+        [[dependencies]]
+        name = "toml.rs"
+        optional = true
+        "#).unwrap();
+
+        let names = toml.read_all("dependencies.[?(@.optional == true)].name").unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0].0.to_string(), "dependencies.[0].name");
+        assert_eq!(names[1].0.to_string(), "dependencies.[2].name");
+
+        let names = toml.read_all(r#"dependencies.[?(@.name == "toml.rs" || !@.optional)]"#).unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0].0.to_string(), "dependencies.[1]");
+
+        assert!(is_match!(toml.read_all("dependencies.[?(@.name ==)]"), Err(Error::FilterParsingError(_, _))));
+        assert!(is_match!(toml.read_all("dependencies.[0].[?(@)]"), Err(Error::TypeError("Array", "Table"))));
+        assert!(is_match!(toml.read("dependencies.[?(@)]"), Err(Error::UnexpectedMultiMatch)));
+
+        for (_, value) in toml.read_all_mut("dependencies.[?(!@.optional)].name").unwrap() {
+            *value = Value::from("env_logger");
+        }
+        assert_eq!(toml.read("dependencies.[1].name").unwrap(), Some(&Value::from("env_logger")));
+    }
+
+    #[test]
+    fn test_read_index_from_end() {
+        let toml : Value = toml_from_str(r#"
+        values = [ [ 1, 2 ], [ 3, 4 ] ]
+        "#).unwrap();
+
+        assert_eq!(toml.read("values.[-1].[-2]").unwrap(), Some(&Value::Integer(3)));
+        assert_eq!(toml.read("values.[-2].[1]").unwrap(), Some(&Value::Integer(2)));
+
+        let err = toml.read("values.[-3]").unwrap_err();
+        assert!(is_match!(err, Error::IndexFromEndOutOfBounds(3, 2)));
+    }
+
 }
 
 #[cfg(test)]
@@ -287,6 +1323,452 @@ mod high_level_fn_test {
         assert_eq!(val.unwrap(), 1);
     }
 
+    #[test]
+    fn test_read_typed() {
+        let toml : Value = toml_from_str(r#"
+        [table]
+        a = 1
+        b = [ "x" ]
+        "#).unwrap();
+
+        assert_eq!(toml.read_typed("table.a").unwrap(), Some((Type::Integer, &Value::Integer(1))));
+        assert_eq!(toml.read_typed("table.b").unwrap().map(|(t, _)| t), Some(Type::Array));
+        assert_eq!(toml.read_typed("table").unwrap().map(|(t, _)| t), Some(Type::Table));
+        assert!(toml.read_typed("table.c").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_try_read() {
+        let toml : Value = toml_from_str(r#"
+        [table]
+        a = 1
+        b = "text"
+        "#).unwrap();
+
+        assert_eq!(toml.try_read_int("table.a").unwrap(), Ok(1));
+        assert_eq!(toml.try_read_string("table.b").unwrap(), Ok(String::from("text")));
+
+        assert_eq!(toml.try_read_bool("table.c").unwrap(), Err(ReadError::Missing {
+            path: String::from("table.c"),
+        }));
+
+        let err = toml.try_read_float("table.b").unwrap().unwrap_err();
+        assert_eq!(err, ReadError::WrongType {
+            path: String::from("table.b"),
+            expected: "Float",
+            found: "String",
+        });
+        assert_eq!(err.to_string(), "Type Error at 'table.b'. Requested Float, but got String");
+
+        assert!(toml.try_read_int("table.a.b").is_err());
+    }
+
+    #[test]
+    fn test_read_numbers_from_str() {
+        let toml : Value = toml_from_str(r#"
+        int = 3
+        dec = "-1_000"
+        hex = "0xdead_BEEF"
+        oct = "+0o755"
+        bin = "0b1010"
+        float = "6.626e-34"
+        inf = "-inf"
+        whole = "42"
+        native = 1.5
+        "#).unwrap();
+
+        assert_eq!(toml.read_int_from_str("int").unwrap(), Some(3));
+        assert_eq!(toml.read_int_from_str("dec").unwrap(), Some(-1000));
+        assert_eq!(toml.read_int_from_str("hex").unwrap(), Some(0xdead_beef));
+        assert_eq!(toml.read_int_from_str("oct").unwrap(), Some(0o755));
+        assert_eq!(toml.read_int_from_str("bin").unwrap(), Some(10));
+        assert_eq!(toml.read_int_from_str("missing").unwrap(), None);
+        assert!(is_match!(toml.read_int_from_str("float"), Err(Error::InvalidNumber(_, _))));
+        assert!(is_match!(toml.read_int_from_str("native"), Err(Error::InvalidNumber(_, _))));
+
+        assert_eq!(toml.read_float_from_str("float").unwrap(), Some(6.626e-34));
+        assert_eq!(toml.read_float_from_str("inf").unwrap(), Some(f64::NEG_INFINITY));
+        assert_eq!(toml.read_float_from_str("whole").unwrap(), Some(42.0));
+        assert_eq!(toml.read_float_from_str("int").unwrap(), Some(3.0));
+        assert_eq!(toml.read_float_from_str("native").unwrap(), Some(1.5));
+        assert!(is_match!(toml.read_float_from_str("hex"), Err(Error::InvalidNumber(_, _))));
+
+        let err = toml.read_int_from_str("float").unwrap_err();
+        assert_eq!(err.to_string(), "Value at 'float' is \"6.626e-34\", which is not a number");
+    }
+
+    #[test]
+    fn test_numeric_widening() {
+        let toml : Value = toml_from_str(r#"
+        int = 3
+        big = 9007199254740993
+        max = 9223372036854775807
+        whole = 3.0
+        half = 2.5
+        huge = 1e19
+        "#).unwrap();
+
+        let strict = QueryOptions::new();
+        assert!(is_match!(toml.read_float_with_options("int", &strict), Err(Error::TypeError("Float", "Integer"))));
+        assert!(is_match!(toml.read_int_with_options("whole", &strict), Err(Error::TypeError("Integer", "Float"))));
+        assert_eq!(toml.read_int_with_options("int", &strict).unwrap(), Some(3));
+        assert_eq!(toml.read_float_with_options("missing", &strict).unwrap(), None);
+
+        let lenient = QueryOptions::new().with_integer_widening(true).with_float_narrowing(true);
+        assert_eq!(toml.read_float_with_options("int", &lenient).unwrap(), Some(3.0));
+        assert_eq!(toml.read_int_with_options("whole", &lenient).unwrap(), Some(3));
+        assert!(is_match!(toml.read_float_with_options("big", &lenient), Err(Error::PrecisionLoss(_, _, "Float"))));
+        assert!(is_match!(toml.read_float_with_options("max", &lenient), Err(Error::PrecisionLoss(_, _, "Float"))));
+        assert!(is_match!(toml.read_int_with_options("half", &lenient), Err(Error::PrecisionLoss(_, _, "Integer"))));
+        assert!(is_match!(toml.read_int_with_options("huge", &lenient), Err(Error::PrecisionLoss(_, _, "Integer"))));
+
+        let err = toml.read_int_with_options("half", &lenient).unwrap_err();
+        assert_eq!(err.to_string(), "Value at 'half' is 2.5, which is not exactly representable as Integer");
+    }
+
+    #[test]
+    fn test_flags_enabled() {
+        let toml : Value = toml_from_str(r#"
+        top = true
+        level = 3
+
+        [features]
+        a = true
+        b = false
+        list = [ true ]
+        "#).unwrap();
+
+        let flags = toml.flags_enabled(&["features.a", "features.b", "features.c", "top", "nope",
+                                         "features.list.[0]", "features.list.[1]", "other.x"]);
+        assert_eq!(flags.unwrap(), vec![true, false, false, true, false, true, false, false]);
+
+        let res = toml.flags_enabled(&["features.a", "level"]);
+        assert!(is_match!(res, Err(Error::TypeErrorAt(ref q, "Boolean", "Integer")) if q == "level"));
+        assert!(is_match!(toml.flags_enabled(&["level.x"]), Err(Error::QueryingValueAsTable(_))));
+        assert!(is_match!(toml.flags_enabled(&["features.[0]"]), Err(Error::NoIndexInTable(0))));
+        assert!(toml.flags_enabled(&[]).unwrap().is_empty());
+
+        let flags = toml.flags_enabled_with_seperator(&["features/a", "features/list/[0]", "top"], '/');
+        assert_eq!(flags.unwrap(), vec![true, true, true]);
+
+        let flags = toml.flags_enabled(&["features.list.[-1]", "features.list.[last]"]);
+        assert_eq!(flags.unwrap(), vec![true, true]);
+        let res = toml.flags_enabled(&["features.list.[-2]"]);
+        assert!(is_match!(res, Err(Error::IndexFromEndOutOfBounds(2, 1))));
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse_int("1_2_3"), Some(123));
+        assert_eq!(parse_int("-0x10"), Some(-16));
+        assert_eq!(parse_int("-9223372036854775808"), Some(::std::i64::MIN));
+        assert_eq!(parse_int("9223372036854775808"), None);
+        for invalid in &["", "_1", "1_", "1__2", " 1", "1,000", "0x", "0x_1", "0b102", "+-1", "1e3"] {
+            assert_eq!(parse_int(invalid), None, "{}", invalid);
+        }
+
+        assert_eq!(parse_float("1_000.5"), Some(1000.5));
+        assert_eq!(parse_float("+1E2"), Some(100.0));
+        assert!(parse_float("nan").unwrap().is_nan());
+        for invalid in &["", ".5", "1.", "1._5", "1,5", "1.e5", "0x10", "infinity", "1 "] {
+            assert_eq!(parse_float(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_read_with_options() {
+        use function::{Functions, QueryOptions};
+
+        let toml : Value = toml_from_str(r#"
+        [package]
+        name = "Toml-Query"
+        "#).unwrap();
+
+        let options = QueryOptions::new().with_functions(Functions::builtins());
+
+        let name = toml.read_with_options("package.name.lower()", &options).unwrap();
+        assert_eq!(name, Some(Value::String(String::from("toml-query"))));
+
+        let name = toml.read_with_options("package.name", &options).unwrap();
+        assert_eq!(name, Some(Value::String(String::from("Toml-Query"))));
+
+        let len = toml.read_with_options("package.len()", &options).unwrap();
+        assert_eq!(len, Some(Value::Integer(1)));
+        assert!(is_match!(toml.read_with_options("len()", &options), Err(Error::EmptyQueryError)));
+
+        assert!(toml.read_with_options("package.missing.lower()", &options).unwrap().is_none());
+
+        let res = toml.read_with_options("package.name.lower()", &QueryOptions::new());
+        assert!(is_match!(res, Err(Error::UnknownFunction(_))));
+
+        let options = QueryOptions::new().with_functions(Functions::builtins()).with_seperator('/');
+        let name = toml.read_with_options("package/name/lower()", &options).unwrap();
+        assert_eq!(name, Some(Value::String(String::from("toml-query"))));
+        let name = toml.read_with_options("package/name", &options).unwrap();
+        assert_eq!(name, Some(Value::String(String::from("Toml-Query"))));
+    }
+
+    #[test]
+    fn test_read_with_basic_syntax() {
+        use function::{Functions, QueryOptions};
+
+        let toml : Value = toml_from_str(r#"
+        [package]
+        name = "toml-query"
+        authors = [ "a", "b" ]
+        "#).unwrap();
+
+        let options = QueryOptions::new().with_functions(Functions::builtins()).with_syntax(Syntax::V1Basic);
+
+        let name = toml.read_with_options("package.name", &options).unwrap();
+        assert_eq!(name, Some(Value::String(String::from("toml-query"))));
+        let author = toml.read_with_options("package.authors.[1]", &options).unwrap();
+        assert_eq!(author, Some(Value::String(String::from("b"))));
+
+        let res = toml.read_with_options("package.name.lower()", &options);
+        assert!(is_match!(res, Err(Error::ExtendedSyntax(ref s)) if s == "lower()"));
+        let res = toml.read_with_options("package.authors.[-1]", &options);
+        assert!(is_match!(res, Err(Error::ExtendedSyntax(ref s)) if s == "[-1]"));
+        let res = toml.read_int_with_options("package.*", &options);
+        assert!(is_match!(res, Err(Error::ExtendedSyntax(_))));
+    }
+
+    #[test]
+    fn test_keys_at() {
+        let toml : Value = toml_from_str(r#"
+        [settings]
+        a = 1
+
+        [settings.b]
+        c = [ 1 ]
+        "#).unwrap();
+
+        let keys = |depth| -> Vec<String> {
+            toml.keys_at("settings", depth).unwrap().into_iter().map(|p| p.to_string()).collect()
+        };
+
+        assert_eq!(keys(1), vec!["settings.a", "settings.b"]);
+        assert_eq!(keys(2), vec!["settings.a", "settings.b", "settings.b.c"]);
+        assert_eq!(keys(3), vec!["settings.a", "settings.b", "settings.b.c", "settings.b.c.[0]"]);
+
+        assert!(toml.keys_at("settings.a", 1).unwrap().is_empty());
+        assert!(is_match!(toml.keys_at("missing", 1), Err(Error::NotAvailable(_))));
+
+        let toml : Value = toml_from_str(r#"
+        [[servers]]
+        name = "a"
+
+        [[servers]]
+        name = "b"
+        "#).unwrap();
+
+        let keys = |query| -> Vec<String> {
+            toml.keys_at(query, 1).unwrap().into_iter().map(|p| p.to_string()).collect()
+        };
+
+        assert_eq!(keys("servers.[-1]"), vec!["servers.[1].name"]);
+        assert_eq!(keys("servers.[last]"), vec!["servers.[1].name"]);
+        assert_eq!(keys("servers.[-2]"), vec!["servers.[0].name"]);
+    }
+
+    #[test]
+    fn test_project() {
+        let toml : Value = toml_from_str(r#"
+        [[dep]]
+        name = "a"
+        version = "1"
+        source = { git = "https://example.com/a" }
+
+        [[dep]]
+        name = "b"
+        version = "2"
+        "#).unwrap();
+
+        let deps = toml.project("dep", &["name", "version"]).unwrap();
+        assert_eq!(deps.len(), 2);
+        assert_eq!(deps[1]["name"], Value::String(String::from("b")));
+        assert_eq!(deps[1]["version"], Value::String(String::from("2")));
+        assert_eq!(deps[0].len(), 2);
+
+        let res = toml.project("dep", &["name", "source.git"]);
+        assert!(is_match!(res, Err(Error::NotAvailable(ref p)) if p == "dep.[1].source.git"));
+        let res = toml.project("dep", &["\"home.page\""]);
+        assert!(is_match!(res, Err(Error::NotAvailable(ref p)) if p == "dep.[0].\"home.page\""));
+        assert!(is_match!(toml.project("dep.[0].name", &[]), Err(Error::TypeError("Array", "String"))));
+    }
+
+    #[test]
+    fn test_memory_footprint() {
+        let toml : Value = toml_from_str(r#"
+        [small]
+        a = 1
+
+        [big]
+        a = "some long string, which takes some memory"
+        b = [ 1, 2, 3, 4 ]
+        "#).unwrap();
+
+        let small = toml.memory_footprint("small").unwrap();
+        let big   = toml.memory_footprint("big").unwrap();
+        assert!(small < big);
+        assert_eq!(toml.memory_footprint("big.a").unwrap(), ::size::memory_footprint(&toml["big"]["a"]));
+        assert!(is_match!(toml.memory_footprint("nope"), Err(Error::NotAvailable(_))));
+    }
+
+    #[test]
+    fn test_serialize_at() {
+        let toml : Value = toml_from_str(r#"
+        [package]
+        name = "toml-query"
+        keywords = [ "toml", "query" ]
+
+        [package.metadata]
+        a = 1
+
+        [[bin]]
+        name = "first"
+        "#).unwrap();
+
+        let package : Value = toml_from_str(&toml.serialize_at("package").unwrap()).unwrap();
+        assert_eq!(package, toml["package"]);
+        assert_eq!(toml.serialize_at("package.metadata").unwrap(), "a = 1\n");
+        assert_eq!(toml.serialize_at("package.name").unwrap(), "name = \"toml-query\"\n");
+        assert_eq!(toml.serialize_at("bin.[0]").unwrap(), "name = \"first\"\n");
+
+        let bins : Value = toml_from_str(&toml.serialize_at("bin").unwrap()).unwrap();
+        assert_eq!(bins.get("bin"), toml.get("bin"));
+
+        let res = toml.serialize_at("package.keywords.[0]");
+        assert!(is_match!(res, Err(Error::TypeErrorAt(_, "Table", "String"))));
+        assert!(is_match!(toml.serialize_at("nope"), Err(Error::NotAvailable(_))));
+
+        let style = FormatStyle::new().key_order(&["name"]);
+        assert!(toml.serialize_at_with("package", &style).unwrap().starts_with("name = "));
+    }
+
+    #[test]
+    fn test_iter_at() {
+        let toml : Value = toml_from_str(r#"
+        list = [ "a", "b" ]
+
+        [table]
+        z = 1
+        a = 2
+        "#).unwrap();
+
+        let entries = toml.iter_at("table").unwrap().collect::<Vec<_>>();
+        assert_eq!(entries, vec![
+            (Segment::Key(String::from("a")), &Value::Integer(2)),
+            (Segment::Key(String::from("z")), &Value::Integer(1)),
+        ]);
+
+        let entries = toml.iter_at("list").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries.map(|(s, _)| s).collect::<Vec<_>>(), vec![Segment::Index(0), Segment::Index(1)]);
+
+        assert!(is_match!(toml.iter_at("table.a"), Err(Error::TypeError(_, "Integer"))));
+        assert!(is_match!(toml.iter_at("nope"), Err(Error::NotAvailable(_))));
+    }
+
+    #[test]
+    fn test_read_bool_lenient() {
+        let toml : Value = toml_from_str(r#"
+        a = true
+        b = "Yes"
+        c = "off"
+        d = 0
+        e = 2
+        f = "maybe"
+        "#).unwrap();
+
+        assert_eq!(toml.read_bool_lenient("a").unwrap(), Some(true));
+        assert_eq!(toml.read_bool_lenient("b").unwrap(), Some(true));
+        assert_eq!(toml.read_bool_lenient("c").unwrap(), Some(false));
+        assert_eq!(toml.read_bool_lenient("d").unwrap(), Some(false));
+        assert_eq!(toml.read_bool_lenient("missing").unwrap(), None);
+        assert!(is_match!(toml.read_bool_lenient("e"), Err(Error::InvalidBoolean(_, _))));
+
+        let err = toml.read_bool_lenient("f").unwrap_err();
+        assert_eq!(err.to_string(),
+                   "Value at 'f' is \"maybe\", expected true/false, \"yes\"/\"no\", \"on\"/\"off\" or 0/1");
+    }
+
+    #[test]
+    fn test_read_string_list() {
+        let toml : Value = toml_from_str(r#"
+        array = [ "a", " b" ]
+        string = "a, b,,c "
+        colons = "a:b"
+        mixed = [ [ "a" ] ]
+        number = 1
+        "#).unwrap();
+
+        let strings = |v: &[&str]| v.iter().map(|s| String::from(*s)).collect::<Vec<_>>();
+        assert_eq!(toml.read_string_list("array").unwrap(), Some(strings(&["a", " b"])));
+        assert_eq!(toml.read_string_list("string").unwrap(), Some(strings(&["a", "b", "c"])));
+        assert_eq!(toml.read_string_list_with_delimiter("colons", ':').unwrap(), Some(strings(&["a", "b"])));
+        assert_eq!(toml.read_string_list("missing").unwrap(), None);
+
+        let res = toml.read_string_list("mixed");
+        assert!(is_match!(res, Err(Error::TypeErrorAt(ref p, "String", "Array")) if p == "mixed.[0]"));
+        assert!(is_match!(toml.read_string_list("number"), Err(Error::TypeError(_, "Integer"))));
+    }
+
+    #[test]
+    fn test_read_table_of() {
+        let toml : Value = toml_from_str(r#"
+        [users]
+        alice = [ 1, 2 ]
+        bob = [ 3 ]
+
+        [ports]
+        http = 80
+        https = "443"
+
+        [hosts.local]
+        "example.com" = "80"
+        "#).unwrap();
+
+        let users = toml.read_table_of::<Vec<i64>>("users").unwrap();
+        assert_eq!(users["alice"], vec![1, 2]);
+        assert_eq!(users["bob"], vec![3]);
+
+        let res = toml.read_table_of::<i64>("ports");
+        assert!(is_match!(res, Err(Error::TypeErrorAt(ref p, "Integer", "String")) if p == "ports.https"));
+        assert!(is_match!(toml.read_table_of::<i64>("ports.http"), Err(Error::TypeError("Table", "Integer"))));
+        assert!(is_match!(toml.read_table_of::<i64>("missing"), Err(Error::NotAvailable(_))));
+
+        let res = toml.read_table_of::<i64>("hosts.local");
+        assert!(is_match!(res, Err(Error::TypeErrorAt(ref p, _, _)) if p == "hosts.local.\"example.com\""));
+        let res = toml.read_table_of_with_seperator::<i64>("hosts/local", '/');
+        assert!(is_match!(res, Err(Error::TypeErrorAt(ref p, _, _)) if p == "hosts.local.\"example.com\""));
+    }
+
+    #[test]
+    fn test_read_as() {
+        let toml : Value = toml_from_str(r#"
+        [color]
+        rgb = [ 0.5, 0.25, 1.0 ]
+        name = "purple"
+
+        [window]
+        size = [ 800, 600 ]
+        "#).unwrap();
+
+        assert_eq!(toml.read_as::<[f64; 3]>("color.rgb").unwrap(), Some([0.5, 0.25, 1.0]));
+        assert_eq!(toml.read_as::<(i64, i64)>("window.size").unwrap(), Some((800, 600)));
+        assert_eq!(toml.read_as::<String>("color.name").unwrap(), Some(String::from("purple")));
+        assert_eq!(toml.read_as::<[f64; 3]>("color.missing").unwrap(), None);
+
+        assert!(is_match!(toml.read_as::<[f64; 4]>("color.rgb"), Err(Error::ArrayLengthMismatch(4, 3))));
+        assert!(is_match!(toml.read_as::<(i64, i64, i64)>("window.size"), Err(Error::ArrayLengthMismatch(3, 2))));
+        assert!(is_match!(toml.read_as::<[i64; 3]>("color.rgb"), Err(Error::TypeError("Integer", "Float"))));
+        assert!(is_match!(toml.read_as::<[f64; 3]>("color.name"), Err(Error::TypeError("Array", "String"))));
+
+        let mixed = Value::Array(vec![Value::String(String::from("x")), Value::Integer(1)]);
+        assert_eq!(<(String, i64)>::from_value(&mixed).unwrap(), (String::from("x"), 1));
+    }
+
     #[cfg(feature = "typed")]
     #[test]
     fn test_name() {
@@ -0,0 +1,270 @@
+/// References to values in other documents
+///
+/// A string value of the form `"@file:<name>:<query>"` refers to the value at `<query>` in the
+/// document `<name>`, and `"@file:<name>"` to the whole document. `dereference()` replaces all
+/// references in a document with the values they refer to. The documents are provided by a
+/// `Loader`, e.g. a `FileLoader` reading files from a directory, which is not available on
+/// wasm32.
+///
+/// References may refer to values which contain references themselves, up to
+/// `MAX_REFERENCE_DEPTH` levels deep. At most `MAX_REFERENCES` references are replaced in total,
+/// so documents which refer to each other many times do not blow up.
+
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fmt::Display;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Component, Path, PathBuf};
+
+use toml::Value;
+
+use read::TomlValueReadExt;
+use error::{Error, Result};
+
+const PREFIX: &str = "@file:";
+
+/// How many references can be nested within the values references refer to
+pub const MAX_REFERENCE_DEPTH: usize = 64;
+
+/// How many references `dereference()` replaces at most
+pub const MAX_REFERENCES: usize = 10_000;
+
+/// Provides the documents references refer to
+pub trait Loader {
+
+    /// Load the document `name`
+    fn load(&self, name: &str) -> Result<Value>;
+
+}
+
+impl<F> Loader for F
+    where F: Fn(&str) -> Result<Value>
+{
+    fn load(&self, name: &str) -> Result<Value> {
+        self(name)
+    }
+}
+
+/// A `Loader` which reads and parses TOML files, with names relative to a base directory
+///
+/// Names which are absolute paths or contain `..` are rejected, so only files below the base
+/// directory can be loaded.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileLoader {
+    base: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileLoader {
+
+    pub fn new<P: Into<PathBuf>>(base: P) -> FileLoader {
+        FileLoader { base: base.into() }
+    }
+
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Loader for FileLoader {
+
+    fn load(&self, name: &str) -> Result<Value> {
+        let failed = |e: &dyn Display| Error::ReferenceLoadFailed(String::from(name), e.to_string());
+
+        let inside_base = Path::new(name)
+            .components()
+            .all(|c| is_match!(c, Component::Normal(_) | Component::CurDir));
+        if !inside_base {
+            return Err(failed(&"the path is not below the base directory"));
+        }
+
+        let mut text = String::new();
+        File::open(self.base.join(name))
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| failed(&e))?;
+
+        ::toml::from_str(&text).map_err(|e| failed(&e))
+    }
+
+}
+
+/// Replace all references in `value` with the values they refer to
+///
+/// References in the values which are inserted are replaced as well. Every document is loaded
+/// only once.
+///
+/// # Return value
+///
+/// * `Err(Error::NotAvailable(_))` with the reference, if the document has no value at the query
+/// * `Err(Error::ReferenceCycle(_))` if a value contains a reference to itself
+/// * `Err(Error::ReferenceLimitExceeded(_, _))` if the references are nested deeper than
+///   `MAX_REFERENCE_DEPTH`, or more than `MAX_REFERENCES` references would be replaced
+/// * `Err(_)` with the errors of the loader
+pub fn dereference<L: Loader>(value: &Value, loader: &L) -> Result<Value> {
+    let mut resolver = Resolver { loader, documents: HashMap::new(), active: Vec::new(), replaced: 0 };
+    let mut value    = value.clone();
+    resolver.resolve(&mut value)?;
+    Ok(value)
+}
+
+struct Resolver<'a, L: Loader + 'a> {
+    loader: &'a L,
+    documents: HashMap<String, Value>,
+
+    /// The references which are being resolved, to detect cycles
+    active: Vec<String>,
+
+    /// The number of references replaced so far
+    replaced: usize,
+}
+
+impl<'a, L: Loader> Resolver<'a, L> {
+
+    fn resolve(&mut self, value: &mut Value) -> Result<()> {
+        let reference = match *value {
+            Value::String(ref s) if s.starts_with(PREFIX) => s.clone(),
+            Value::Table(ref mut t) => return t.values_mut().try_for_each(|v| self.resolve(v)),
+            Value::Array(ref mut a) => return a.iter_mut().try_for_each(|v| self.resolve(v)),
+            _ => return Ok(()),
+        };
+
+        if self.active.contains(&reference) {
+            return Err(Error::ReferenceCycle(reference));
+        }
+        if self.active.len() == MAX_REFERENCE_DEPTH {
+            return Err(Error::ReferenceLimitExceeded(reference, MAX_REFERENCE_DEPTH));
+        }
+        if self.replaced == MAX_REFERENCES {
+            return Err(Error::ReferenceLimitExceeded(reference, MAX_REFERENCES));
+        }
+        self.replaced += 1;
+
+        let mut target = self.lookup(&reference)?;
+        self.active.push(reference);
+        self.resolve(&mut target)?;
+        let _ = self.active.pop();
+
+        *value = target;
+        Ok(())
+    }
+
+    fn lookup(&mut self, reference: &str) -> Result<Value> {
+        let target = &reference[PREFIX.len()..];
+        let (name, query) = match target.rfind(':') {
+            Some(idx) => (&target[..idx], Some(&target[idx + 1..])),
+            None      => (target, None),
+        };
+
+        if !self.documents.contains_key(name) {
+            let document = self.loader.load(name)?;
+            let _ = self.documents.insert(String::from(name), document);
+        }
+
+        let document = &self.documents[name];
+        match query {
+            None        => Ok(document.clone()),
+            Some(query) => document.read(query)?
+                .cloned()
+                .ok_or_else(|| Error::NotAvailable(String::from(reference))),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn loader(name: &str) -> Result<Value> {
+        let text = match name {
+            "shared.toml" => r#"
+                port = 8080
+                hosts = [ "a", "@file:hosts.toml:primary" ]
+            "#,
+            "hosts.toml" => r#"primary = "b""#,
+            "cycle.toml" => r#"a = "@file:cycle.toml:b"
+                               b = "@file:cycle.toml:a""#,
+            _ => return Err(Error::ReferenceLoadFailed(String::from(name), String::from("not found"))),
+        };
+        Ok(toml_from_str(text).unwrap())
+    }
+
+    #[test]
+    fn test_dereference() {
+        let doc : Value = toml_from_str(r#"
+        name = "app"
+
+        [server]
+        port = "@file:shared.toml:port"
+        hosts = "@file:shared.toml:hosts"
+        all = "@file:hosts.toml"
+        "#).unwrap();
+
+        let expected : Value = toml_from_str(r#"
+        name = "app"
+
+        [server]
+        port = 8080
+        hosts = [ "a", "b" ]
+        all = { primary = "b" }
+        "#).unwrap();
+
+        assert_eq!(dereference(&doc, &loader).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let doc = Value::String(String::from("@file:shared.toml:nope"));
+        assert!(is_match!(dereference(&doc, &loader), Err(Error::NotAvailable(_))));
+
+        let doc = Value::String(String::from("@file:missing.toml:a"));
+        assert!(is_match!(dereference(&doc, &loader), Err(Error::ReferenceLoadFailed(_, _))));
+
+        let doc = Value::String(String::from("@file:cycle.toml:a"));
+        assert!(is_match!(dereference(&doc, &loader), Err(Error::ReferenceCycle(_))));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_file_loader() {
+        let doc = Value::String(String::from("@file:cycle.toml:a"));
+        let res = dereference(&doc, &FileLoader::new("/nonexistent"));
+        assert!(is_match!(res, Err(Error::ReferenceLoadFailed(ref name, _)) if name == "cycle.toml"));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_file_loader_stays_below_base() {
+        let loader = FileLoader::new(".");
+        for name in &["/etc/passwd", "../Cargo.toml", "src/../../Cargo.toml"] {
+            let res = loader.load(name);
+            assert!(is_match!(res, Err(Error::ReferenceLoadFailed(_, ref e)) if e.contains("base directory")), "{}", name);
+        }
+        assert!(loader.load("./Cargo.toml").is_ok());
+    }
+
+    #[test]
+    fn test_limits() {
+        // every level refers to the next one twice, so resolving level 0 replaces 2^20 references
+        let wide = |name: &str| -> Result<Value> {
+            let level : usize = name.parse().unwrap();
+            let next = Value::String(format!("@file:{}", level + 1));
+            Ok(Value::Array(if level < 20 { vec![next.clone(), next] } else { vec![] }))
+        };
+        let doc = Value::String(String::from("@file:0"));
+        let res = dereference(&doc, &wide);
+        assert!(is_match!(res, Err(Error::ReferenceLimitExceeded(_, MAX_REFERENCES))));
+
+        let deep = |name: &str| -> Result<Value> {
+            let level : usize = name.parse().unwrap();
+            Ok(Value::String(format!("@file:{}", level + 1)))
+        };
+        let res = dereference(&doc, &deep);
+        assert!(is_match!(res, Err(Error::ReferenceLimitExceeded(_, MAX_REFERENCE_DEPTH))));
+    }
+
+}
@@ -0,0 +1,348 @@
+/// Renaming keys in bulk
+///
+/// `TomlValueRenameExt::rename_matching()` renames all keys below a table which match a
+/// `KeyPattern`, e.g. for migrating `plugin-*` keys to a new naming scheme. `transform_keys()`
+/// converts all keys of a document to a `KeyCase`.
+
+use std::mem;
+
+use regex::{self, Regex};
+use toml::Value;
+
+use read::TomlValueReadExt;
+use path::{Segment, TomlPath};
+use normalize::KeyNormalizer;
+use error::{Error, Result};
+
+/// A pattern for single keys
+#[derive(Debug, Clone)]
+pub struct KeyPattern(Regex);
+
+impl KeyPattern {
+
+    /// A pattern in which `*` matches any number of characters, e.g. `plugin-*`
+    pub fn glob(glob: &str) -> KeyPattern {
+        let re = glob.split('*').map(regex::escape).collect::<Vec<_>>().join(".*");
+        KeyPattern(Regex::new(&format!("^{}$", re)).expect("Escaped glob is a valid regex"))
+    }
+
+    /// A pattern which matches keys the regex `re` finds a match in
+    ///
+    /// # Panics
+    ///
+    /// If `re` is not a valid regex.
+    pub fn regex(re: &str) -> KeyPattern {
+        KeyPattern(Regex::new(re).expect("Invalid regex for KeyPattern"))
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        self.0.is_match(key)
+    }
+
+}
+
+/// A key which was renamed by `TomlValueRenameExt::rename_matching()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: TomlPath,
+    pub to: TomlPath,
+}
+
+pub trait TomlValueRenameExt {
+
+    /// Rename all keys matching `pattern` in the table at `query` and in all tables below it
+    ///
+    /// The new key is what `rename` returns for the old key. Keys for which it returns the old
+    /// key are left as they are. Renames are done table by table, so the paths of keys below a
+    /// renamed key already contain its new name.
+    ///
+    /// If a new key would replace another value, nothing is renamed at all.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(renames)` in key order, a table before the tables below it
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    /// * `Err(Error::TypeError(_, _))` if the value at `query` is not a Table
+    /// * `Err(Error::RenameConflict(_))` with the path of the first key which would be
+    ///   overwritten
+    fn rename_matching<F>(&mut self, query: &str, pattern: &KeyPattern, rename: F) -> Result<Vec<Rename>>
+        where F: FnMut(&str) -> String
+    {
+        self.rename_matching_with_seperator(query, '.', pattern, rename)
+    }
+
+    fn rename_matching_with_seperator<F>(&mut self, query: &str, sep: char, pattern: &KeyPattern, rename: F)
+        -> Result<Vec<Rename>>
+        where F: FnMut(&str) -> String;
+
+}
+
+impl TomlValueRenameExt for Value {
+
+    fn rename_matching_with_seperator<F>(&mut self, query: &str, sep: char, pattern: &KeyPattern, mut rename: F)
+        -> Result<Vec<Rename>>
+        where F: FnMut(&str) -> String
+    {
+        let mut path = TomlPath::from_query(query, sep)?;
+        let target   = self.read_mut_with_seperator(query, sep)?
+            .ok_or_else(|| Error::NotAvailable(String::from(query)))?;
+
+        if !target.is_table() {
+            return Err(Error::TypeError("Table", ::util::name_of_val(target)));
+        }
+
+        rename_in(target, &mut path, pattern, &mut rename)
+    }
+
+}
+
+/// A naming convention for keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    /// `max-size`
+    KebabCase,
+
+    /// `max_size`
+    SnakeCase,
+
+    /// `maxSize`
+    CamelCase,
+}
+
+impl KeyCase {
+
+    /// Convert `key` to this case
+    ///
+    /// Words are seperated by `-`, `_` and by changes from lower to upper case, so `max-size`,
+    /// `max_size`, `maxSize` and `MaxSize` are the same words. A run of upper case letters is one
+    /// word, as in `HTTPServer`.
+    pub fn convert(&self, key: &str) -> String {
+        let words = words(key);
+        match *self {
+            KeyCase::KebabCase => words.join("-"),
+            KeyCase::SnakeCase => words.join("_"),
+            KeyCase::CamelCase => words.iter()
+                .enumerate()
+                .map(|(i, word)| if i == 0 { word.clone() } else { capitalize(word) })
+                .collect(),
+        }
+    }
+
+}
+
+/// Keys are equivalent if they are the same words, see `KeyCase::convert()`
+impl KeyNormalizer for KeyCase {
+    fn normalize(&self, key: &str) -> String {
+        self.convert(key)
+    }
+}
+
+/// The lower case words of `key`
+fn words(key: &str) -> Vec<String> {
+    let chars = key.chars().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut word  = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '-' || c == '_' {
+            words.push(::std::mem::replace(&mut word, String::new()));
+            continue;
+        }
+
+        let prev = if i > 0 { chars.get(i - 1).cloned() } else { None };
+        let next = chars.get(i + 1).cloned();
+        let boundary = c.is_uppercase() && match prev {
+            Some(p) if p.is_lowercase() || p.is_numeric() => true,
+            Some(p) if p.is_uppercase()                   => next.map(char::is_lowercase).unwrap_or(false),
+            _                                             => false,
+        };
+        if boundary {
+            words.push(::std::mem::replace(&mut word, String::new()));
+        }
+        word.extend(c.to_lowercase());
+    }
+    words.push(word);
+
+    words.into_iter().filter(|w| !w.is_empty()).collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None        => String::new(),
+    }
+}
+
+/// Convert all keys in `value`, and in all tables below it, to `case`
+///
+/// Works like `TomlValueRenameExt::rename_matching()` with a pattern which matches every key, so
+/// if two keys of a table are converted to the same key, nothing is converted at all and
+/// `Err(Error::RenameConflict(_))` is returned.
+pub fn transform_keys(value: &mut Value, case: KeyCase) -> Result<Vec<Rename>> {
+    rename_in(value, &mut TomlPath::new(), &KeyPattern::glob("*"), &mut |key: &str| case.convert(key))
+}
+
+/// Convert all keys in the table at `query`, and in all tables below it, to `case`
+///
+/// See `transform_keys()`. Fails like `TomlValueRenameExt::rename_matching()`.
+pub fn transform_keys_at(value: &mut Value, query: &str, case: KeyCase) -> Result<Vec<Rename>> {
+    value.rename_matching(query, &KeyPattern::glob("*"), |key| case.convert(key))
+}
+
+/// Rename keys in `value`, which is at `path`, and below it
+///
+/// The renames are done on a copy, so a conflict leaves `value` untouched.
+fn rename_in<F>(value: &mut Value, path: &mut TomlPath, pattern: &KeyPattern, rename: &mut F) -> Result<Vec<Rename>>
+    where F: FnMut(&str) -> String
+{
+    let mut renamed = value.clone();
+    let mut renames = Vec::new();
+    rename_below(&mut renamed, path, pattern, rename, &mut renames)?;
+    *value = renamed;
+    Ok(renames)
+}
+
+fn rename_below<F>(value: &mut Value, path: &mut TomlPath, pattern: &KeyPattern, rename: &mut F, renames: &mut Vec<Rename>)
+    -> Result<()>
+    where F: FnMut(&str) -> String
+{
+    match *value {
+        Value::Table(ref mut table) => {
+            for (key, v) in mem::replace(table, Default::default()) {
+                let new_key = if pattern.matches(&key) { rename(&key) } else { key.clone() };
+
+                if table.contains_key(&new_key) {
+                    return Err(Error::RenameConflict(path.join(Segment::Key(new_key)).to_string()));
+                }
+                if new_key != key {
+                    renames.push(Rename {
+                        from: path.join(Segment::Key(key)),
+                        to: path.join(Segment::Key(new_key.clone())),
+                    });
+                }
+                table.insert(new_key, v);
+            }
+
+            for (key, v) in table.iter_mut() {
+                path.push(Segment::Key(key.clone()));
+                rename_below(v, path, pattern, rename, renames)?;
+                path.pop();
+            }
+        },
+        Value::Array(ref mut array) => {
+            for (idx, v) in array.iter_mut().enumerate() {
+                path.push(Segment::Index(idx));
+                rename_below(v, path, pattern, rename, renames)?;
+                path.pop();
+            }
+        },
+        _ => {},
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_rename_matching() {
+        let mut toml : Value = toml_from_str(r#"
+        [plugins]
+        plugin-a = 1
+        plugin-b = { plugin-c = true }
+        other = 2
+
+        [[plugins.list]]
+        plugin-d = "x"
+        "#).unwrap();
+
+        let renames = toml.rename_matching("plugins", &KeyPattern::glob("plugin-*"), |k| {
+            format!("ext_{}", &k[7..])
+        }).unwrap();
+
+        let path = |q: &str| TomlPath::from_query(q, '.').unwrap();
+        assert_eq!(renames, vec![
+            Rename { from: path("plugins.plugin-a"), to: path("plugins.ext_a") },
+            Rename { from: path("plugins.plugin-b"), to: path("plugins.ext_b") },
+            Rename { from: path("plugins.ext_b.plugin-c"), to: path("plugins.ext_b.ext_c") },
+            Rename { from: path("plugins.list.[0].plugin-d"), to: path("plugins.list.[0].ext_d") },
+        ]);
+
+        assert_eq!(toml.read("plugins.ext_a").unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(toml.read("plugins.ext_b.ext_c").unwrap(), Some(&Value::Boolean(true)));
+        assert_eq!(toml.read("plugins.list.[0].ext_d").unwrap(), Some(&Value::String(String::from("x"))));
+        assert_eq!(toml.read("plugins.other").unwrap(), Some(&Value::Integer(2)));
+        assert!(toml.read("plugins.plugin-a").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rename_matching_conflict() {
+        let mut toml : Value = toml_from_str(r#"
+        [table]
+        a1 = 1
+        a2 = 2
+        b = 3
+        "#).unwrap();
+        let original = toml.clone();
+
+        let res = toml.rename_matching("table", &KeyPattern::regex("^a[0-9]$"), |_| String::from("a"));
+        assert!(is_match!(res, Err(Error::RenameConflict(ref p)) if p == "table.a"));
+        assert_eq!(toml, original);
+
+        // Swapping keys is not a conflict
+        let renames = toml.rename_matching("table", &KeyPattern::regex("^(a1|b)$"), |k| {
+            String::from(if k == "b" { "a1" } else { "b" })
+        }).unwrap();
+        assert_eq!(renames.len(), 2);
+        assert_eq!(toml.read("table.a1").unwrap(), Some(&Value::Integer(3)));
+        assert_eq!(toml.read("table.b").unwrap(), Some(&Value::Integer(1)));
+
+        let res = toml.rename_matching("table.b", &KeyPattern::glob("*"), |k| k.to_uppercase());
+        assert!(is_match!(res, Err(Error::TypeError("Table", "Integer"))));
+    }
+
+    #[test]
+    fn test_key_case() {
+        assert_eq!(KeyCase::SnakeCase.convert("maxSize"), "max_size");
+        assert_eq!(KeyCase::SnakeCase.convert("HTTPServer"), "http_server");
+        assert_eq!(KeyCase::KebabCase.convert("opt_level2"), "opt-level2");
+        assert_eq!(KeyCase::KebabCase.convert("Content--Type"), "content-type");
+        assert_eq!(KeyCase::CamelCase.convert("max-size"), "maxSize");
+        assert_eq!(KeyCase::CamelCase.convert("MAX_SIZE"), "maxSize");
+        assert_eq!(KeyCase::CamelCase.convert("utf8Name"), "utf8Name");
+    }
+
+    #[test]
+    fn test_transform_keys() {
+        let mut toml : Value = toml_from_str(r#"
+        topLevel = 1
+
+        [build-options]
+        maxSize = 2
+
+        [[build-options.targetList]]
+        target-name = "x"
+        "#).unwrap();
+
+        let renames = transform_keys(&mut toml, KeyCase::SnakeCase).unwrap();
+        assert_eq!(renames.len(), 5);
+        assert_eq!(toml.read("top_level").unwrap(), Some(&Value::Integer(1)));
+        assert_eq!(toml.read("build_options.max_size").unwrap(), Some(&Value::Integer(2)));
+        assert!(toml.read("build_options.target_list.[0].target_name").unwrap().is_some());
+
+        let renames = transform_keys_at(&mut toml, "build_options.target_list", KeyCase::CamelCase);
+        assert!(is_match!(renames, Err(Error::TypeError("Table", "Array"))));
+
+        let renames = transform_keys_at(&mut toml, "build_options", KeyCase::KebabCase).unwrap();
+        assert_eq!(renames[0].to.to_string(), "build_options.max-size");
+        assert!(toml.read("build_options.target-list.[0].target-name").unwrap().is_some());
+
+        let mut toml : Value = toml_from_str("a-b = 1\na_b = 2").unwrap();
+        let res = transform_keys(&mut toml, KeyCase::SnakeCase);
+        assert!(is_match!(res, Err(Error::RenameConflict(ref p)) if p == "a_b"));
+    }
+
+}
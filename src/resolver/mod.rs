@@ -1,3 +1,19 @@
 pub mod mut_resolver;
 pub mod mut_creating_resolver;
 pub mod non_mut_resolver;
+pub mod multi_resolver;
+pub mod mut_multi_resolver;
+
+use error::{Error, Result};
+
+/// Turn an index into a position in an array of length `len`
+///
+/// Indices counted from the end must point into the array, other indices are passed through
+/// unchecked.
+pub fn array_index(idx: usize, from_end: bool, len: usize) -> Result<usize> {
+    if from_end {
+        len.checked_sub(idx).ok_or(Error::IndexFromEndOutOfBounds(idx, len))
+    } else {
+        Ok(idx)
+    }
+}
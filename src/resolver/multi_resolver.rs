@@ -0,0 +1,108 @@
+/// The query resolver for queries which can match many values
+
+use toml::Value;
+use tokenizer::Token;
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+use resolver::array_index;
+
+/// Resolves the path in the passed document recursively, collecting all matching values
+///
+/// A `Wildcard` token matches every value of a table, a `Union` token the values of the listed
+/// keys, in the order of the list, and a `Filter` token the elements of an array for which the
+/// filter expression holds. Up to the first of these tokens, the query is resolved like
+/// with the non-mutable resolver and errors are returned. Below it, values which do not match the
+/// rest of the query are skipped.
+pub fn resolve<'doc>(toml: &'doc Value,
+                     tokens: &[Token],
+                     path: &mut TomlPath,
+                     below_multi: bool,
+                     results: &mut Vec<(TomlPath, &'doc Value)>)
+    -> Result<()>
+{
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None        => {
+            results.push((path.clone(), toml));
+            return Ok(());
+        },
+    };
+
+    match *token {
+        Token::Wildcard { .. } | Token::Union { .. } => match *toml {
+            Value::Table(ref t) => {
+                let matches : Vec<(&String, &'doc Value)> = match *token {
+                    Token::Union { ref keys, .. } => keys.iter()
+                        .filter_map(|key| t.get(key).map(|v| (key, v)))
+                        .collect(),
+                    _ => t.iter().collect(),
+                };
+
+                for (key, sub_document) in matches {
+                    path.push(Segment::Key(key.clone()));
+                    resolve(sub_document, rest, path, true, results)?;
+                    let _ = path.pop();
+                }
+                Ok(())
+            },
+            _ if below_multi => Ok(()),
+            Value::Array(_)  => Err(Error::NoIdentifierInArray(multi_segment(token))),
+            _                => Err(Error::QueryingValueAsTable(multi_segment(token))),
+        },
+
+        Token::Filter { ref expr, .. } => {
+            let expr = expr.expr();
+            match *toml {
+                Value::Array(ref a) => {
+                    for (idx, element) in a.iter().enumerate().filter(|&(_, e)| expr.evaluate(e)) {
+                        path.push(Segment::Index(idx));
+                        resolve(element, rest, path, true, results)?;
+                        let _ = path.pop();
+                    }
+                    Ok(())
+                },
+                _ if below_multi => Ok(()),
+                ref other        => Err(Error::TypeError("Array", ::util::name_of_val(other))),
+            }
+        },
+
+        _ => match step(toml, token) {
+            Ok(Some((segment, sub_document))) => {
+                path.push(segment);
+                resolve(sub_document, rest, path, below_multi, results)?;
+                let _ = path.pop();
+                Ok(())
+            },
+            Ok(None)              => Ok(()),
+            Err(_) if below_multi => Ok(()),
+            Err(e)                => Err(e),
+        },
+    }
+}
+
+/// Resolve a single `Identifier` or `Index` token in `toml`
+fn step<'doc>(toml: &'doc Value, token: &Token) -> Result<Option<(Segment, &'doc Value)>> {
+    match (toml, token) {
+        (&Value::Table(ref t), &Token::Identifier { ref ident, .. }) => {
+            Ok(t.get(&ident[..]).map(|v| (Segment::Key(ident.to_string()), v)))
+        },
+        (&Value::Array(ref a), &Token::Index { idx, from_end, .. }) => {
+            array_index(idx, from_end, a.len())
+                .map(|idx| a.get(idx).map(|v| (Segment::Index(idx), v)))
+        },
+        (&Value::Table(_), &Token::Index { idx, .. })            => Err(Error::NoIndexInTable(idx)),
+        (&Value::Array(_), &Token::Identifier { ref ident, .. }) => Err(Error::NoIdentifierInArray(ident.to_string())),
+        (_, &Token::Identifier { ref ident, .. })                => Err(Error::QueryingValueAsTable(ident.to_string())),
+        (_, &Token::Index { idx, .. })                           => Err(Error::QueryingValueAsArray(idx)),
+        (_, &Token::Wildcard { .. }) | (_, &Token::Union { .. }) | (_, &Token::Filter { .. }) => Err(Error::UnexpectedMultiMatch),
+    }
+}
+
+/// The segment of the query for `token`, a `Wildcard`, `Union` or `Filter` token
+pub fn multi_segment(token: &Token) -> String {
+    match *token {
+        Token::Union { ref keys, .. }  => format!("{{{}}}", keys.join(",")),
+        Token::Filter { ref expr, .. } => format!("[?({})]", expr),
+        _                              => String::from("*"),
+    }
+}
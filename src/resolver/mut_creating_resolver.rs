@@ -5,75 +5,64 @@ use std::collections::BTreeMap;
 use toml::Value;
 use tokenizer::Token;
 use error::{Error, Result};
+use resolver::array_index;
 
-pub fn resolve<'doc>(toml: &'doc mut Value, tokens: &Token) -> Result<&'doc mut Value> {
+pub fn resolve<'doc>(toml: &'doc mut Value, tokens: &[Token]) -> Result<&'doc mut Value> {
+    let mut current = toml;
+    for token in tokens {
+        current = step(current, token)?;
+    }
+    Ok(current)
+}
+
+/// Resolve a single token in `toml`, creating a table if a key is missing
+fn step<'doc>(toml: &'doc mut Value, token: &Token) -> Result<&'doc mut Value> {
 
     // Cases:
     //
     //  1. Identifier, toml: table, ident present       -> traverse
-    //  2. Identifier, toml: table, no indent present   -> create Table
-    //      2.1 If next token                           -> traverse
-    //      2.2 no next token                           -> return created Table
+    //  2. Identifier, toml: table, no indent present   -> create Table, traverse
     //  3. Identifier, toml: array                      -> error
     //  4. Index, toml: table                           -> error
     //  5. Index, toml: array, idx present              -> traverse
-    //  6. Index, toml: array, idx not present
-    //      6.1 -> next token is ident                  -> push Table
-    //      6.2 -> next token is index                  -> push Array
-    //      then traverse
+    //  6. Index, toml: array, idx not present        -> error (not implemented yet)
+    //  7. Any token, toml: neither table nor array     -> error
 
-    match *tokens {
+    match *token {
         Token::Identifier { ref ident, .. } => {
+            trace!(target: "toml_query::resolver", key = &ident[..]; "Resolving key");
             match toml {
                 &mut Value::Table(ref mut t) => {
-                    if t.contains_key(ident) {
-                        match tokens.next() {
-                            Some(next) => resolve(t.get_mut(ident).unwrap(), next),
-                            None => t.get_mut(ident).ok_or_else(|| unreachable!()),
-                        }
+                    if t.contains_key(&ident[..]) {
+                        Ok(t.get_mut(&ident[..]).unwrap())
                     } else {
-                        match tokens.next() {
-                            Some(next) => {
-                                let subdoc = t.entry(ident.clone()).or_insert(Value::Table(BTreeMap::new()));
-                                resolve(subdoc, next)
-                            },
-                            None => Ok(t.entry(ident.clone()).or_insert(Value::Table(BTreeMap::new()))),
-                        }
+                        debug!(target: "toml_query::resolver", key = &ident[..]; "Creating table");
+                        #[cfg(feature = "metrics")]
+                        ::metrics::record_created_table();
+                        Ok(t.entry(ident.to_string()).or_insert(Value::Table(BTreeMap::new())))
                     }
                 },
-                &mut Value::Array(_) => Err(Error::NoIdentifierInArray(ident.clone())),
-                _ => unimplemented!()
+                &mut Value::Array(_) => Err(Error::NoIdentifierInArray(ident.to_string())),
+                _ => Err(Error::QueryingValueAsTable(ident.to_string())),
             }
         }
-        Token::Index { idx , .. } => {
+        Token::Index { idx, from_end, .. } => {
+            trace!(target: "toml_query::resolver", index = idx; "Resolving index");
             match toml {
                 &mut Value::Table(_) => Err(Error::NoIndexInTable(idx)),
                 &mut Value::Array(ref mut ary) => {
+                    let idx = array_index(idx, from_end, ary.len())?;
                     if ary.len() > idx {
-                        match tokens.next() {
-                            Some(next) => resolve(ary.get_mut(idx).unwrap(), next),
-                            None => ary.get_mut(idx).ok_or_else(|| unreachable!()),
-                        }
+                        Ok(ary.get_mut(idx).unwrap())
                     } else {
-                        if let Some(next) = tokens.next() {
-                            match **next {
-                                Token::Identifier { .. } => {
-                                    ary.push(Value::Table(BTreeMap::new()));
-                                },
-                                Token::Index { .. } => {
-                                    ary.push(Value::Array(vec![]));
-                                }
-                            }
-                            //resolve(toml, next)
-                            panic!("Cannot do this")
-                        } else {
-                            unimplemented!()
-                        }
+                        // Creating intermediate array elements is not supported (yet)
+                        Err(Error::ArrayIndexOutOfBounds(idx, ary.len()))
                     }
                 }
-                _ => unimplemented!()
+                _ => Err(Error::QueryingValueAsArray(idx)),
             }
         }
+        Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
     }
 }
 
@@ -387,7 +376,7 @@ mod test {
         assert!(result.is_ok());
         let result = result.unwrap();
 
-        let tokens = tokenize_with_seperator(&String::from("color"), '.').unwrap();
+        let tokens = tokenize_with_seperator("color", '.').unwrap();
         let result = resolve(result, &tokens);
 
         assert!(result.is_ok());
@@ -494,5 +483,18 @@ mod test {
         //}
     }
 
+    #[test]
+    fn test_resolve_deep_query() {
+        use resolver::non_mut_resolver;
+
+        let mut toml = Value::Table(Default::default());
+        let query    = vec!["a"; 1000].join(".");
+        let tokens   = tokenize_with_max_depth(&query, '.', 1000).unwrap();
+
+        *resolve(&mut toml, &tokens).unwrap() = Value::Integer(1);
+        let found = non_mut_resolver::resolve(&toml, &tokens, true).unwrap();
+        assert_eq!(found, Some(&Value::Integer(1)));
+    }
+
 }
 
@@ -0,0 +1,102 @@
+/// The mutable query resolver for queries which can match many values
+
+use std::collections::HashMap;
+
+use toml::Value;
+use tokenizer::Token;
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+use resolver::array_index;
+use resolver::multi_resolver::multi_segment;
+
+/// Resolves the path in the passed document recursively, collecting all matching values mutably
+///
+/// Matches the same values as `multi_resolver::resolve()`. The values do not overlap, as
+/// `Wildcard` and `Union` tokens match different keys of a table.
+pub fn resolve<'doc>(toml: &'doc mut Value,
+                     tokens: &[Token],
+                     path: &mut TomlPath,
+                     below_multi: bool,
+                     results: &mut Vec<(TomlPath, &'doc mut Value)>)
+    -> Result<()>
+{
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None        => {
+            results.push((path.clone(), toml));
+            return Ok(());
+        },
+    };
+
+    match *token {
+        Token::Wildcard { .. } | Token::Union { .. } => match *toml {
+            Value::Table(ref mut t) => {
+                let matches : Vec<(&String, &'doc mut Value)> = match *token {
+                    Token::Union { ref keys, .. } => {
+                        let mut entries : HashMap<&String, &'doc mut Value> = t.iter_mut().collect();
+                        keys.iter()
+                            .filter_map(|key| entries.remove(key).map(|v| (key, v)))
+                            .collect()
+                    },
+                    _ => t.iter_mut().collect(),
+                };
+
+                for (key, sub_document) in matches {
+                    path.push(Segment::Key(key.clone()));
+                    resolve(sub_document, rest, path, true, results)?;
+                    let _ = path.pop();
+                }
+                Ok(())
+            },
+            _ if below_multi => Ok(()),
+            Value::Array(_)  => Err(Error::NoIdentifierInArray(multi_segment(token))),
+            _                => Err(Error::QueryingValueAsTable(multi_segment(token))),
+        },
+
+        Token::Filter { ref expr, .. } => {
+            let expr = expr.expr();
+            match *toml {
+                Value::Array(ref mut a) => {
+                    for (idx, element) in a.iter_mut().enumerate().filter(|&(_, ref e)| expr.evaluate(e)) {
+                        path.push(Segment::Index(idx));
+                        resolve(element, rest, path, true, results)?;
+                        let _ = path.pop();
+                    }
+                    Ok(())
+                },
+                _ if below_multi => Ok(()),
+                ref other        => Err(Error::TypeError("Array", ::util::name_of_val(other))),
+            }
+        },
+
+        _ => match step(toml, token) {
+            Ok(Some((segment, sub_document))) => {
+                path.push(segment);
+                resolve(sub_document, rest, path, below_multi, results)?;
+                let _ = path.pop();
+                Ok(())
+            },
+            Ok(None)              => Ok(()),
+            Err(_) if below_multi => Ok(()),
+            Err(e)                => Err(e),
+        },
+    }
+}
+
+/// Resolve a single `Identifier` or `Index` token in `toml`
+fn step<'doc>(toml: &'doc mut Value, token: &Token) -> Result<Option<(Segment, &'doc mut Value)>> {
+    match (toml, token) {
+        (&mut Value::Table(ref mut t), &Token::Identifier { ref ident, .. }) => {
+            Ok(t.get_mut(&ident[..]).map(|v| (Segment::Key(ident.to_string()), v)))
+        },
+        (&mut Value::Array(ref mut a), &Token::Index { idx, from_end, .. }) => {
+            let idx = array_index(idx, from_end, a.len())?;
+            Ok(a.get_mut(idx).map(|v| (Segment::Index(idx), v)))
+        },
+        (&mut Value::Table(_), &Token::Index { idx, .. })            => Err(Error::NoIndexInTable(idx)),
+        (&mut Value::Array(_), &Token::Identifier { ref ident, .. }) => Err(Error::NoIdentifierInArray(ident.to_string())),
+        (_, &Token::Identifier { ref ident, .. })                    => Err(Error::QueryingValueAsTable(ident.to_string())),
+        (_, &Token::Index { idx, .. })                               => Err(Error::QueryingValueAsArray(idx)),
+        (_, &Token::Wildcard { .. }) | (_, &Token::Union { .. }) | (_, &Token::Filter { .. })     => Err(Error::UnexpectedMultiMatch),
+    }
+}
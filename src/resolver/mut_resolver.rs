@@ -1,56 +1,77 @@
 /// The query resolver that operates on the AST and the TOML object
 
-use std::ops::IndexMut;
-
 use toml::Value;
 use tokenizer::Token;
 use error::{Error, Result};
+use resolver::array_index;
 
 /// Resolves the path in the passed document recursively
 ///
+/// An empty slice of tokens resolves to the document itself.
+///
 /// # Guarantees
 ///
 /// If error_if_not_found is set to true, this function does not return Ok(None) in any case.
 ///
-pub fn resolve<'doc>(toml: &'doc mut Value, tokens: &Token, error_if_not_found: bool) -> Result<Option<&'doc mut Value>> {
+pub fn resolve<'doc>(toml: &'doc mut Value, tokens: &[Token], error_if_not_found: bool) -> Result<Option<&'doc mut Value>> {
+    let mut current = toml;
+    for token in tokens {
+        current = match step(current, token, error_if_not_found)? {
+            Some(sub_document) => sub_document,
+            None               => return Ok(None),
+        };
+    }
+    Ok(Some(current))
+}
+
+/// Resolve a single token in `toml`
+fn step<'doc>(toml: &'doc mut Value, token: &Token, error_if_not_found: bool) -> Result<Option<&'doc mut Value>> {
     match toml {
         &mut Value::Table(ref mut t) => {
-            match tokens {
+            match token {
                 &Token::Identifier { ref ident, .. } => {
-                    match t.get_mut(ident) {
+                    trace!(target: "toml_query::resolver", key = &ident[..]; "Resolving key");
+                    match t.get_mut(&ident[..]) {
                         None => if error_if_not_found {
-                            return Err(Error::IdentifierNotFoundInDocument(ident.to_owned()))
+                            return Err(Error::IdentifierNotFoundInDocument(ident.to_string()))
                         } else {
                             Ok(None)
                         },
-                        Some(sub_document) => match tokens.next() {
-                            Some(next) => resolve(sub_document, next, error_if_not_found),
-                            None       => Ok(Some(sub_document)),
-                        },
+                        Some(sub_document) => Ok(Some(sub_document)),
                     }
                 },
 
                 &Token::Index { idx, .. } => Err(Error::NoIndexInTable(idx)),
+                &Token::Wildcard { .. } | &Token::Union { .. } | &Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
             }
         },
 
         &mut Value::Array(ref mut ary) => {
-            match tokens {
-                &Token::Index { idx, .. } => {
-                    match tokens.next() {
-                        Some(next) => resolve(ary.get_mut(idx).unwrap(), next, error_if_not_found),
-                        None       => Ok(Some(ary.index_mut(idx))),
+            match token {
+                &Token::Index { idx, from_end, .. } => {
+                    trace!(target: "toml_query::resolver", index = idx; "Resolving index");
+                    let len = ary.len();
+                    let idx = array_index(idx, from_end, len)?;
+                    match ary.get_mut(idx) {
+                        None => if error_if_not_found {
+                            Err(Error::ArrayIndexOutOfBounds(idx, len))
+                        } else {
+                            Ok(None)
+                        },
+                        Some(sub_document) => Ok(Some(sub_document)),
                     }
                 },
                 &Token::Identifier { ref ident, .. } => {
-                    Err(Error::NoIdentifierInArray(ident.clone()))
+                    Err(Error::NoIdentifierInArray(ident.to_string()))
                 },
+                &Token::Wildcard { .. } | &Token::Union { .. } | &Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
             }
         },
 
-        _ => match tokens {
-            &Token::Identifier { ref ident, .. } => Err(Error::QueryingValueAsTable(ident.clone())),
+        _ => match token {
+            &Token::Identifier { ref ident, .. } => Err(Error::QueryingValueAsTable(ident.to_string())),
             &Token::Index { idx, .. }            => Err(Error::QueryingValueAsArray(idx)),
+            &Token::Wildcard { .. } | &Token::Union { .. } | &Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
         }
     }
 }
@@ -80,6 +101,14 @@ mod test {
         assert!(is_match!(result, Error::IdentifierNotFoundInDocument { .. }));
     }
 
+    #[test]
+    fn test_resolve_array_index_out_of_bounds() {
+        let mut toml = toml_from_str("example = [ 1 ]").unwrap();
+        let result = do_resolve!(toml => "example.[1]");
+
+        assert!(is_match!(result, Err(Error::ArrayIndexOutOfBounds(1, 1))));
+    }
+
     #[test]
     fn test_resolve_present_bool() {
         let mut toml = toml_from_str("example = true").unwrap();
@@ -408,7 +437,7 @@ mod test {
         assert!(result.is_some());
         let result = result.unwrap();
 
-        let tokens = tokenize_with_seperator(&String::from("color"), '.').unwrap();
+        let tokens = tokenize_with_seperator("color", '.').unwrap();
         let result = resolve(result, &tokens, true);
 
         assert!(result.is_ok());
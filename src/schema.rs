@@ -0,0 +1,345 @@
+/// Declarative expectations on a TOML document
+///
+/// A `Schema` is a list of queries, each with the type the value at that query must have and
+/// optionally a default value which is used if the value is not there. Additionally, constraints
+/// (see the `validate` module) can be attached to queries.
+///
+/// With the `typed` feature, `TomlValueIntoTypedExt::into_typed()` checks a document against a
+/// schema before deserializing it.
+
+#[cfg(feature = "typed")]
+use serde::de::DeserializeOwned;
+use toml::Value;
+
+use read::TomlValueReadExt;
+use types::Type;
+use insert::TomlValueInsertExt;
+use validate::Validator;
+#[cfg(feature = "typed")]
+use validate::Report;
+#[cfg(feature = "typed")]
+use document::Document;
+use error::{Error, Result};
+
+/// A single declaration in a `Schema`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaEntry {
+    query: String,
+    kind: Type,
+    default: Option<Value>,
+}
+
+impl SchemaEntry {
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn kind(&self) -> Type {
+        self.kind
+    }
+
+    pub fn default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    entries: Vec<SchemaEntry>,
+    constraints: Vec<(String, Validator)>,
+}
+
+impl Schema {
+
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    /// Declare that the value at `query` must be present and of type `kind`
+    pub fn declare(mut self, query: &str, kind: Type) -> Schema {
+        self.entries.push(SchemaEntry {
+            query: String::from(query),
+            kind,
+            default: None,
+        });
+        self
+    }
+
+    /// Declare that the value at `query` must be of type `kind`, and that `default` is used if it
+    /// is not present
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::TypeErrorAt(_, _, _))` if `default` is not of type `kind`
+    pub fn declare_with_default(mut self, query: &str, kind: Type, default: Value) -> Result<Schema> {
+        if !kind.matches(&default) {
+            let found = ::util::name_of_val(&default);
+            return Err(Error::TypeErrorAt(String::from(query), kind.name(), found));
+        }
+
+        self.entries.push(SchemaEntry {
+            query: String::from(query),
+            kind,
+            default: Some(default),
+        });
+        Ok(self)
+    }
+
+    /// Attach a constraint to the value at `query`
+    ///
+    /// The constraint is only checked if the value is present. Use `declare()` to require the
+    /// value to be there.
+    pub fn constrain(mut self, query: &str, validator: Validator) -> Schema {
+        self.constraints.push((String::from(query), validator));
+        self
+    }
+
+    pub fn entries(&self) -> &[SchemaEntry] {
+        &self.entries
+    }
+
+    pub fn constraints(&self) -> &[(String, Validator)] {
+        &self.constraints
+    }
+
+    /// Check `document` against all declarations of the schema
+    ///
+    /// Values which are not present but have a default are not reported, as `apply_defaults()`
+    /// would fill them in. Constraints are checked after the declarations.
+    ///
+    /// # Return value
+    ///
+    /// All violations are collected, the check does not stop at the first one. `Ok(())` is
+    /// returned only if there are none.
+    pub fn validate(&self, document: &Value) -> ::std::result::Result<(), Vec<Error>> {
+        let mut errors = self.entries
+            .iter()
+            .filter_map(|entry| match document.read(&entry.query) {
+                Ok(Some(value)) => if entry.kind.matches(value) {
+                    None
+                } else {
+                    let found = ::util::name_of_val(value);
+                    Some(Error::TypeErrorAt(entry.query.clone(), entry.kind.name(), found))
+                },
+                Ok(None) => if entry.default.is_some() {
+                    None
+                } else {
+                    Some(Error::NotAvailable(entry.query.clone()))
+                },
+                Err(e) => Some(e),
+            })
+            .collect::<Vec<Error>>();
+
+        for &(ref query, ref validator) in &self.constraints {
+            match document.read(query) {
+                Ok(Some(value)) => if let Err(e) = validator.check(query, value) {
+                    errors.push(e);
+                },
+                Ok(None) => {},
+                Err(e)   => errors.push(e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Insert the default value for every declaration which is not present in `document`
+    ///
+    /// Intermediate tables are created as with `TomlValueInsertExt::insert()`.
+    ///
+    /// # Return value
+    ///
+    /// The queries for which a default was inserted, in declaration order.
+    pub fn apply_defaults(&self, document: &mut Value) -> Result<Vec<String>> {
+        let mut applied = Vec::new();
+
+        for entry in self.entries.iter() {
+            if let Some(ref default) = entry.default {
+                if document.read(&entry.query)?.is_none() {
+                    let _ = document.insert(&entry.query, default.clone())?;
+                    applied.push(entry.query.clone());
+                }
+            }
+        }
+
+        Ok(applied)
+    }
+
+}
+
+/// Deserialize a whole document after checking it against a `Schema`
+#[cfg(feature = "typed")]
+pub trait TomlValueIntoTypedExt: Sized {
+
+    /// Validate the document with `schema`, apply the defaults of the schema and deserialize it
+    ///
+    /// Unlike deserializing directly, which stops at the first problem, this reports all
+    /// violations of the schema at once. If the document is valid, but still cannot be
+    /// deserialized, the report contains the deserialization error.
+    fn into_typed<T: DeserializeOwned>(self, schema: &Schema) -> ::std::result::Result<T, Report>;
+
+}
+
+#[cfg(feature = "typed")]
+impl TomlValueIntoTypedExt for Value {
+
+    fn into_typed<T: DeserializeOwned>(mut self, schema: &Schema) -> ::std::result::Result<T, Report> {
+        schema.validate(&self)?;
+        let _ = schema.apply_defaults(&mut self).map_err(|e| vec![e])?;
+        self.try_into().map_err(|e| Report::from(vec![Error::TomlDeserialize(e)]))
+    }
+
+}
+
+#[cfg(feature = "typed")]
+impl TomlValueIntoTypedExt for Document {
+
+    fn into_typed<T: DeserializeOwned>(self, schema: &Schema) -> ::std::result::Result<T, Report> {
+        self.into_value().into_typed(schema)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .declare("server.host", Type::String)
+            .declare_with_default("server.port", Type::Integer, Value::Integer(8080))
+            .and_then(|s| s.declare_with_default("log.level", Type::String, Value::String(String::from("info"))))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let toml : Value = toml_from_str(r#"
+        [server]
+        host = "localhost"
+        port = 1234
+        "#).unwrap();
+
+        assert!(schema().validate(&toml).is_ok());
+    }
+
+    #[test]
+    fn test_validate_collects_all_errors() {
+        let toml : Value = toml_from_str(r#"
+        [server]
+        port = "1234"
+        "#).unwrap();
+
+        let errs = schema().validate(&toml).unwrap_err();
+        assert_eq!(errs.len(), 2);
+        assert!(is_match!(errs[0], Error::NotAvailable(_)));
+        assert!(is_match!(errs[1], Error::TypeErrorAt(_, "Integer", "String")));
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let mut toml : Value = toml_from_str(r#"
+        [server]
+        host = "localhost"
+        port = 1234
+        "#).unwrap();
+
+        let applied = schema().apply_defaults(&mut toml).unwrap();
+        assert_eq!(applied, vec![String::from("log.level")]);
+
+        assert_eq!(toml.read("server.port").unwrap(), Some(&Value::Integer(1234)));
+        assert_eq!(toml.read("log.level").unwrap(), Some(&Value::String(String::from("info"))));
+    }
+
+    #[test]
+    fn test_apply_defaults_twice_is_noop() {
+        let mut toml : Value = toml_from_str("").unwrap();
+
+        assert_eq!(schema().apply_defaults(&mut toml).unwrap().len(), 2);
+        assert!(schema().apply_defaults(&mut toml).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_constraints() {
+        let schema = schema()
+            .constrain("server.port", Validator::Range(1..=65535))
+            .constrain("log.level", Validator::one_of_strings(&["debug", "info"]));
+
+        let toml : Value = toml_from_str(r#"
+        [server]
+        host = "localhost"
+        port = 0
+        "#).unwrap();
+
+        let errs = schema.validate(&toml).unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert!(is_match!(errs[0], Error::ValidationFailed(_, _)));
+    }
+
+    #[test]
+    fn test_default_of_wrong_type() {
+        let res = Schema::new().declare_with_default("a", Type::Integer, Value::Boolean(true));
+        assert!(is_match!(res, Err(Error::TypeErrorAt(ref q, "Integer", "Boolean")) if q == "a"));
+    }
+
+}
+
+#[cfg(all(test, feature = "typed"))]
+mod typed_test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        host: String,
+        port: i64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        server: Server,
+    }
+
+    fn schema() -> Schema {
+        Schema::new()
+            .declare("server.host", Type::String)
+            .declare_with_default("server.port", Type::Integer, Value::Integer(8080))
+            .unwrap()
+            .constrain("server.host", Validator::regex("^[a-z.]+$").unwrap())
+    }
+
+    #[test]
+    fn test_into_typed() {
+        let toml : Value = toml_from_str(r#"
+        [server]
+        host = "localhost"
+        "#).unwrap();
+
+        let config = toml.into_typed::<Config>(&schema()).unwrap();
+        assert_eq!(config, Config { server: Server { host: String::from("localhost"), port: 8080 } });
+    }
+
+    #[test]
+    fn test_into_typed_reports_all_errors() {
+        let toml : Value = toml_from_str(r#"
+        [server]
+        host = "Local Host"
+        port = "80"
+        "#).unwrap();
+
+        let report = Document::new(toml).into_typed::<Config>(&schema()).unwrap_err();
+        assert_eq!(report.failures().len(), 2);
+
+        let toml : Value = toml_from_str("[server]\nhost = 'localhost'").unwrap();
+        let report = toml.into_typed::<Config>(&Schema::new()).unwrap_err();
+        assert!(is_match!(report.failures()[0], Error::TomlDeserialize(_)));
+    }
+
+}
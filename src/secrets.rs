@@ -0,0 +1,119 @@
+/// Substitution of secrets
+///
+/// Documents can refer to secrets with string values of the form `"<scheme>:<location>"`, e.g.
+/// `"vault:kv/app#token"`. `substitute_secrets()` replaces them with the values fetched from the
+/// `SecretsProvider` for the scheme, so the secrets never have to be written into the document.
+
+use toml::Value;
+
+use path::{TomlPath, value_at_mut};
+use walk::walk;
+use error::{Error, Result};
+
+/// Fetches secrets for one scheme
+pub trait SecretsProvider {
+
+    /// The scheme of the values this provider resolves, without the `:`
+    fn scheme(&self) -> &str;
+
+    /// Fetch the secret at `location`, the part of the value after `<scheme>:`
+    fn fetch(&self, location: &str) -> Result<Value>;
+
+}
+
+/// Replace all values referring to secrets with the secrets
+///
+/// A string value refers to a secret if it starts with the scheme of one of the `providers`,
+/// followed by `:` and a non-empty location. Nothing is replaced unless all secrets could be fetched.
+///
+/// # Return value
+///
+/// * `Ok(paths)` with the paths of the replaced values
+/// * `Err(Error::SecretUnavailable(_, _))` with the path of the value and the error of the
+///   provider, if fetching a secret failed
+pub fn substitute_secrets(value: &mut Value, providers: &[&dyn SecretsProvider]) -> Result<Vec<TomlPath>> {
+    let mut secrets = Vec::new();
+    walk(value, None, &mut |path, sub| {
+        if let Value::String(ref s) = *sub {
+            let provider = providers.iter().find(|p| {
+                s.len() > p.scheme().len() + 1 && s.starts_with(p.scheme()) && s[p.scheme().len()..].starts_with(':')
+            });
+
+            if let Some(provider) = provider {
+                let location = &s[provider.scheme().len() + 1..];
+                let secret   = provider.fetch(location)
+                    .map_err(|e| Error::SecretUnavailable(path.to_string(), e.to_string()));
+                secrets.push((path.clone(), secret));
+            }
+        }
+    });
+
+    let secrets = secrets
+        .into_iter()
+        .map(|(path, secret)| secret.map(|s| (path, s)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(secrets
+        .into_iter()
+        .map(|(path, secret)| {
+            if let Some(target) = value_at_mut(value, path.segments()) {
+                *target = secret;
+            }
+            path
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    struct Vault;
+
+    impl SecretsProvider for Vault {
+        fn scheme(&self) -> &str {
+            "vault"
+        }
+
+        fn fetch(&self, location: &str) -> Result<Value> {
+            match location {
+                "kv/app#token" => Ok(Value::String(String::from("s3cr3t"))),
+                "kv/app#port"  => Ok(Value::Integer(5432)),
+                _              => Err(Error::NotAvailable(String::from(location))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut doc : Value = toml_from_str(r#"
+        name = "vault:"
+        other = "vaulted:kv/app#token"
+
+        [db]
+        token = "vault:kv/app#token"
+        ports = [ "vault:kv/app#port" ]
+        "#).unwrap();
+
+        let replaced = substitute_secrets(&mut doc, &[&Vault]).unwrap();
+        assert_eq!(replaced.iter().map(|p| p.to_string()).collect::<Vec<_>>(), vec!["db.ports.[0]", "db.token"]);
+        assert_eq!(doc["db"]["token"], Value::String(String::from("s3cr3t")));
+        assert_eq!(doc["db"]["ports"][0], Value::Integer(5432));
+        assert_eq!(doc["name"], Value::String(String::from("vault:")));
+    }
+
+    #[test]
+    fn test_unavailable() {
+        let mut doc : Value = toml_from_str(r#"
+        token = "vault:kv/app#token"
+        missing = "vault:kv/other"
+        "#).unwrap();
+        let before = doc.clone();
+
+        let res = substitute_secrets(&mut doc, &[&Vault]);
+        assert!(is_match!(res, Err(Error::SecretUnavailable(ref p, _)) if p == "missing"));
+        assert_eq!(doc, before);
+    }
+
+}
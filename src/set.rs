@@ -6,6 +6,8 @@ use toml::Value;
 
 use tokenizer::tokenize_with_seperator;
 use tokenizer::Token;
+use read::TomlValueReadExt;
+use path::TomlPath;
 use error::{Error, Result};
 
 pub trait TomlValueSetExt {
@@ -37,6 +39,26 @@ pub trait TomlValueSetExt {
         self.set_with_seperator(query, '.', value)
     }
 
+    /// Set `value` at `path`
+    ///
+    /// Unlike a query, the keys of `path` are never parsed, see `path::Path`. Otherwise like
+    /// `TomlValueSetExt::set_with_seperator`.
+    fn set_path(&mut self, path: &TomlPath, value: Value) -> Result<Option<Value>> {
+        self.set_with_seperator(&path.to_string(), '.', value)
+    }
+
+    /// Set `value` at `query` like `set_with_seperator()`, but refuse to replace a non-empty
+    /// table with another kind of value, or another value with a table
+    ///
+    /// In these cases `Err(Error::WouldShadowTable(_, _, _))` is returned and the document is not
+    /// changed. `set_with_seperator()` is the variant which replaces the value anyways.
+    fn set_checked(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>>
+        where Self: for<'doc> TomlValueReadExt<'doc>
+    {
+        ::util::check_shadowing(query, self.read_with_seperator(query, sep)?, &value)?;
+        self.set_with_seperator(query, sep, value)
+    }
+
     /// A convenience method for setting any arbitrary serializable value.
     #[cfg(feature = "typed")]
     fn set_serialized<S: Serialize>(&mut self, query: &str, value: S) -> Result<Option<Value>> {
@@ -49,46 +71,58 @@ pub trait TomlValueSetExt {
 impl TomlValueSetExt for Value {
 
     fn set_with_seperator(&mut self, query: &str, sep: char, value: Value) -> Result<Option<Value>> {
-        use resolver::mut_resolver::resolve;
+        instrument!("set", query, {
+            tokenize_with_seperator(query, sep).and_then(|tokens| set_impl(self, &tokens, value))
+        })
+    }
 
-        let mut tokens = try!(tokenize_with_seperator(query, sep));
-        let last = tokens.pop_last();
+    fn set_path(&mut self, path: &TomlPath, value: Value) -> Result<Option<Value>> {
+        let query = path.to_string();
+        instrument!("set", &query, path.to_tokens().and_then(|tokens| set_impl(self, &tokens, value)))
+    }
 
-        let val = try!(resolve(self, &tokens, true))
-            .unwrap(); // safe because of resolve() guarantees
-        let last = last.unwrap_or_else(|| Box::new(tokens));
+}
 
-        match *last {
-            Token::Identifier { ident, .. } => {
-                match val {
-                    &mut Value::Table(ref mut t) => {
-                        Ok(t.insert(ident, value))
-                    },
-                    &mut Value::Array(_) => Err(Error::NoIdentifierInArray(ident)),
-                    _ => Err(Error::QueryingValueAsTable(ident)),
-                }
+pub(crate) fn set_impl(document: &mut Value, tokens: &[Token], value: Value) -> Result<Option<Value>> {
+    use resolver::mut_resolver::resolve;
+    use resolver::array_index;
+
+    let (last, parents) = tokens.split_last().ok_or(Error::EmptyQueryError)?;
+    let val = try!(resolve(document, parents, true))
+        .unwrap(); // safe because of resolve() guarantees
+
+    match *last {
+        Token::Identifier { ref ident, .. } => {
+            match val {
+                &mut Value::Table(ref mut t) => {
+                    Ok(t.insert(ident.to_string(), value))
+                },
+                &mut Value::Array(_) => Err(Error::NoIdentifierInArray(ident.to_string())),
+                _ => Err(Error::QueryingValueAsTable(ident.to_string())),
             }
+        }
 
-            Token::Index { idx, .. } => {
-                match val {
-                    &mut Value::Array(ref mut a) => {
-                        if a.len() > idx {
-                            let result = a.swap_remove(idx);
-                            a.insert(idx, value);
-                            Ok(Some(result))
-                        } else {
-                            a.push(value);
-                            Ok(None)
-                        }
+        Token::Index { idx, from_end, .. } => {
+            match val {
+                &mut Value::Array(ref mut a) => {
+                    let idx = array_index(idx, from_end, a.len())?;
+                    if a.len() > idx {
+                        let result = a.swap_remove(idx);
+                        a.insert(idx, value);
+                        Ok(Some(result))
+                    } else {
+                        a.push(value);
+                        Ok(None)
                     }
-                    &mut Value::Table(_) => Err(Error::NoIndexInTable(idx)),
-                    _ => Err(Error::QueryingValueAsArray(idx)),
                 }
+                &mut Value::Table(_) => Err(Error::NoIndexInTable(idx)),
+                _ => Err(Error::QueryingValueAsArray(idx)),
             }
-
         }
-    }
 
+        Token::Wildcard { .. } | Token::Union { .. } | Token::Filter { .. } => Err(Error::UnexpectedMultiMatch),
+
+    }
 }
 
 #[cfg(test)]
@@ -323,6 +357,19 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_set_with_seperator_top_level_value() {
+        let mut toml : Value = toml_from_str(r#"
+        val = 0
+        "#).unwrap();
+
+        let res = toml.set_with_seperator(&String::from("val"), '.', Value::Integer(1));
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), Some(Value::Integer(0)));
+        assert_eq!(toml.get("val"), Some(&Value::Integer(1)));
+    }
+
     #[test]
     fn test_set_with_seperator_into_nonexistent_table() {
         let mut toml : Value = toml_from_str("").unwrap();
@@ -403,6 +450,42 @@ mod test {
         assert!(is_match!(res, Error::QueryingValueAsArray(_)));
     }
 
+    #[test]
+    fn test_set_checked() {
+        let mut toml : Value = toml_from_str(r#"
+        name = "a"
+        empty = {}
+
+        [server]
+        port = 80
+        "#).unwrap();
+
+        let res = toml.set_checked("server", '.', Value::Integer(1));
+        assert!(is_match!(res, Err(Error::WouldShadowTable(ref q, "Table", "Integer")) if q == "server"));
+        let res = toml.set_checked("name", '.', Value::Table(Default::default()));
+        assert!(is_match!(res, Err(Error::WouldShadowTable(ref q, "String", "Table")) if q == "name"));
+        assert_eq!(toml.read("server.port").unwrap(), Some(&Value::Integer(80)));
+
+        assert!(toml.set_checked("server.port", '.', Value::Integer(8080)).is_ok());
+        assert!(toml.set_checked("empty", '.', Value::Integer(1)).is_ok());
+        assert!(toml.set_checked("server", '.', Value::Table(Default::default())).is_ok());
+        assert!(toml.set("name", Value::Table(Default::default())).is_ok());
+    }
+
+    #[test]
+    fn test_set_with_seperator_index_from_end() {
+        let mut toml : Value = toml_from_str(r#"
+        values = [ 1, 2, 3 ]
+        "#).unwrap();
+
+        let res = toml.set_with_seperator(&String::from("values.[-1]"), '.', Value::Integer(4));
+        assert_eq!(res.unwrap(), Some(Value::Integer(3)));
+        assert_eq!(toml["values"], Value::Array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(4)]));
+
+        let res = toml.set_with_seperator(&String::from("values.[-4]"), '.', Value::Integer(0));
+        assert!(is_match!(res, Err(Error::IndexFromEndOutOfBounds(4, 3))));
+    }
+
     #[cfg(feature = "typed")]
     #[test]
     fn test_serialize() {
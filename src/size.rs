@@ -0,0 +1,199 @@
+/// Size estimations for TOML documents
+///
+/// These functions walk the document and add up the sizes of its parts, without building the
+/// serialized representation.
+
+use std::mem::size_of;
+
+use toml::Value;
+use toml::value::Table;
+
+/// Approximate the length of `value` serialized as TOML text, in bytes
+///
+/// Tables are assumed to be written as `[header]` sections (and arrays of tables as
+/// `[[header]]` sections), everything else inline. The result is an estimation: Escaping and
+/// number formatting may make the actual text slightly longer or shorter.
+pub fn estimated_len(value: &Value) -> usize {
+    match *value {
+        Value::Table(ref t) => table_len(t, 0),
+        _                   => inline_len(value),
+    }
+}
+
+/// Approximate the memory used by `value`, in bytes
+///
+/// This is the size of the `Value` itself plus the heap memory of the strings, arrays and tables
+/// in it, counting their allocated capacity. The bookkeeping of the allocator and the internal
+/// nodes of the tables are not included, so the actual usage is somewhat higher.
+pub fn memory_footprint(value: &Value) -> usize {
+    size_of::<Value>() + heap_size(value)
+}
+
+fn heap_size(value: &Value) -> usize {
+    match *value {
+        Value::String(ref s)  => s.capacity(),
+        Value::Array(ref ary) => {
+            let unused = ary.capacity() - ary.len();
+            ary.iter().map(memory_footprint).sum::<usize>() + unused * size_of::<Value>()
+        },
+        Value::Table(ref t)   => {
+            t.iter()
+                .map(|(k, v)| size_of::<String>() + k.capacity() + memory_footprint(v))
+                .sum()
+        },
+        _ => 0,
+    }
+}
+
+/// Length of the body of a table section, whose header path has length `header_len`
+fn table_len(table: &Table, header_len: usize) -> usize {
+    let mut len = 0;
+
+    for (key, value) in table.iter() {
+        if !is_section(value) {
+            len += key_len(key) + " = ".len() + inline_len(value) + 1;
+        }
+    }
+
+    for (key, value) in table.iter() {
+        let sub_header_len = if header_len == 0 {
+            key_len(key)
+        } else {
+            header_len + 1 + key_len(key)
+        };
+
+        match *value {
+            Value::Table(ref sub) => {
+                len += "\n[]\n".len() + sub_header_len + table_len(sub, sub_header_len);
+            },
+            Value::Array(ref ary) if is_section(value) => for element in ary {
+                if let Value::Table(ref sub) = *element {
+                    len += "\n[[]]\n".len() + sub_header_len + table_len(sub, sub_header_len);
+                }
+            },
+            _ => { /* already counted */ },
+        }
+    }
+
+    len
+}
+
+/// Whether a value is written as its own section rather than inline
+pub(crate) fn is_section(value: &Value) -> bool {
+    match *value {
+        Value::Table(_)       => true,
+        Value::Array(ref ary) => !ary.is_empty() && ary.iter().all(|v| is_match!(*v, Value::Table(_))),
+        _                     => false,
+    }
+}
+
+fn key_len(key: &str) -> usize {
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.len()
+    } else {
+        string_len(key)
+    }
+}
+
+fn string_len(s: &str) -> usize {
+    let escapes = s.chars().filter(|c| *c == '"' || *c == '\\' || c.is_control()).count();
+    s.len() + escapes + 2
+}
+
+fn inline_len(value: &Value) -> usize {
+    match *value {
+        Value::String(ref s)   => string_len(s),
+        Value::Integer(i)      => i.to_string().len(),
+        Value::Float(f)        => f.to_string().len() + if f.fract() == 0.0 { 2 } else { 0 },
+        Value::Boolean(b)      => if b { 4 } else { 5 },
+        Value::Datetime(ref d) => d.to_string().len(),
+        Value::Array(ref ary)  => {
+            let elements = ary.iter().map(inline_len).sum::<usize>();
+            let seperators = ary.len().saturating_sub(1) * ", ".len();
+            "[]".len() + elements + seperators
+        },
+        Value::Table(ref t)    => {
+            let entries = t.iter()
+                .map(|(k, v)| key_len(k) + " = ".len() + inline_len(v))
+                .sum::<usize>();
+            let seperators = t.len().saturating_sub(1) * ", ".len();
+            "{  }".len() + entries + seperators
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn assert_close(doc: &str) {
+        let value : Value = toml_from_str(doc).unwrap();
+        let actual    = ::toml::to_string(&value).unwrap().len() as f64;
+        let estimated = estimated_len(&value) as f64;
+
+        assert!((estimated - actual).abs() <= actual * 0.1,
+                "Estimated {}, but actual length is {}", estimated, actual);
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(estimated_len(&Value::Integer(1234)), 4);
+        assert_eq!(estimated_len(&Value::Boolean(false)), 5);
+        assert_eq!(estimated_len(&Value::String(String::from("a\"b"))), 6);
+    }
+
+    #[test]
+    fn test_memory_footprint() {
+        let value = size_of::<Value>();
+        assert_eq!(memory_footprint(&Value::Integer(1)), value);
+        assert_eq!(memory_footprint(&Value::String(String::with_capacity(10))), value + 10);
+
+        let doc : Value = toml_from_str(r#"
+        [a]
+        b = [ 1, 2 ]
+        "#).unwrap();
+        let b = match doc["a"]["b"] {
+            Value::Array(ref ary) => value + ary.capacity() * value,
+            _                     => unreachable!(),
+        };
+        assert_eq!(memory_footprint(&doc["a"]["b"]), b);
+        assert!(memory_footprint(&doc["a"]) >= value + size_of::<String>() + 1 + b);
+        assert!(memory_footprint(&doc) >= value + size_of::<String>() + 1 + memory_footprint(&doc["a"]));
+    }
+
+    #[test]
+    fn test_flat_document() {
+        assert_close(r#"
+        name = "some name"
+        version = 12345
+        enabled = true
+        ratio = 0.25
+        list = [ "a", "b", "c" ]
+        "#);
+    }
+
+    #[test]
+    fn test_nested_document() {
+        assert_close(r#"
+        top = "level"
+
+        [package]
+        name = "toml-query"
+        authors = [ "Someone <some@one.org>", "Else <else@where.org>" ]
+
+        [package.metadata.docs]
+        features = [ "typed", "logging" ]
+
+        [[bin]]
+        name = "first"
+        path = "src/bin/first.rs"
+
+        [[bin]]
+        name = "second"
+        path = "src/bin/second.rs"
+        "#);
+    }
+
+}
@@ -0,0 +1,439 @@
+/// Answering queries while reading TOML text
+///
+/// `StreamingReader` reads a document line by line and only parses the values the queries need,
+/// so the document never has to be held in memory as a whole. Everything else is only scanned
+/// to find where it ends.
+///
+/// The input is expected to be a valid TOML document. Keys are not checked for duplicates, and
+/// errors in values which are not needed for any query are not detected.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use toml::Value;
+
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+
+/// A fixed set of queries, answered from TOML text
+#[derive(Debug, Clone)]
+pub struct StreamingReader {
+    queries: Vec<TomlPath>,
+}
+
+impl StreamingReader {
+
+    /// Compile `queries`
+    pub fn new(queries: &[&str]) -> Result<StreamingReader> {
+        let queries = queries
+            .iter()
+            .map(|q| TomlPath::from_query(q, '.'))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(StreamingReader { queries })
+    }
+
+    /// Read `input` and return the values of the queries, in the order the queries were passed
+    ///
+    /// Queries which point to tables get the table with everything below it.
+    pub fn read<R: BufRead>(&self, input: R) -> Result<Vec<Option<Value>>> {
+        let mut state = State {
+            queries: &self.queries,
+            results: vec![None; self.queries.len()],
+            header: TomlPath::new(),
+            array_lengths: HashMap::new(),
+        };
+
+        let mut lines = input.lines().enumerate();
+        while let Some((idx, line)) = lines.next() {
+            let line_no = idx + 1;
+            let line    = line.map_err(Error::Io)?;
+            let trimmed = line.trim();
+            let err     = |msg: String| Error::StreamingParseError(line_no, msg);
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if trimmed.starts_with("[[") {
+                let (keys, rest) = parse_keys(&trimmed[2..], ']').map_err(&err)?;
+                if !rest.starts_with("]]") {
+                    return Err(err(String::from("expected ']]'")));
+                }
+                state.array_header(keys).map_err(&err)?;
+            } else if trimmed.starts_with('[') {
+                let (keys, _) = parse_keys(&trimmed[1..], ']').map_err(&err)?;
+                state.table_header(keys).map_err(&err)?;
+            } else {
+                let (keys, rest) = parse_keys(trimmed, '=').map_err(&err)?;
+                let mut text = String::from(&rest[1..]);
+                while !is_complete(&text) {
+                    match lines.next() {
+                        Some((_, line)) => {
+                            text.push('\n');
+                            text.push_str(&line.map_err(Error::Io)?);
+                        },
+                        None => return Err(err(String::from("unexpected end of input"))),
+                    }
+                }
+                state.key_value(keys, &text).map_err(&err)?;
+            }
+        }
+
+        Ok(state.results)
+    }
+
+}
+
+struct State<'a> {
+    queries: &'a [TomlPath],
+    results: Vec<Option<Value>>,
+
+    /// The path of the current table
+    header: TomlPath,
+
+    /// Number of elements of the arrays of tables seen so far
+    array_lengths: HashMap<TomlPath, usize>,
+}
+
+type ParseResult<T> = ::std::result::Result<T, String>;
+
+impl<'a> State<'a> {
+
+    /// The path of a table header, with the current elements of arrays of tables filled in
+    fn resolve(&self, keys: Vec<String>) -> TomlPath {
+        let mut path = TomlPath::new();
+        for key in keys {
+            path.push(Segment::Key(key));
+            if let Some(len) = self.array_lengths.get(&path) {
+                path.push(Segment::Index(len - 1));
+            }
+        }
+        path
+    }
+
+    fn table_header(&mut self, keys: Vec<String>) -> ParseResult<()> {
+        self.header = self.resolve(keys);
+        self.enter_table()
+    }
+
+    fn array_header(&mut self, mut keys: Vec<String>) -> ParseResult<()> {
+        let last     = keys.pop().ok_or_else(|| String::from("empty header"))?;
+        let mut path = self.resolve(keys);
+        path.push(Segment::Key(last));
+
+        let len = {
+            let len = self.array_lengths.entry(path.clone()).or_insert(0);
+            *len += 1;
+            *len
+        };
+        path.push(Segment::Index(len - 1));
+
+        self.header = path;
+        self.enter_table()
+    }
+
+    /// Create the table for the current header in all results it is part of
+    fn enter_table(&mut self) -> ParseResult<()> {
+        let header = &self.header;
+        for (query, result) in self.queries.iter().zip(self.results.iter_mut()) {
+            if header.starts_with(query) {
+                let relative = &header.segments()[query.len()..];
+                let root     = result.get_or_insert_with(|| container_for(relative.first()));
+                slot_at(root, relative)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn key_value(&mut self, keys: Vec<String>, text: &str) -> ParseResult<()> {
+        let mut path = self.header.clone();
+        for key in keys {
+            path.push(Segment::Key(key));
+        }
+
+        let mut value = None;
+        for (query, result) in self.queries.iter().zip(self.results.iter_mut()) {
+            let below = query.starts_with(&path);
+            let above = path.starts_with(query);
+            if !below && !above {
+                continue;
+            }
+
+            if value.is_none() {
+                value = Some(parse_value(text)?);
+            }
+            let value = value.as_ref().unwrap();
+
+            if below {
+                *result = get(value, &query.segments()[path.len()..]).cloned();
+            } else {
+                let relative = &path.segments()[query.len()..];
+                let root     = result.get_or_insert_with(|| container_for(relative.first()));
+                *slot_at(root, relative)? = value.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+fn parse_value(text: &str) -> ParseResult<Value> {
+    let document = ::toml::from_str::<Value>(&format!("v = {}", text)).map_err(|e| e.to_string())?;
+    match document {
+        Value::Table(mut t) => t.remove("v").ok_or_else(|| String::from("no value")),
+        _                   => Err(String::from("no value")),
+    }
+}
+
+fn get<'v>(mut value: &'v Value, path: &[Segment]) -> Option<&'v Value> {
+    for segment in path {
+        value = match (value, segment) {
+            (&Value::Table(ref t), &Segment::Key(ref key)) => t.get(key)?,
+            (&Value::Array(ref a), &Segment::Index(idx))   => a.get(idx)?,
+            _                                              => return None,
+        };
+    }
+    Some(value)
+}
+
+/// An empty array if `next` is an index, an empty table otherwise
+fn container_for(next: Option<&Segment>) -> Value {
+    match next {
+        Some(&Segment::Index(_)) => Value::Array(Vec::new()),
+        _                        => Value::Table(BTreeMap::new()),
+    }
+}
+
+/// Get the value at `path` below `root`, creating tables on the way
+///
+/// An index one past the end of an array appends a table to it.
+fn slot_at<'v>(root: &'v mut Value, path: &[Segment]) -> ParseResult<&'v mut Value> {
+    match path.split_first() {
+        None => Ok(root),
+        Some((&Segment::Key(ref key), rest)) => match *root {
+            Value::Table(ref mut t) => {
+                let next = t.entry(key.clone()).or_insert_with(|| container_for(rest.first()));
+                slot_at(next, rest)
+            },
+            _ => Err(format!("'{}' is not in a table", key)),
+        },
+        Some((&Segment::Index(idx), rest)) => match *root {
+            Value::Array(ref mut a) => {
+                if idx == a.len() {
+                    a.push(Value::Table(BTreeMap::new()));
+                }
+                match a.get_mut(idx) {
+                    Some(next) => slot_at(next, rest),
+                    None       => Err(format!("index {} out of order", idx)),
+                }
+            },
+            _ => Err(format!("[{}] is not in an array", idx)),
+        },
+    }
+}
+
+/// Parse a (dotted) key up to `end`, returning the keys and the text starting at `end`
+fn parse_keys(text: &str, end: char) -> ParseResult<(Vec<String>, &str)> {
+    let mut keys  = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    loop {
+        while chars.peek().map(|&(_, c)| c == ' ' || c == '\t').unwrap_or(false) {
+            let _ = chars.next();
+        }
+
+        let mut key = String::new();
+        match chars.next() {
+            Some((_, '"')) => loop {
+                match chars.next() {
+                    Some((_, '"'))  => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => key.push('\n'),
+                        Some((_, 't')) => key.push('\t'),
+                        Some((_, 'r')) => key.push('\r'),
+                        Some((_, c))   => key.push(c),
+                        None           => return Err(String::from("unterminated key")),
+                    },
+                    Some((_, c))    => key.push(c),
+                    None            => return Err(String::from("unterminated key")),
+                }
+            },
+            Some((_, '\'')) => loop {
+                match chars.next() {
+                    Some((_, '\'')) => break,
+                    Some((_, c))    => key.push(c),
+                    None            => return Err(String::from("unterminated key")),
+                }
+            },
+            Some((_, c)) if c.is_alphanumeric() || c == '_' || c == '-' => {
+                key.push(c);
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '-' {
+                        key.push(c);
+                        let _ = chars.next();
+                    } else {
+                        break;
+                    }
+                }
+            },
+            _ => return Err(String::from("expected a key")),
+        }
+        keys.push(key);
+
+        while chars.peek().map(|&(_, c)| c == ' ' || c == '\t').unwrap_or(false) {
+            let _ = chars.next();
+        }
+
+        match chars.next() {
+            Some((_, '.'))           => continue,
+            Some((i, c)) if c == end => return Ok((keys, &text[i..])),
+            _                        => return Err(format!("expected '.' or '{}' after key", end)),
+        }
+    }
+}
+
+/// Whether `text` is a complete value, i.e. all strings, arrays and inline tables are closed
+fn is_complete(text: &str) -> bool {
+    let bytes     = text.as_bytes();
+    let mut depth = 0i64;
+    let mut i     = 0;
+
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''") {
+            let delim = &rest[..3];
+            let mut j = i + 3;
+            loop {
+                if j + 3 > bytes.len() {
+                    return false;
+                }
+                if &bytes[j..j + 3] == delim && (delim[0] == b'\'' || bytes[j - 1] != b'\\') {
+                    break;
+                }
+                j += 1;
+            }
+            i = j + 3;
+            continue;
+        }
+
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            },
+            b'\'' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'\'' {
+                    i += 1;
+                }
+            },
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            },
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => depth -= 1,
+            _ => {},
+        }
+        i += 1;
+    }
+
+    depth <= 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use read::TomlValueReadExt;
+
+    const DOC: &str = r#"
+# A lock file
+version = 3
+
+[metadata]
+generated = "by a tool # not a comment"
+"quoted.key" = 'with = sign'
+
+[[package]]
+name = "a"
+version = "1.0.0"
+dependencies = [
+    "b",    # first
+    "c [x]",
+]
+
+[package.source]
+kind = "registry"
+
+[[package]]
+name = "b"
+version = "2.0.0"
+description = """
+Multi-line
+[not a header]
+"""
+
+[[package.features]]
+name = "default"
+"#;
+
+    fn read(queries: &[&str]) -> Vec<Option<Value>> {
+        StreamingReader::new(queries).unwrap().read(DOC.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_leaf_values() {
+        let values = read(&["version", "metadata.generated", "package.[1].name", "package.[0].dependencies.[1]"]);
+        assert_eq!(values[0], Some(Value::Integer(3)));
+        assert_eq!(values[1], Some(Value::String(String::from("by a tool # not a comment"))));
+        assert_eq!(values[2], Some(Value::String(String::from("b"))));
+        assert_eq!(values[3], Some(Value::String(String::from("c [x]"))));
+    }
+
+    #[test]
+    fn test_missing_values() {
+        let values = read(&["nope", "metadata.nope", "package.[2]"]);
+        assert_eq!(values, vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_tables() {
+        let full : Value = ::toml::from_str(DOC).unwrap();
+        let values = read(&["metadata", "package", "package.[0].source", "package.[1].features"]);
+
+        assert_eq!(values[0].as_ref(), full.read("metadata").unwrap());
+        assert_eq!(values[1].as_ref(), full.read("package").unwrap());
+        assert_eq!(values[2].as_ref(), full.read("package.[0].source").unwrap());
+        assert_eq!(values[3].as_ref(), full.read("package.[1].features").unwrap());
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let reader = StreamingReader::new(&["a"]).unwrap();
+        let res = reader.read("x = 1\na = [ 1, ".as_bytes());
+        assert!(is_match!(res, Err(Error::StreamingParseError(2, _))));
+
+        let res = reader.read("a = nope".as_bytes());
+        assert!(is_match!(res, Err(Error::StreamingParseError(1, _))));
+    }
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete(" 1"));
+        assert!(is_complete(" [ 1, 2 ] # [ comment"));
+        assert!(is_complete(" \"[\""));
+        assert!(!is_complete(" [ 1,"));
+        assert!(!is_complete(" \"\"\"abc"));
+        assert!(is_complete(" '''a\n]'''"));
+    }
+
+}
@@ -0,0 +1,115 @@
+/// The Toml Template extensions
+///
+/// Stamping out copies of a part of a document, with some values replaced.
+
+use toml::Value;
+
+use read::TomlValueReadExt;
+use set::TomlValueSetExt;
+use insert::TomlValueInsertExt;
+use error::{Error, Result};
+
+pub trait TomlValueTemplateExt {
+
+    /// Clone the value at `template`, apply `substitutions` to the clone and insert it at `target`
+    ///
+    /// # Semantics
+    ///
+    /// The queries in `substitutions` are relative to the template. If the clone has a value at
+    /// such a query, it is replaced (as with `set()`), otherwise the value is inserted (as with
+    /// `insert()`, creating intermediate tables). Substitutions are applied in order.
+    ///
+    /// The result is inserted at `target` with `insert()` semantics.
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(Some(old_value))` if a value was replaced at `target`, `Ok(None)` otherwise
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `template`
+    ///
+    fn instantiate(&mut self, template: &str, target: &str, substitutions: &[(&str, Value)])
+        -> Result<Option<Value>>;
+
+}
+
+impl TomlValueTemplateExt for Value {
+
+    fn instantiate(&mut self, template: &str, target: &str, substitutions: &[(&str, Value)])
+        -> Result<Option<Value>>
+    {
+        let mut instance = self.read(template)?
+            .ok_or_else(|| Error::NotAvailable(String::from(template)))?
+            .clone();
+
+        for &(query, ref value) in substitutions {
+            if instance.read(query)?.is_some() {
+                let _ = instance.set(query, value.clone())?;
+            } else {
+                let _ = instance.insert(query, value.clone())?;
+            }
+        }
+
+        self.insert(target, instance)
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Value {
+        toml_from_str(r#"
+        [templates.tenant]
+        name = "TEMPLATE"
+        quota = 10
+
+        [templates.tenant.db]
+        host = "localhost"
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let mut toml = doc();
+
+        let res = toml.instantiate("templates.tenant", "tenants.acme", &[
+            ("name", Value::String(String::from("acme"))),
+            ("db.host", Value::String(String::from("db.acme"))),
+            ("db.port", Value::Integer(5432)),
+        ]);
+        assert!(res.unwrap().is_none());
+
+        let expected : Value = toml_from_str(r#"
+        name = "acme"
+        quota = 10
+
+        [db]
+        host = "db.acme"
+        port = 5432
+        "#).unwrap();
+
+        assert_eq!(toml.read("tenants.acme").unwrap(), Some(&expected));
+
+        // The template itself is untouched
+        let template = doc();
+        assert_eq!(toml.read("templates.tenant").unwrap(), template.read("templates.tenant").unwrap());
+    }
+
+    #[test]
+    fn test_instantiate_missing_template() {
+        let mut toml = doc();
+        let res = toml.instantiate("templates.nope", "tenants.acme", &[]);
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+    }
+
+    #[test]
+    fn test_instantiate_replaces_target() {
+        let mut toml = doc();
+        let _ = toml.insert("tenants.acme", Value::Integer(1)).unwrap();
+
+        let res = toml.instantiate("templates.tenant", "tenants.acme", &[]).unwrap();
+        assert_eq!(res, Some(Value::Integer(1)));
+    }
+
+}
@@ -0,0 +1,199 @@
+//! Utilities for testing code which builds on this crate
+//!
+//! This module is only available with the `testutils` feature. It provides `quickcheck`
+//! generators for random documents, and for random documents together with queries which are
+//! known to resolve in them, and the `assert_toml_eq!` and `assert_query_eq!` macros, which
+//! print the differences between documents by path when they fail.
+
+use std::collections::BTreeMap;
+
+use quickcheck::{Arbitrary, Gen};
+use toml::Value;
+
+use walk::paths;
+
+const MAX_DEPTH: usize = 3;
+
+/// Assert that two documents are equal, printing their differences otherwise
+///
+/// See `diff::diff()`. The differences are listed from the left to the right document.
+#[macro_export]
+macro_rules! assert_toml_eq {
+    ($left:expr, $right:expr) => {{
+        let differences = $crate::diff::diff(&$left, &$right);
+        if !differences.is_empty() {
+            let lines = differences.iter().map(|d| format!("  {}", d)).collect::<Vec<_>>();
+            panic!("assertion failed: documents differ (left -> right):\n{}", lines.join("\n"));
+        }
+    }};
+}
+
+/// Assert that the value at a query equals the expected value, printing their differences
+/// otherwise
+///
+/// The paths of the differences are relative to the query.
+#[macro_export]
+macro_rules! assert_query_eq {
+    ($doc:expr, $query:expr, $expected:expr) => {{
+        use $crate::read::TomlValueReadExt;
+
+        let query = $query;
+        match $doc.read(query) {
+            Ok(Some(found)) => {
+                let differences = $crate::diff::diff(found, &$expected);
+                if !differences.is_empty() {
+                    let lines = differences.iter().map(|d| format!("  {}", d)).collect::<Vec<_>>();
+                    panic!("assertion failed: value at '{}' differs (found -> expected):\n{}",
+                           query, lines.join("\n"));
+                }
+            },
+            Ok(None) => panic!("assertion failed: no value at '{}'", query),
+            Err(e)   => panic!("assertion failed: reading '{}' failed: {}", query, e),
+        }
+    }};
+}
+
+/// A random TOML document with a table at the top level
+///
+/// Keys are generated so that they can be used in queries with the default seperator. Arrays
+/// are homogeneous and never contain arrays, and strings contain no control characters, so the
+/// document can always be serialized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitraryDocument(pub Value);
+
+impl Arbitrary for ArbitraryDocument {
+    fn arbitrary<G: Gen>(g: &mut G) -> ArbitraryDocument {
+        ArbitraryDocument(arbitrary_table(g, 0))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = ArbitraryDocument>> {
+        Box::new(shrink_table(&self.0).into_iter().map(ArbitraryDocument))
+    }
+}
+
+/// A random document together with queries for all values in it
+///
+/// Every query in `queries` resolves to a value when read from `document`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentWithQueries {
+    pub document: Value,
+    pub queries: Vec<String>,
+}
+
+impl DocumentWithQueries {
+    fn new(document: Value) -> DocumentWithQueries {
+        let queries = paths(&document, None).into_iter().map(|p| p.to_string()).collect();
+        DocumentWithQueries { document, queries }
+    }
+}
+
+impl Arbitrary for DocumentWithQueries {
+    fn arbitrary<G: Gen>(g: &mut G) -> DocumentWithQueries {
+        DocumentWithQueries::new(arbitrary_table(g, 0))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = DocumentWithQueries>> {
+        Box::new(shrink_table(&self.document).into_iter().map(DocumentWithQueries::new))
+    }
+}
+
+/// Shrink a table by removing one of its entries
+fn shrink_table(value: &Value) -> Vec<Value> {
+    match *value {
+        Value::Table(ref t) => t.keys()
+            .map(|key| {
+                let mut smaller = t.clone();
+                let _ = smaller.remove(key);
+                Value::Table(smaller)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn arbitrary_key<G: Gen>(g: &mut G) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyz_";
+    const REST: &[u8]  = b"abcdefghijklmnopqrstuvwxyz_0123456789-";
+
+    let len = g.gen_range(0, 8);
+    let mut key = String::new();
+    key.push(*g.choose(FIRST).unwrap() as char);
+    for _ in 0..len {
+        key.push(*g.choose(REST).unwrap() as char);
+    }
+    key
+}
+
+fn arbitrary_table<G: Gen>(g: &mut G, depth: usize) -> Value {
+    let len = g.gen_range(0, 5);
+    let table = (0..len)
+        .map(|_| (arbitrary_key(g), arbitrary_value(g, depth + 1)))
+        .collect::<BTreeMap<String, Value>>();
+    Value::Table(table)
+}
+
+/// Generate a value of kind `kind`, see `arbitrary_value()`
+fn arbitrary_value_of_kind<G: Gen>(g: &mut G, kind: usize, depth: usize) -> Value {
+    match kind {
+        0 => Value::String(String::arbitrary(g).chars().filter(|c| !c.is_control()).collect()),
+        1 => Value::Integer(i64::arbitrary(g)),
+        2 => Value::Float(g.gen_range(-1.0e6, 1.0e6)),
+        3 => Value::Boolean(bool::arbitrary(g)),
+        4 => Value::Datetime("1979-05-27T07:32:00Z".parse().unwrap()),
+        5 => {
+            // No arrays of arrays, toml 0.4 cannot serialize all of them
+            let element_kind = match g.gen_range(0, if depth >= MAX_DEPTH { 5 } else { 6 }) {
+                5 => 6,
+                k => k,
+            };
+            let len = g.gen_range(0, 4);
+            Value::Array((0..len).map(|_| arbitrary_value_of_kind(g, element_kind, depth + 1)).collect())
+        },
+        _ => arbitrary_table(g, depth),
+    }
+}
+
+fn arbitrary_value<G: Gen>(g: &mut G, depth: usize) -> Value {
+    let kind = g.gen_range(0, if depth >= MAX_DEPTH { 5 } else { 7 });
+    arbitrary_value_of_kind(g, kind, depth)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use read::TomlValueReadExt;
+
+    #[test]
+    fn test_assert_toml_eq() {
+        let doc: Value = ::toml::from_str("a = 1").unwrap();
+        assert_toml_eq!(doc, doc.clone());
+        assert_query_eq!(doc, "a", Value::Integer(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "~ b.[1]: 2 -> 3")]
+    fn test_assert_toml_eq_fails() {
+        let left: Value  = ::toml::from_str("b = [ 1, 2 ]").unwrap();
+        let right: Value = ::toml::from_str("b = [ 1, 3 ]").unwrap();
+        assert_toml_eq!(left, right);
+    }
+
+    #[test]
+    #[should_panic(expected = "no value at 'x'")]
+    fn test_assert_query_eq_missing() {
+        let doc: Value = ::toml::from_str("a = 1").unwrap();
+        assert_query_eq!(doc, "x", Value::Integer(1));
+    }
+
+    quickcheck! {
+        fn test_queries_resolve(dwq: DocumentWithQueries) -> bool {
+            dwq.queries.iter().all(|q| dwq.document.read(q).map(|v| v.is_some()).unwrap_or(false))
+        }
+
+        fn test_documents_serialize(doc: ArbitraryDocument) -> bool {
+            let text = ::toml::to_string(&doc.0).unwrap();
+            ::toml::from_str::<Value>(&text).unwrap() == doc.0
+        }
+    }
+
+}
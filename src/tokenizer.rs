@@ -1,132 +1,428 @@
 /// The tokenizer for the query interpreter
-
+///
+/// With only the `parser` feature, this module and `path` are available without the `toml`
+/// dependency, for tools which work with query strings but not with documents.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::iter::FromIterator;
+use std::iter::Peekable;
+use std::ops::{Deref, Range};
+use std::str::CharIndices;
+
+use path::write_key;
 use error::{Error, Result};
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Token {
+/// A segment of a query
+///
+/// Identifiers borrow from the query they were parsed from, if they are written in it as they
+/// are, so parsing a query does not copy its keys. `Token::into_owned()` detaches a token from
+/// the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
     Identifier {
-        ident: String,
-        next: Option<Box<Token>>
+        ident: Cow<'a, str>,
     },
 
+    /// An array index, `from_end` is set for negative indices: `[-1]` has `idx: 1`
+    ///
+    /// `[last]` is the same as `[-1]`. `[+]` has `idx: 0` and `from_end` set, it points behind the
+    /// last element, where inserts append.
     Index {
         idx: usize,
-        next: Option<Box<Token>>
+        from_end: bool,
+    },
+
+    /// `*`, matches every key of a table
+    Wildcard,
+
+    /// `{a,b,c}`, matches the listed keys of a table
+    Union {
+        keys: Vec<String>,
+    },
+
+    /// `[?(expr)]`, matches the elements of an array for which the filter expression `expr`
+    /// holds, see the `filter` module
+    Filter {
+        expr: FilterExpr,
     }
 }
 
-impl Token {
+impl<'a> Token<'a> {
 
-    pub fn next(&self) -> Option<&Box<Token>> {
-        trace!("Matching token (self): {:?}", self);
+    /// This token, without borrowing from the query
+    pub fn into_owned(self) -> Token<'static> {
         match self {
-            &Token::Identifier { ref next, .. } => next.as_ref(),
-            &Token::Index { ref next, .. }      => next.as_ref(),
+            Token::Identifier { ident }    => Token::Identifier { ident: Cow::Owned(ident.into_owned()) },
+            Token::Index { idx, from_end } => Token::Index { idx, from_end },
+            Token::Wildcard                => Token::Wildcard,
+            Token::Union { keys }          => Token::Union { keys },
+            Token::Filter { expr }         => Token::Filter { expr },
         }
     }
 
-    /// Convenience function for `token.next().is_some()`
-    pub fn has_next(&self) -> bool {
-        trace!("self.has_next(): {:?}", self.next().is_some());
-        self.next().is_some()
+    #[cfg(test)]
+    pub fn identifier(&self) -> &str {
+        trace!("self.identifier()");
+        match self {
+            &Token::Identifier { ref ident, .. } => &ident[..],
+            _ => unreachable!(),
+        }
     }
 
-    pub fn set_next(&mut self, token: Token) {
-        trace!("self.set_next({:?})", token);
+    #[cfg(test)]
+    pub fn idx(&self) -> usize {
+        trace!("self.idx()");
         match self {
-            &mut Token::Identifier { ref mut next, .. } => *next = Some(Box::new(token)),
-            &mut Token::Index { ref mut next, .. }      => *next = Some(Box::new(token)),
+            &Token::Index { idx: i, .. } => i,
+            _ => unreachable!(),
         }
     }
 
-    /// Pop the last token from the chain of tokens
-    ///
-    /// Returns None if the current Token has no next token
-    pub fn pop_last(&mut self) -> Option<Box<Token>> {
-        trace!("self.pop_last()");
-        if !self.has_next() {
-            trace!("self.pop_last(): No next");
-            None
-        } else {
-            trace!("self.pop_last(): Having next");
-            match self {
-                &mut Token::Identifier { ref mut next, .. } => {
-                    trace!("self.pop_last(): self is Identifier");
-                    if next.is_some() {
-                        trace!("self.pop_last(): next is Some(_)");
-                        let mut n = next.take().unwrap();
-                        if n.has_next() {
-                            trace!("self.pop_last(): next also has a next");
-
-                            trace!("self.pop_last(): Recursing now");
-                            let result = n.pop_last();
-
-                            *next = Some(n);
-
-                            trace!("self.pop_last(): Returning Result");
-                            return result;
-                        } else {
-                            trace!("self.pop_last(): next itself has no next, returning Some");
-                            Some(n)
-                        }
-                    } else {
-                        trace!("self.pop_last(): next is none, returning None");
-                        None
-                    }
-                },
+}
 
-                &mut Token::Index { ref mut next, .. } => {
-                    trace!("self.pop_last(): self is Index");
-                    if next.is_some() {
-                        trace!("self.pop_last(): next is Some(_)");
+/// The expression of a `Filter` token
+///
+/// With the `value` feature, the expression is parsed by the tokenizer, so a query with an
+/// invalid filter fails to tokenize and the resolvers do not parse it again for every array.
+/// Two expressions are equal if they are written the same.
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    source: String,
+    #[cfg(feature = "value")]
+    parsed: ::filter::Expr,
+}
 
-                        let mut n = next.take().unwrap();
-                        if n.has_next() {
-                            trace!("self.pop_last(): next also has a next");
+impl FilterExpr {
 
-                            trace!("self.pop_last(): Recursing now");
-                            let result = n.pop_last();
+    #[cfg(feature = "value")]
+    fn parse(source: String) -> Result<FilterExpr> {
+        let parsed = ::filter::Expr::parse(&source)?;
+        Ok(FilterExpr { source, parsed })
+    }
 
-                            *next = Some(n);
+    #[cfg(not(feature = "value"))]
+    fn parse(source: String) -> Result<FilterExpr> {
+        Ok(FilterExpr { source })
+    }
 
-                            trace!("self.pop_last(): Returning Result");
-                            return result;
-                        } else {
-                            trace!("self.pop_last(): next itself has no next, returning Some");
-                            Some(n)
-                        }
-                    } else {
-                        trace!("self.pop_last(): next is none, returning None");
-                        None
-                    }
+    /// The expression as it is written in the query, without the surrounding `[?(` and `)]`
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The parsed expression
+    #[cfg(feature = "value")]
+    pub fn expr(&self) -> &::filter::Expr {
+        &self.parsed
+    }
+
+}
+
+impl PartialEq for FilterExpr {
+    fn eq(&self, other: &FilterExpr) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for FilterExpr {}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The token as a segment of a query
+///
+/// Keys which would not be read back as the same key are written in double quotes, like in the
+/// `Display` implementation of `TomlPath`. Indices counted from the end are written as `[-n]`, and
+/// the index for appending as `[+]`.
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Identifier { ref ident }         => write_key(f, ident),
+            Token::Index { idx, from_end: false }   => write!(f, "[{}]", idx),
+            Token::Index { idx: 0, from_end: true } => write!(f, "[+]"),
+            Token::Index { idx, from_end: true }    => write!(f, "[-{}]", idx),
+            Token::Wildcard                         => write!(f, "*"),
+            Token::Union { ref keys }               => write!(f, "{{{}}}", keys.join(",")),
+            Token::Filter { ref expr }              => write!(f, "[?({})]", expr),
+        }
+    }
+}
+
+/// The tokens of a query, one for every segment
+///
+/// Dereferences to a slice of tokens, so the tokens can be counted, iterated and sliced. The
+/// resolvers walk a document with the slice of the tokens which are not resolved yet.
+/// A `Tokens` returned by the tokenizer is never empty.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Tokens<'a>(Vec<Token<'a>>);
+
+impl<'a> Tokens<'a> {
+
+    pub fn new() -> Tokens<'a> {
+        Tokens(Vec::new())
+    }
+
+    pub fn push(&mut self, token: Token<'a>) {
+        self.0.push(token)
+    }
+
+    /// Remove the last token, returns None if there are no tokens
+    pub fn pop(&mut self) -> Option<Token<'a>> {
+        self.0.pop()
+    }
+
+    /// The tokens, without borrowing from the query
+    pub fn into_owned(self) -> Tokens<'static> {
+        self.0.into_iter().map(Token::into_owned).collect()
+    }
+
+    pub fn into_vec(self) -> Vec<Token<'a>> {
+        self.0
+    }
+
+}
+
+impl<'a> Deref for Tokens<'a> {
+    type Target = [Token<'a>];
+
+    fn deref(&self) -> &[Token<'a>] {
+        &self.0
+    }
+}
+
+impl<'a> FromIterator<Token<'a>> for Tokens<'a> {
+    fn from_iter<I: IntoIterator<Item = Token<'a>>>(iter: I) -> Tokens<'a> {
+        Tokens(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for Tokens<'a> {
+    type Item     = Token<'a>;
+    type IntoIter = ::std::vec::IntoIter<Token<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b Tokens<'a> {
+    type Item     = &'b Token<'a>;
+    type IntoIter = ::std::slice::Iter<'b, Token<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// The tokens as a query with `.` as seperator
+impl<'a> fmt::Display for Tokens<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, token) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", token)?;
+        }
+        Ok(())
+    }
+}
+
+/// A segment of a query, before it is turned into a `Token`
+#[derive(Debug)]
+pub(crate) enum Part<'a> {
+    Plain(Cow<'a, str>),
+
+    /// A segment in quotes, which is always an identifier
+    Quoted(Cow<'a, str>),
+}
+
+/// Split `query` at `seperator`, except inside quoted segments
+///
+/// A segment which starts with `"` or `'` is quoted and ends at the matching quote, which must be
+/// followed by the seperator or the end of the query. Inside double quotes, a backslash escapes
+/// the next character. Single quotes are literal.
+///
+/// Outside of quotes, `\` followed by the seperator or by another `\` stands for that character.
+/// Other backslashes are part of the identifier.
+///
+/// A segment which starts with `[?` is a filter and ends at the matching `]`. Seperators within
+/// it, like in `[?(@.a == 1)]`, do not split the query.
+///
+/// A quoted key in brackets, like `["b.c"]` in `a["b.c"]`, is a segment of its own, which does not
+/// need a seperator before it. Quoted segments and filters may be followed by a bracketed segment
+/// without a seperator as well, so `a["b"][0]` is the same as `a."b".[0]`.
+///
+/// Every part comes with the range of bytes of `query` it was read from.
+pub(crate) fn split_query<'a>(query: &'a str, seperator: char) -> Result<Vec<(Part<'a>, Range<usize>)>> {
+    let failed    = || Error::QueryParsingError(String::from(query));
+    let mut parts = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    /// Read a quoted key up to the closing `quote`, the opening quote is at `begin - 1`
+    fn read_quoted<'a>(query: &'a str, chars: &mut Peekable<CharIndices<'a>>, quote: char, begin: usize)
+        -> Option<Cow<'a, str>>
+    {
+        let mut owned : Option<String> = None;
+        loop {
+            match chars.next()? {
+                (end, c) if c == quote => {
+                    return Some(owned.map(Cow::Owned).unwrap_or(Cow::Borrowed(&query[begin..end])))
+                },
+                (pos, '\\') if quote == '"' => {
+                    let (_, escaped) = chars.next()?;
+                    owned.get_or_insert_with(|| String::from(&query[begin..pos])).push(escaped);
+                },
+                (_, c) => if let Some(ref mut key) = owned {
+                    key.push(c);
                 },
             }
         }
     }
 
-    #[cfg(test)]
-    pub fn identifier(&self) -> &String {
-        trace!("self.identifier()");
-        match self {
-            &Token::Identifier { ref ident, .. } => &ident,
-            _ => unreachable!(),
+    /// Whether the next characters are `["` or `['`
+    fn at_bracketed_key(chars: &Peekable<CharIndices>) -> bool {
+        let mut ahead = chars.clone();
+        if let (Some((_, '[')), Some((_, quote))) = (ahead.next(), ahead.next()) {
+            quote == '"' || quote == '\''
+        } else {
+            false
         }
     }
 
-    #[cfg(test)]
-    pub fn idx(&self) -> usize {
-        trace!("self.idx()");
-        match self {
-            &Token::Index { idx: i, .. } => i,
-            _ => unreachable!(),
+    // Parts borrow from the query, unless escapes make them differ from it
+    loop {
+        let part_start = chars.peek().map(|&(i, _)| i).unwrap_or_else(|| query.len());
+        let part = match chars.peek().cloned() {
+            Some((start, quote)) if quote == '"' || quote == '\'' => {
+                let _ = chars.next();
+                Part::Quoted(read_quoted(query, &mut chars, quote, start + 1).ok_or_else(failed)?)
+            },
+            Some((_, '[')) if at_bracketed_key(&chars) => {
+                let _ = chars.next();
+                let (start, quote) = chars.next().ok_or_else(failed)?;
+                let key = read_quoted(query, &mut chars, quote, start + 1).ok_or_else(failed)?;
+                match chars.next() {
+                    Some((_, ']')) => Part::Quoted(key),
+                    _              => return Err(failed()),
+                }
+            },
+            Some((start, '[')) if chars.clone().nth(1).map(|(_, c)| c) == Some('?') => {
+                let mut depth  = 0;
+                let mut quote  = None;
+                let end = loop {
+                    let (pos, c) = chars.next().ok_or_else(failed)?;
+                    match (quote, c) {
+                        (Some(_), '\\')            => { let _ = chars.next().ok_or_else(failed)?; },
+                        (Some(q), c) if c == q     => quote = None,
+                        (Some(_), _)               => { },
+                        (None, '"') | (None, '\'') => quote = Some(c),
+                        (None, '[') | (None, '(')  => depth += 1,
+                        (None, ']') | (None, ')')  => depth -= 1,
+                        (None, _)                  => { },
+                    }
+                    if depth == 0 {
+                        break pos + c.len_utf8();
+                    }
+                };
+                Part::Plain(Cow::Borrowed(&query[start..end]))
+            },
+            next => {
+                let begin   = next.map(|(i, _)| i).unwrap_or_else(|| query.len());
+                let mut end = begin;
+                let mut owned : Option<String> = None;
+                while let Some((pos, c)) = chars.peek().cloned() {
+                    if c == seperator || at_bracketed_key(&chars) {
+                        break;
+                    }
+                    let _ = chars.next();
+
+                    // `\.` and `\\` stand for the seperator and the backslash
+                    match chars.peek().cloned() {
+                        Some((_, next)) if c == '\\' && (next == seperator || next == '\\') => {
+                            owned.get_or_insert_with(|| String::from(&query[begin..pos])).push(next);
+                            let _ = chars.next();
+                        },
+                        _ => if let Some(ref mut plain) = owned {
+                            plain.push(c);
+                        },
+                    }
+                    end = chars.peek().map(|&(i, _)| i).unwrap_or_else(|| query.len());
+                }
+                Part::Plain(owned.map(Cow::Owned).unwrap_or(Cow::Borrowed(&query[begin..end])))
+            },
+        };
+        let part_end = chars.peek().map(|&(i, _)| i).unwrap_or_else(|| query.len());
+        parts.push((part, part_start..part_end));
+
+        // A bracketed segment may follow without a seperator
+        if chars.peek().map(|&(_, c)| c) == Some('[') {
+            continue;
+        }
+
+        match chars.next() {
+            None                           => return Ok(parts),
+            Some((_, c)) if c == seperator => { },
+            Some(_)                        => return Err(failed()),
         }
     }
+}
+
+/// The maximum number of segments of a query parsed with `tokenize_with_seperator()`
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// The grammar of queries
+///
+/// `V1Basic` is the grammar of the first releases: keys and array indices like `[0]`, separated
+/// by the seperator. `V2Extended` adds quoted keys, escaped seperators, indices counted from the
+/// end, wildcards, unions, filters and function calls. Applications which pass user input as
+/// queries can stick to `V1Basic`, so that queries do not change their meaning when the grammar
+/// grows. Under `V1Basic`, extended syntax fails with `Error::ExtendedSyntax`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    V1Basic,
+    V2Extended,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Syntax::V2Extended
+    }
+}
+
+pub fn tokenize_with_seperator<'a>(query: &'a str, seperator: char) -> Result<Tokens<'a>> {
+    tokenize(query, seperator, DEFAULT_MAX_DEPTH, Syntax::V2Extended)
+}
+
+/// Tokenize `query`, failing with `Error::QueryTooDeep` if it has more than `max_depth` segments
+///
+/// Tokenizing a query and resolving it to a single value do not recurse per segment, but the
+/// resolvers for wildcards, unions and filters do. Queries from untrusted input should be limited.
+pub fn tokenize_with_max_depth<'a>(query: &'a str, seperator: char, max_depth: usize) -> Result<Tokens<'a>> {
+    tokenize(query, seperator, max_depth, Syntax::V2Extended)
+}
+
+/// Tokenize `query` with the grammar `syntax`
+pub fn tokenize_with_syntax<'a>(query: &'a str, seperator: char, syntax: Syntax) -> Result<Tokens<'a>> {
+    tokenize(query, seperator, DEFAULT_MAX_DEPTH, syntax)
+}
 
+/// Tokenize `query`, returning the range of bytes of `query` of every token as well
+pub fn tokenize_with_spans<'a>(query: &'a str, seperator: char) -> Result<(Tokens<'a>, Vec<Range<usize>>)> {
+    tokenize_spanned(query, seperator, DEFAULT_MAX_DEPTH, Syntax::V2Extended)
 }
 
-pub fn tokenize_with_seperator(query: &str, seperator: char) -> Result<Token> {
-    use std::str::Split;
-    trace!("tokenize_with_seperator(query: {:?}, seperator: {:?})", query, seperator);
+fn tokenize<'a>(query: &'a str, seperator: char, max_depth: usize, syntax: Syntax) -> Result<Tokens<'a>> {
+    tokenize_spanned(query, seperator, max_depth, syntax).map(|(tokens, _)| tokens)
+}
+
+fn tokenize_spanned<'a>(query: &'a str, seperator: char, max_depth: usize, syntax: Syntax)
+    -> Result<(Tokens<'a>, Vec<Range<usize>>)>
+{
+    trace!("tokenize(query: {:?}, seperator: {:?}, max_depth: {}, syntax: {:?})", query, seperator, max_depth, syntax);
 
     /// Creates a Token object from a string
     ///
@@ -135,7 +431,6 @@ pub fn tokenize_with_seperator(query: &str, seperator: char) -> Result<Token> {
     /// * If the internal regex does not compile (should never happen)
     /// * If the token is non-valid (that is, a array index with a non-i64)
     /// * If the regex does not find anything
-    /// * If the integer in the brackets (`[]`) cannot be parsed to a valid i64
     ///
     /// # Incorrect behaviour
     ///
@@ -143,41 +438,84 @@ pub fn tokenize_with_seperator(query: &str, seperator: char) -> Result<Token> {
     ///
     /// # Returns
     ///
-    /// The `Token` object with the correct identifier/index for this token and no next token.
+    /// The `Token` object with the correct identifier/index for this token.
     ///
-    fn mk_token_object(s: &str) -> Result<Token> {
+    fn mk_token_object<'a>(s: Cow<'a, str>) -> Result<Token<'a>> {
         use regex::Regex;
         use std::str::FromStr;
 
         trace!("mk_token_object(s: {:?})", s);
 
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"^\[\d+\]$").unwrap();
+            static ref RE: Regex = Regex::new(r"^\[-?\d+\]$").unwrap();
+        }
+
+        if s == "*" {
+            trace!("returning Ok(Wildcard)");
+            return Ok(Token::Wildcard);
+        }
+
+        match &*s {
+            "[last]" => return Ok(Token::Index { idx: 1, from_end: true }),
+            "[+]"    => return Ok(Token::Index { idx: 0, from_end: true }),
+            _        => { },
+        }
+
+        if s.starts_with("[?(") && s.ends_with(")]") {
+            let expr = FilterExpr::parse(String::from(s[3..s.len() - 2].trim()))?;
+            trace!("returning Ok(Filter(expr: {:?}))", expr);
+            return Ok(Token::Filter { expr });
         }
 
-        if !has_array_brackets(s) {
-            trace!("returning Ok(Identifier(ident: {:?}, next: None))", s);
-            return Ok(Token::Identifier { ident: String::from(s), next: None });
+        if s.starts_with('{') && s.ends_with('}') {
+            let mut keys : Vec<String> = Vec::new();
+            for key in s[1..s.len() - 1].split(',').map(str::trim) {
+                if key.is_empty() {
+                    return Err(Error::EmptyIdentifier);
+                }
+                if keys.iter().any(|k| k == key) {
+                    return Err(Error::QueryParsingError(String::from(s)));
+                }
+                keys.push(String::from(key));
+            }
+            trace!("returning Ok(Union(keys: {:?}))", keys);
+            return Ok(Token::Union { keys });
+        }
+
+        if !has_array_brackets(&s) {
+            trace!("returning Ok(Identifier(ident: {:?}))", s);
+            return Ok(Token::Identifier { ident: s });
         }
 
-        match RE.captures(s) {
+        match RE.captures(&s) {
             None => return Err(Error::ArrayAccessWithoutIndex),
             Some(captures) => {
                 trace!("Captured: {:?}", captures);
                 match captures.get(0) {
-                    None => Ok(Token::Identifier { ident: String::from(s), next: None }),
+                    None => Ok(Token::Identifier { ident: s.clone() }),
                     Some(mtch) => {
                         trace!("First capture: {:?}", mtch);
 
                         let mtch = without_array_brackets(mtch.as_str());
                         trace!(".. without array brackets: {:?}", mtch);
 
-                        let i : usize = FromStr::from_str(&mtch).unwrap(); // save because regex
+                        let (from_end, mtch) = if mtch.starts_with('-') {
+                            (true, &mtch[1..])
+                        } else {
+                            (false, &mtch[..])
+                        };
+
+                        let i : usize = FromStr::from_str(mtch)
+                            .map_err(|_| Error::ArrayAccessWithInvalidIndex)?; // only on overflow
 
-                        trace!("returning Ok(Index(idx: {}, next: None)", i);
+                        if from_end && i == 0 {
+                            return Err(Error::ArrayAccessWithInvalidIndex);
+                        }
+
+                        trace!("returning Ok(Index(idx: {}, from_end: {})", i, from_end);
                         Ok(Token::Index {
                             idx: i,
-                            next: None,
+                            from_end,
                         })
                     }
                 }
@@ -197,26 +535,33 @@ pub fn tokenize_with_seperator(query: &str, seperator: char) -> Result<Token> {
         s.replace("[","").replace("]","")
     }
 
-    fn build_token_tree(split: &mut Split<char>, last: &mut Token) -> Result<()> {
-        trace!("build_token_tree(split: {:?}, last: {:?})", split, last);
-        match split.next() {
-            None        => { /* No more tokens */ }
-            Some(token) => {
-                trace!("build_token_tree(...): next from split: {:?}", token);
+    /// Creates a Token object from a part of the query
+    fn mk_token_from_part<'a>(part: Part<'a>) -> Result<Token<'a>> {
+        match part {
+            Part::Quoted(ident) => Ok(Token::Identifier { ident }),
+            Part::Plain(ref s) if s.is_empty() => {
+                trace!("Empty identifier... returning Error");
+                Err(Error::EmptyIdentifier)
+            },
+            Part::Plain(s) => mk_token_object(s),
+        }
+    }
 
-                if token.len() == 0 {
-                    trace!("build_token_tree(...): Empty identifier... returning Error");
-                    return Err(Error::EmptyIdentifier)
-                }
+    /// Creates a Token object from a part of the query, which must be in the basic syntax
+    fn mk_basic_token_from_part<'a>(part: Part<'a>) -> Result<Token<'a>> {
+        // Owned plain parts contain escapes
+        let extended_part = match part {
+            Part::Quoted(_)               => true,
+            Part::Plain(Cow::Owned(_))    => true,
+            Part::Plain(Cow::Borrowed(_)) => false,
+        };
 
-                let mut token = try!(mk_token_object(token));
-                try!(build_token_tree(split, &mut token));
-                last.set_next(token);
-            }
+        let token = mk_token_from_part(part)?;
+        match token {
+            Token::Identifier { .. } |
+            Token::Index { from_end: false, .. } if !extended_part => Ok(token),
+            _ => Err(Error::ExtendedSyntax(token.to_string())),
         }
-
-        trace!("build_token_tree(...): returning Ok(())");
-        Ok(())
     }
 
     if query.is_empty() {
@@ -224,26 +569,20 @@ pub fn tokenize_with_seperator(query: &str, seperator: char) -> Result<Token> {
         return Err(Error::EmptyQueryError)
     }
 
-    let mut tokens = query.split(seperator);
-    trace!("Tokens splitted: {:?}", tokens);
-
-    match tokens.next() {
-        None        => Err(Error::EmptyQueryError),
-        Some(token) => {
-            trace!("next Token: {:?}", token);
-
-            if token.len() == 0 {
-                trace!("Empty token. Returning Error");
-                return Err(Error::EmptyIdentifier);
-            }
+    let parts = split_query(query, seperator)?;
+    trace!("Tokens splitted: {:?}", parts);
 
-            let mut tok = try!(mk_token_object(token));
-            let _       = try!(build_token_tree(&mut tokens, &mut tok));
-
-            trace!("Returning Ok({:?})", tok);
-            Ok(tok)
-        }
+    if parts.len() > max_depth {
+        return Err(Error::QueryTooDeep(parts.len(), max_depth));
     }
+
+    let (parts, spans) : (Vec<_>, Vec<_>) = parts.into_iter().unzip();
+    let tokens = match syntax {
+        Syntax::V1Basic    => parts.into_iter().map(mk_basic_token_from_part).collect::<Result<Tokens>>()?,
+        Syntax::V2Extended => parts.into_iter().map(mk_token_from_part).collect::<Result<Tokens>>()?,
+    };
+    trace!("Returning Ok({:?})", tokens);
+    Ok((tokens, spans))
 }
 
 #[cfg(test)]
@@ -251,11 +590,9 @@ mod test {
     use error::Error;
     use super::*;
 
-    use std::ops::Deref;
-
     #[test]
     fn test_tokenize_empty_query_to_error() {
-        let tokens = tokenize_with_seperator(&String::from(""), '.');
+        let tokens = tokenize_with_seperator("", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
@@ -264,7 +601,7 @@ mod test {
 
     #[test]
     fn test_tokenize_seperator_only() {
-        let tokens = tokenize_with_seperator(&String::from("."), '.');
+        let tokens = tokenize_with_seperator(".", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
@@ -273,7 +610,7 @@ mod test {
 
     #[test]
     fn test_tokenize_array_brackets_only() {
-        let tokens = tokenize_with_seperator(&String::from("[]"), '.');
+        let tokens = tokenize_with_seperator("[]", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
@@ -282,7 +619,7 @@ mod test {
 
     #[test]
     fn test_tokenize_identifiers_with_array_brackets_only() {
-        let tokens = tokenize_with_seperator(&String::from("a.b.c.[]"), '.');
+        let tokens = tokenize_with_seperator("a.b.c.[]", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
@@ -291,7 +628,7 @@ mod test {
 
     #[test]
     fn test_tokenize_identifiers_in_array_brackets() {
-        let tokens = tokenize_with_seperator(&String::from("[a]"), '.');
+        let tokens = tokenize_with_seperator("[a]", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
@@ -300,12 +637,13 @@ mod test {
 
     #[test]
     fn test_tokenize_single_token_query() {
-        let tokens = tokenize_with_seperator(&String::from("example"), '.');
+        let tokens = tokenize_with_seperator("example", '.');
         assert!(tokens.is_ok());
         let tokens = tokens.unwrap();
 
-        assert!(match tokens {
-            Token::Identifier { ref ident, next: None } => {
+        assert_eq!(tokens.len(), 1);
+        assert!(match tokens[0] {
+            Token::Identifier { ref ident } => {
                 assert_eq!("example", ident);
                 true
             },
@@ -315,235 +653,402 @@ mod test {
 
     #[test]
     fn test_tokenize_double_token_query() {
-        let tokens = tokenize_with_seperator(&String::from("a.b"), '.');
+        let tokens = tokenize_with_seperator("a.b", '.');
         assert!(tokens.is_ok());
         let tokens = tokens.unwrap();
 
-        assert!(match tokens {
-            Token::Identifier { next: Some(ref next), .. } => { 
-                assert_eq!("b", next.deref().identifier());
-                match next.deref() {
-                    &Token::Identifier { next: None, .. } => true,
-                    _ => false
-                }
-            },
-            _ => false,
-        });
-        assert_eq!("a", tokens.identifier());
+        assert_eq!(tokens.len(), 2);
+        assert_eq!("a", tokens[0].identifier());
+        assert_eq!("b", tokens[1].identifier());
     }
 
     #[test]
     fn test_tokenize_ident_then_array_query() {
-        let tokens = tokenize_with_seperator(&String::from("a.[0]"), '.');
+        let tokens = tokenize_with_seperator("a.[0]", '.');
         assert!(tokens.is_ok());
         let tokens = tokens.unwrap();
 
-        assert_eq!("a", tokens.identifier());
-        assert!(match tokens {
-            Token::Identifier { next: Some(ref next), .. } => {
-                match next.deref() {
-                    &Token::Index { idx: 0, next: None } => true,
-                    _ => false
-                }
-            },
-            _ => false,
-        });
+        assert_eq!(tokens.len(), 2);
+        assert_eq!("a", tokens[0].identifier());
+        assert!(is_match!(tokens[1], Token::Index { idx: 0, from_end: false }));
     }
 
     #[test]
     fn test_tokenize_many_idents_then_array_query() {
-        let tokens = tokenize_with_seperator(&String::from("a.b.c.[1000]"), '.');
+        let tokens = tokenize_with_seperator("a.b.c.[1000]", '.');
         assert!(tokens.is_ok());
         let tokens = tokens.unwrap();
 
-        assert_eq!("a", tokens.identifier());
+        assert_eq!("a", tokens[0].identifier());
 
-        let expected =
-            Token::Identifier {
-                ident: String::from("a"),
-                next: Some(Box::new(Token::Identifier {
-                    ident: String::from("b"),
-                    next: Some(Box::new(Token::Identifier {
-                        ident: String::from("c"),
-                        next: Some(Box::new(Token::Index {
-                            idx: 1000,
-                            next: None,
-                        })),
-                    })),
-                })),
-            };
+        let expected = vec![
+            Token::Identifier { ident: Cow::Borrowed("a") },
+            Token::Identifier { ident: Cow::Borrowed("b") },
+            Token::Identifier { ident: Cow::Borrowed("c") },
+            Token::Index { idx: 1000, from_end: false },
+        ];
 
-        assert_eq!(expected, tokens);
+        assert_eq!(expected, tokens.into_vec());
     }
 
     #[test]
     fn test_tokenize_empty_token_after_good_token() {
-        let tokens = tokenize_with_seperator(&String::from("a..b"), '.');
+        let tokens = tokenize_with_seperator("a..b", '.');
         assert!(tokens.is_err());
         let tokens = tokens.unwrap_err();
 
         assert!(is_match!(tokens, Error::EmptyIdentifier { .. }));
     }
 
-    quickcheck! {
-        fn test_array_index(i: usize) -> bool {
-            match tokenize_with_seperator(&format!("[{}]", i), '.') {
-                Ok(Token::Index { next: None, ..  }) => true,
-                _                                    => false,
-            }
-        }
+    #[test]
+    fn test_tokenize_overflowing_index() {
+        let tokens = tokenize_with_seperator("a.[99999999999999999999999]", '.');
+        assert!(is_match!(tokens, Err(Error::ArrayAccessWithInvalidIndex)));
     }
 
     #[test]
-    fn test_pop_last_token_from_single_identifier_token_is_none() {
-        let mut token = Token::Identifier {
-            ident: String::from("something"),
-            next: None,
-        };
+    fn test_tokenize_index_from_end() {
+        let tokens = tokenize_with_seperator("a.[-2]", '.').unwrap();
+        assert!(is_match!(tokens[1], Token::Index { idx: 2, from_end: true }));
 
-        let last = token.pop_last();
-        assert!(last.is_none());
+        let tokens = tokenize_with_seperator("a.[-0]", '.');
+        assert!(is_match!(tokens, Err(Error::ArrayAccessWithInvalidIndex)));
+
+        let tokens = tokenize_with_seperator("a.[--1]", '.');
+        assert!(is_match!(tokens, Err(Error::ArrayAccessWithoutIndex)));
     }
 
     #[test]
-    fn test_pop_last_token_from_single_index_token_is_none() {
-        let mut token = Token::Index {
-            idx: 0,
-            next: None,
-        };
+    fn test_tokenize_pseudo_indices() {
+        let tokens = tokenize_with_seperator("a.[last]", '.').unwrap();
+        assert!(is_match!(tokens[1], Token::Index { idx: 1, from_end: true }));
 
-        let last = token.pop_last();
-        assert!(last.is_none());
+        let tokens = tokenize_with_seperator("a.[+]", '.').unwrap();
+        assert!(is_match!(tokens[1], Token::Index { idx: 0, from_end: true }));
+
+        let tokens = tokenize_with_seperator("a.[first]", '.');
+        assert!(is_match!(tokens, Err(Error::ArrayAccessWithoutIndex)));
     }
 
     #[test]
-    fn test_pop_last_token_from_single_identifier_token_is_one() {
-        let mut token = Token::Identifier {
-            ident: String::from("some"),
-            next: Some(Box::new(Token::Identifier {
-                ident: String::from("thing"),
-                next: None,
-            })),
-        };
+    fn test_tokenize_wildcard() {
+        let tokens = tokenize_with_seperator("a.*.b", '.').unwrap();
+        assert!(is_match!(tokens[1], Token::Wildcard));
+        assert_eq!(tokens.len(), 3);
 
-        let last = token.pop_last();
-
-        assert!(last.is_some());
-        let last = last.unwrap();
+        let tokens = tokenize_with_seperator(r#"a."*""#, '.').unwrap();
+        assert_eq!("*", tokens[1].identifier());
+    }
 
-        assert!(is_match!(*last, Token::Identifier { .. }));
-        match *last {
-            Token::Identifier { ident, .. } => {
-                assert_eq!("thing", ident);
-            }
-            _ => panic!("What just happened?"),
+    #[test]
+    fn test_tokenize_union() {
+        let tokens = tokenize_with_seperator("package.{name, version}", '.').unwrap();
+        match tokens[1] {
+            Token::Union { ref keys } => assert_eq!(keys, &["name", "version"]),
+            ref other                 => panic!("Unexpected token: {:?}", other),
         }
+
+        let tokens = tokenize_with_seperator("package.{name,}", '.');
+        assert!(is_match!(tokens, Err(Error::EmptyIdentifier)));
+
+        let tokens = tokenize_with_seperator("package.{a,a}", '.');
+        assert!(is_match!(tokens, Err(Error::QueryParsingError(_))));
     }
 
     #[test]
-    fn test_pop_last_token_from_single_index_token_is_one() {
-        let mut token = Token::Index {
-            idx: 0,
-            next: Some(Box::new(Token::Index {
-                idx: 1,
-                next: None,
-            })),
-        };
-
-        let last = token.pop_last();
+    fn test_tokenize_filter() {
+        let tokens = tokenize_with_seperator(r#"a.[?(@.b.[0] == "x.]")].c"#, '.').unwrap();
+        match tokens[1] {
+            Token::Filter { ref expr } => assert_eq!(expr.as_str(), r#"@.b.[0] == "x.]""#),
+            ref other                  => panic!("Unexpected token: {:?}", other),
+        }
+        assert_eq!("c", tokens[2].identifier());
 
-        assert!(last.is_some());
-        let last = last.unwrap();
+        let tokens = tokenize_with_seperator("a.[?(@.b == 1)", '.');
+        assert!(is_match!(tokens, Err(Error::QueryParsingError(_))));
+    }
 
-        assert!(is_match!(*last, Token::Index { idx: 1, .. }));
+    quickcheck! {
+        fn test_array_index(i: usize) -> bool {
+            match tokenize_with_seperator(&format!("[{}]", i), '.') {
+                Ok(ref tokens) if tokens.len() == 1 => is_match!(tokens[0], Token::Index { .. }),
+                _                                   => false,
+            }
+        }
     }
 
     #[test]
-    fn test_pop_last_token_from_identifier_chain() {
-        let tokens = tokenize_with_seperator(&String::from("a.b.c.d.e.f"), '.');
+    fn test_pop_token_from_identifier_chain() {
+        let tokens = tokenize_with_seperator("a.b.c.d.e.f", '.');
         assert!(tokens.is_ok());
         let mut tokens = tokens.unwrap();
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!("f", last.unwrap().identifier());
     }
 
     #[test]
-    fn test_pop_last_token_from_mixed_chain() {
-        let tokens = tokenize_with_seperator(&String::from("a.[100].c.[3].e.f"), '.');
+    fn test_pop_token_from_mixed_chain() {
+        let tokens = tokenize_with_seperator("a.[100].c.[3].e.f", '.');
         assert!(tokens.is_ok());
         let mut tokens = tokens.unwrap();
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!("f", last.unwrap().identifier());
     }
 
     #[test]
-    fn test_pop_last_token_from_identifier_chain_is_array() {
-        let tokens = tokenize_with_seperator(&String::from("a.b.c.d.e.f.[1000]"), '.');
+    fn test_tokenize_quoted_identifiers() {
+        let tokens = tokenize_with_seperator(r#"dependencies."serde.derive".'[0]'.[1]"#, '.').unwrap();
+        assert_eq!(tokens[0].identifier(), "dependencies");
+        assert_eq!(tokens[1].identifier(), "serde.derive");
+        assert_eq!(tokens[2].identifier(), "[0]");
+        assert_eq!(tokens[3].idx(), 1);
+
+        let tokens = tokenize_with_seperator(r#""a \"b\" \\"/'c"d'/"""#, '/').unwrap();
+        assert_eq!(tokens[0].identifier(), r#"a "b" \"#);
+        assert_eq!(tokens[1].identifier(), r#"c"d"#);
+        assert_eq!(tokens[2].identifier(), "");
+    }
+
+    #[test]
+    fn test_tokenize_escaped_seperator() {
+        let tokens = tokenize_with_seperator(r"a\.b.c\\.d\e\\\.", '.').unwrap();
+        assert_eq!(tokens[0].identifier(), "a.b");
+        assert_eq!(tokens[1].identifier(), r"c\");
+        assert_eq!(tokens[2].identifier(), r"d\e\.");
+
+        let tokens = tokenize_with_seperator(r"a\/b/c\.d", '/').unwrap();
+        assert_eq!(tokens[0].identifier(), "a/b");
+        assert_eq!(tokens[1].identifier(), r"c\.d");
+    }
+
+    #[test]
+    fn test_tokenize_invalid_quotes() {
+        for query in &[r#""a"#, "'a", r#""a"b"#, r#"a."b"c.d"#, r#""a\""#] {
+            let res = tokenize_with_seperator(query, '.');
+            assert!(is_match!(res, Err(Error::QueryParsingError(_))), "{}", query);
+        }
+
+        // quotes which do not start a segment are part of the identifier
+        let tokens = tokenize_with_seperator(r#"a"b"#, '.').unwrap();
+        assert_eq!(tokens[0].identifier(), r#"a"b"#);
+    }
+
+    #[test]
+    fn test_pop_token_from_identifier_chain_is_array() {
+        let tokens = tokenize_with_seperator("a.b.c.d.e.f.[1000]", '.');
         assert!(tokens.is_ok());
         let mut tokens = tokens.unwrap();
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!(1000, last.unwrap().idx());
     }
 
     #[test]
-    fn test_pop_last_token_from_mixed_chain_is_array() {
-        let tokens = tokenize_with_seperator(&String::from("a.[100].c.[3].e.f.[1000]"), '.');
+    fn test_pop_token_from_mixed_chain_is_array() {
+        let tokens = tokenize_with_seperator("a.[100].c.[3].e.f.[1000]", '.');
         assert!(tokens.is_ok());
         let mut tokens = tokens.unwrap();
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!(1000, last.unwrap().idx());
     }
 
     #[test]
-    fn test_pop_last_token_from_one_token() {
-        let tokens = tokenize_with_seperator(&String::from("a"), '.');
+    fn test_pop_token_from_one_token() {
+        let tokens = tokenize_with_seperator("a", '.');
         assert!(tokens.is_ok());
         let mut tokens = tokens.unwrap();
 
-        let last = tokens.pop_last();
-        assert!(last.is_none());
+        let last = tokens.pop();
+        assert_eq!("a", last.unwrap().identifier());
+        assert!(tokens.is_empty());
+        assert!(tokens.pop().is_none());
+    }
+
+    #[test]
+    fn test_tokenize_borrows_from_query() {
+        fn idents<'a>(tokens: &Tokens<'a>) -> Vec<Cow<'a, str>> {
+            tokens.iter()
+                .filter_map(|token| match *token {
+                    Token::Identifier { ref ident } => Some(ident.clone()),
+                    _                               => None,
+                })
+                .collect()
+        }
+
+        let tokens = tokenize_with_seperator(r#"a."b.c".d\.e"#, '.').unwrap();
+
+        let found = idents(&tokens);
+        assert_eq!(found, vec!["a", "b.c", "d.e"]);
+        assert!(is_match!(found[0], Cow::Borrowed(_)));
+        assert!(is_match!(found[1], Cow::Borrowed(_)));
+        assert!(is_match!(found[2], Cow::Owned(_)));
+
+        let owned : Tokens<'static> = tokens.clone().into_owned();
+        assert_eq!(owned, tokens);
+        assert!(idents(&owned).iter().all(|i| is_match!(*i, Cow::Owned(_))));
+    }
+
+    #[test]
+    fn test_display() {
+        let queries = [
+            "a.[1].b",
+            "a.[-2].[+]",
+            r#"deps.*.{version,path}.[?(@.optional == true)]"#,
+            r#""weird.key"."*".b"#,
+        ];
+
+        for query in queries.iter() {
+            let tokens = tokenize_with_seperator(query, '.').unwrap();
+            assert_eq!(tokens.to_string(), *query);
+            assert_eq!(tokenize_with_seperator(&tokens.to_string(), '.').unwrap(), tokens);
+        }
+
+        let tokens = tokenize_with_seperator("a/b.c/[last]", '/').unwrap();
+        assert_eq!(tokens.to_string(), r#"a."b.c".[-1]"#);
     }
 
     #[test]
-    fn test_pop_last_chain() {
-        let tokens = tokenize_with_seperator(&String::from("a.[100].c.[3].e.f.[1000]"), '.');
+    fn test_pop_chain() {
+        let tokens = tokenize_with_seperator("a.[100].c.[3].e.f.[1000]", '.');
         assert!(tokens.is_ok());
         let mut tokens = tokens.unwrap();
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!(1000, last.unwrap().idx());
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!("f", last.unwrap().identifier());
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!("e", last.unwrap().identifier());
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!(3, last.unwrap().idx());
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!("c", last.unwrap().identifier());
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
         assert!(last.is_some());
         assert_eq!(100, last.unwrap().idx());
 
-        let last = tokens.pop_last();
+        let last = tokens.pop();
+        assert!(last.is_some());
+        assert_eq!("a", last.unwrap().identifier());
+
+        let last = tokens.pop();
         assert!(last.is_none());
     }
 
+    #[test]
+    fn test_max_depth() {
+        let query = vec!["a"; 2000].join(".");
+        let res   = tokenize_with_seperator(&query, '.');
+        assert!(is_match!(res, Err(Error::QueryTooDeep(2000, DEFAULT_MAX_DEPTH))));
+        assert_eq!(tokenize_with_max_depth(&query, '.', 2000).unwrap().len(), 2000);
+
+        let res = tokenize_with_max_depth("a.b.c", '.', 2);
+        assert!(is_match!(res, Err(Error::QueryTooDeep(3, 2))));
+    }
+
+    #[test]
+    fn test_tokenize_bracketed_keys() {
+        let expected = tokenize_with_seperator(r#"a."b.c".d.[0]"#, '.').unwrap();
+        let queries  = [
+            r#"a["b.c"].d.[0]"#,
+            r#"a['b.c'].d.[0]"#,
+            r#"a["b.c"]["d"][0]"#,
+            r#"["a"]["b.c"].d.[0]"#,
+            r#"a.["b.c"].d.[0]"#,
+        ];
+        for query in queries.iter() {
+            assert_eq!(tokenize_with_seperator(query, '.').unwrap(), expected, "{}", query);
+        }
+
+        let tokens = tokenize_with_seperator(r#"a.b["c"].[0]"#, '.').unwrap();
+        assert_eq!(tokens.to_string(), "a.b.c.[0]");
+        assert!(is_match!(tokens[2], Token::Identifier { ident: Cow::Borrowed("c") }));
+
+        let tokens = tokenize_with_seperator(r#"a/b["c/d"]/e"#, '/').unwrap();
+        assert_eq!(tokens[2].identifier(), "c/d");
+
+        let tokens = tokenize_with_seperator(r#"a.[?(@.b == 1)]["c"]"#, '.').unwrap();
+        assert!(is_match!(tokens[1], Token::Filter { .. }));
+        assert_eq!(tokens[2].identifier(), "c");
+
+        // brackets without quotes are still part of the key
+        let tokens = tokenize_with_seperator("a[0]", '.').unwrap();
+        assert_eq!(tokens[0].identifier(), "a[0]");
+
+        for query in &[r#"a["b"#, r#"a["b"c]"#, r#"a["b"]c"#, r#"a["b"."#] {
+            let res = tokenize_with_seperator(query, '.');
+            assert!(is_match!(res, Err(Error::QueryParsingError(_))), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_tokenize_with_spans() {
+        let query = r#"a."b.c"["d"].[0].é"#;
+        let (tokens, spans) = tokenize_with_spans(query, '.').unwrap();
+        assert_eq!(tokens.len(), spans.len());
+
+        let segments : Vec<&str> = spans.into_iter().map(|span| &query[span]).collect();
+        assert_eq!(segments, [r#"a"#, r#""b.c""#, r#"["d"]"#, "[0]", "é"]);
+    }
+
+    #[test]
+    fn test_basic_syntax() {
+        let tokens = tokenize_with_syntax("a.b.[1]", '.', Syntax::V1Basic).unwrap();
+        assert_eq!(tokens, tokenize_with_seperator("a.b.[1]", '.').unwrap());
+
+        let extended = [
+            ("a.*", "*"),
+            ("a.{b,c}", "{b,c}"),
+            ("a.[?(@.b == 1)]", "[?(@.b == 1)]"),
+            ("a.[-1]", "[-1]"),
+            ("a.[last]", "[-1]"),
+            ("a.[+]", "[+]"),
+            (r#"a."b""#, "b"),
+            (r#"a."b.c""#, r#""b.c""#),
+            (r"a\.b", r#""a.b""#),
+            (r#"a["b"]"#, "b"),
+        ];
+        for &(query, segment) in extended.iter() {
+            match tokenize_with_syntax(query, '.', Syntax::V1Basic) {
+                Err(Error::ExtendedSyntax(ref s)) => assert_eq!(s, segment, "{}", query),
+                other                             => panic!("Unexpected result for {}: {:?}", query, other),
+            }
+            assert!(tokenize_with_syntax(query, '.', Syntax::V2Extended).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_tokens_slice() {
+        let tokens = tokenize_with_seperator("a.[1].*.b", '.').unwrap();
+        assert_eq!(tokens.len(), 4);
+
+        let (last, parents) = tokens.split_last().unwrap();
+        assert_eq!("b", last.identifier());
+        assert_eq!(parents.len(), 3);
+        assert_eq!(1, tokens[1..].first().unwrap().idx());
+
+        let rendered : Vec<String> = tokens.iter().map(Token::to_string).collect();
+        assert_eq!(rendered, ["a", "[1]", "*", "b"]);
+
+        let collected : Tokens = tokens.clone().into_iter().collect();
+        assert_eq!(collected, tokens);
+    }
+
 }
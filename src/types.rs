@@ -0,0 +1,82 @@
+/// The types of values in TOML documents
+
+use toml::Value;
+
+/// The type of a value in a TOML document
+///
+/// Used wherever a query comes with an expectation on the type of the value it points to.
+/// Available as `toml_query::Type` and, for compatibility, as `toml_query::read::Type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Datetime,
+    Array,
+    Table,
+}
+
+impl Type {
+
+    /// Check whether `value` is of this type
+    pub fn matches(&self, value: &Value) -> bool {
+        Type::from(value) == *self
+    }
+
+    /// The name of the type, as used in error messages
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Type::String   => "String",
+            Type::Integer  => "Integer",
+            Type::Float    => "Float",
+            Type::Boolean  => "Boolean",
+            Type::Datetime => "Datetime",
+            Type::Array    => "Array",
+            Type::Table    => "Table",
+        }
+    }
+
+}
+
+impl<'a> From<&'a Value> for Type {
+    fn from(value: &'a Value) -> Type {
+        match *value {
+            Value::String(_)   => Type::String,
+            Value::Integer(_)  => Type::Integer,
+            Value::Float(_)    => Type::Float,
+            Value::Boolean(_)  => Type::Boolean,
+            Value::Datetime(_) => Type::Datetime,
+            Value::Array(_)    => Type::Array,
+            Value::Table(_)    => Type::Table,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    #[test]
+    fn test_from_value() {
+        let doc : Value = toml_from_str(r#"
+        s = "s"
+        i = 1
+        a = [ 1 ]
+        "#).unwrap();
+
+        assert_eq!(Type::from(&doc), Type::Table);
+        assert_eq!(Type::from(&doc["s"]), Type::String);
+        assert_eq!(Type::from(&doc["i"]), Type::Integer);
+        assert_eq!(Type::from(&doc["a"]), Type::Array);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(Type::Integer.matches(&Value::Integer(1)));
+        assert!(!Type::Integer.matches(&Value::Float(1.0)));
+        assert_eq!(Type::from(&Value::Boolean(true)).name(), "Boolean");
+    }
+
+}
@@ -1,14 +1,59 @@
 use toml::Value;
 
+use error::{Error, Result};
+use path::TomlPath;
+use types::Type;
+
 pub fn name_of_val(val: &Value) -> &'static str {
-    match *val {
-        Value::Array(_)    => "Array",
-        Value::Boolean(_)  => "Boolean",
-        Value::Datetime(_) => "Datetime",
-        Value::Float(_)    => "Float",
-        Value::Integer(_)  => "Integer",
-        Value::String(_)   => "String",
-        Value::Table(_)    => "Table",
+    Type::from(val).name()
+}
+
+/// Log the outcome of a public operation on a document
+///
+/// Successful operations are logged with `trace` level, failed ones with `debug` level. The
+/// operation, the query and the error are passed as structured fields.
+#[cfg(feature = "log")]
+pub fn log_outcome<T>(operation: &'static str, query: &str, result: &Result<T>) {
+    match *result {
+        Ok(_) => {
+            trace!(target: "toml_query", operation = operation, query = query;
+                   "{} '{}' succeeded", operation, query);
+        },
+        Err(ref e) => {
+            debug!(target: "toml_query", operation = operation, query = query, error:% = e;
+                   "{} '{}' failed: {}", operation, query, e);
+        },
     }
 }
 
+#[cfg(not(feature = "log"))]
+#[inline(always)]
+pub fn log_outcome<T>(_: &'static str, _: &str, _: &Result<T>) { }
+
+/// Log a read of the deprecated path `old`, which was resolved to `new`
+#[cfg(feature = "log")]
+pub fn log_deprecated(old: &TomlPath, new: &TomlPath, message: &str) {
+    warn!(target: "toml_query", old:% = old, new:% = new;
+          "'{}' is deprecated, reading '{}' instead: {}", old, new, message);
+}
+
+#[cfg(not(feature = "log"))]
+#[inline(always)]
+pub fn log_deprecated(_: &TomlPath, _: &TomlPath, _: &str) { }
+
+/// Fail if writing `new` over `old` at `query` would replace a non-empty table with another
+/// kind of value, or any other value with a table
+pub fn check_shadowing(query: &str, old: Option<&Value>, new: &Value) -> Result<()> {
+    let shadows = match (old, new) {
+        (Some(&Value::Table(ref t)), new) => !t.is_empty() && !is_match!(*new, Value::Table(_)),
+        (Some(_), &Value::Table(_))       => true,
+        _                                 => false,
+    };
+
+    if shadows {
+        let old = old.map(name_of_val).unwrap_or("None");
+        Err(Error::WouldShadowTable(String::from(query), old, name_of_val(new)))
+    } else {
+        Ok(())
+    }
+}
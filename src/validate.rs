@@ -0,0 +1,829 @@
+/// Constraints on values in a TOML document
+///
+/// A `Validator` describes a constraint on a single value. Validators can be combined with
+/// `Validator::All` and `Validator::Any`, used on their own via
+/// `TomlValueValidateExt::validate_value()`, or attached to a query in a `Schema`.
+///
+/// `TomlValueValidateExt::check_all()` checks many queries for presence and type at once, and
+/// `TomlValueConformExt::conforms_to()` checks a whole document against an expected document.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use regex::Regex;
+use toml::Value;
+use toml::value::Datetime;
+
+use read::TomlValueReadExt;
+use types::Type;
+use path::{Segment, TomlPath};
+use error::{Error, Result};
+
+#[derive(Debug, Clone)]
+pub enum Validator {
+    /// Integer (or Float) value must be inside the range
+    Range(RangeInclusive<i64>),
+
+    /// String value must match the regex
+    Regex(Regex),
+
+    /// Value must be equal to one of the values
+    OneOf(Vec<Value>),
+
+    /// String, Array or Table must not be empty. Other values always satisfy this.
+    NonEmpty,
+
+    /// All of the validators must be satisfied
+    All(Vec<Validator>),
+
+    /// At least one of the validators must be satisfied
+    Any(Vec<Validator>),
+
+    /// Datetime value must be a date without a time, like `1979-05-27`
+    DateOnly,
+
+    /// Datetime value must have a date, a time and an offset, like `1979-05-27T07:32:00Z`
+    WithOffset,
+
+    /// Datetime value must be inside the range
+    ///
+    /// The value must be of the same kind as the bounds: a date, a time, a local datetime or a
+    /// datetime with offset. Datetimes with offsets are compared as points in time, so
+    /// `07:32:00Z` and `09:32:00+02:00` on the same day are equal.
+    DatetimeRange(Datetime, Datetime),
+}
+
+impl Validator {
+
+    /// Convenience constructor for `Validator::Regex`
+    ///
+    /// # Return value
+    ///
+    /// * `Err(Error::RegexParsingError(_, _))` if `re` is not a valid regex
+    pub fn regex(re: &str) -> Result<Validator> {
+        Regex::new(re)
+            .map(Validator::Regex)
+            .map_err(|e| Error::RegexParsingError(String::from(re), e.to_string()))
+    }
+
+    /// Convenience constructor for `Validator::OneOf` with string values
+    pub fn one_of_strings(strings: &[&str]) -> Validator {
+        Validator::OneOf(strings.iter().map(|s| Value::String(String::from(*s))).collect())
+    }
+
+    /// Check whether `value` satisfies this constraint
+    pub fn is_satisfied_by(&self, value: &Value) -> bool {
+        match *self {
+            Validator::Range(ref range) => match *value {
+                Value::Integer(i) => *range.start() <= i && i <= *range.end(),
+                Value::Float(f)   => *range.start() as f64 <= f && f <= *range.end() as f64,
+                _                 => false,
+            },
+            Validator::Regex(ref re) => match *value {
+                Value::String(ref s) => re.is_match(s),
+                _                    => false,
+            },
+            Validator::OneOf(ref values) => values.iter().any(|v| v == value),
+            Validator::NonEmpty => match *value {
+                Value::String(ref s) => !s.is_empty(),
+                Value::Array(ref a)  => !a.is_empty(),
+                Value::Table(ref t)  => !t.is_empty(),
+                _                    => true,
+            },
+            Validator::All(ref vs) => vs.iter().all(|v| v.is_satisfied_by(value)),
+            Validator::Any(ref vs) => vs.iter().any(|v| v.is_satisfied_by(value)),
+            Validator::DateOnly | Validator::WithOffset | Validator::DatetimeRange(_, _) => {
+                let moment = match *value {
+                    Value::Datetime(ref dt) => match Moment::parse(&dt.to_string()) {
+                        Some(moment) => moment,
+                        None         => return false,
+                    },
+                    _ => return false,
+                };
+
+                match *self {
+                    Validator::DateOnly   => moment.date.is_some() && moment.time.is_none(),
+                    Validator::WithOffset => moment.date.is_some() && moment.offset.is_some(),
+                    Validator::DatetimeRange(ref start, ref end) => {
+                        match (Moment::parse(&start.to_string()), Moment::parse(&end.to_string())) {
+                            (Some(start), Some(end)) => {
+                                moment.same_kind(&start) && moment.same_kind(&end)
+                                    && start.key() <= moment.key() && moment.key() <= end.key()
+                            },
+                            _ => false,
+                        }
+                    },
+                    _ => unreachable!(),
+                }
+            },
+        }
+    }
+
+    /// Check `value`, returning a `ValidationFailed` error which names `query` if the constraint
+    /// is not satisfied
+    pub fn check(&self, query: &str, value: &Value) -> Result<()> {
+        if self.is_satisfied_by(value) {
+            Ok(())
+        } else {
+            Err(Error::ValidationFailed(String::from(query), self.to_string()))
+        }
+    }
+
+}
+
+impl fmt::Display for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn join(f: &mut fmt::Formatter, vs: &[Validator], sep: &str) -> fmt::Result {
+            for (i, v) in vs.iter().enumerate() {
+                if i != 0 {
+                    write!(f, "{}", sep)?;
+                }
+                write!(f, "({})", v)?;
+            }
+            Ok(())
+        }
+
+        match *self {
+            Validator::Range(ref r)   => write!(f, "must be in range {}..={}", r.start(), r.end()),
+            Validator::Regex(ref re)  => write!(f, "must match '{}'", re.as_str()),
+            Validator::OneOf(ref vs)  => {
+                write!(f, "must be one of ")?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                Ok(())
+            },
+            Validator::NonEmpty       => write!(f, "must not be empty"),
+            Validator::All(ref vs)    => join(f, vs, " and "),
+            Validator::Any(ref vs)    => join(f, vs, " or "),
+            Validator::DateOnly       => write!(f, "must be a date without time"),
+            Validator::WithOffset     => write!(f, "must be a datetime with offset"),
+            Validator::DatetimeRange(ref start, ref end) => write!(f, "must be in range {}..={}", start, end),
+        }
+    }
+}
+
+/// The parts of a `Datetime`, which does not expose them
+struct Moment {
+    /// Days since 1970-01-01
+    date: Option<i64>,
+
+    /// Seconds since midnight and nanoseconds
+    time: Option<(i64, u32)>,
+
+    /// Offset from UTC in seconds
+    offset: Option<i64>,
+}
+
+impl Moment {
+
+    /// Parse a datetime as written in TOML and as displayed by `Datetime`
+    ///
+    /// This is `YYYY-MM-DD`, `HH:MM:SS[.fraction]` or both joined by `T` or a space, followed by
+    /// `Z` or `+HH:MM` if there is an offset. `None` if `text` is not such a datetime.
+    fn parse(text: &str) -> Option<Moment> {
+        let mut p = DatetimeParser { rest: text };
+
+        let date = if text.get(4..5) == Some("-") {
+            let year = p.number(4)?;
+            p.expect('-')?;
+            let month = p.number(2).filter(|m| 1 <= *m && *m <= 12)?;
+            p.expect('-')?;
+            let day = p.number(2).filter(|d| 1 <= *d && *d <= days_in_month(year, month))?;
+            Some(days_from_civil(year, month, day))
+        } else {
+            None
+        };
+
+        let has_time = date.is_none() || p.eat('T') || p.eat('t') || p.eat(' ');
+        let time = if has_time {
+            let hour = p.number(2).filter(|h| *h < 24)?;
+            p.expect(':')?;
+            let minute = p.number(2).filter(|m| *m < 60)?;
+            p.expect(':')?;
+            let second = p.number(2).filter(|s| *s <= 60)?;
+
+            let mut nanos = 0;
+            if p.eat('.') {
+                let digits = p.digits();
+                if digits.is_empty() {
+                    return None;
+                }
+                let fraction = format!("{:0<9}", digits);
+                nanos = fraction[..9].parse().ok()?;
+            }
+            Some((hour * 3600 + minute * 60 + second, nanos))
+        } else {
+            None
+        };
+
+        let offset = if time.is_none() || p.rest.is_empty() {
+            None
+        } else if p.eat('Z') || p.eat('z') {
+            Some(0)
+        } else {
+            let negative = if p.eat('-') { true } else { p.expect('+')?; false };
+            let hours = p.number(2).filter(|h| *h < 24)?;
+            p.expect(':')?;
+            let minutes = p.number(2).filter(|m| *m < 60)?;
+            let seconds = hours * 3600 + minutes * 60;
+            Some(if negative { -seconds } else { seconds })
+        };
+
+        if !p.rest.is_empty() {
+            return None;
+        }
+
+        Some(Moment { date, time, offset })
+    }
+
+    fn same_kind(&self, other: &Moment) -> bool {
+        self.date.is_some() == other.date.is_some()
+            && self.time.is_some() == other.time.is_some()
+            && self.offset.is_some() == other.offset.is_some()
+    }
+
+    /// A key which orders moments of the same kind
+    fn key(&self) -> (i64, u32) {
+        let (seconds, nanos) = self.time.unwrap_or((0, 0));
+        (self.date.unwrap_or(0) * 86400 + seconds - self.offset.unwrap_or(0), nanos)
+    }
+
+}
+
+/// A cursor over the text of a datetime
+struct DatetimeParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> DatetimeParser<'a> {
+
+    /// Consume `c`, if it is next
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest.starts_with(c) {
+            self.rest = &self.rest[c.len_utf8()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        if self.eat(c) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consume all ASCII digits at the start
+    fn digits(&mut self) -> &'a str {
+        let end    = self.rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest.len());
+        let digits = &self.rest[..end];
+        self.rest  = &self.rest[end..];
+        digits
+    }
+
+    /// Consume a number of exactly `len` digits
+    fn number(&mut self, len: usize) -> Option<i64> {
+        let digits = self.rest.get(..len).filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+        self.rest  = &self.rest[len..];
+        digits.parse().ok()
+    }
+
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2              => 28,
+        4 | 6 | 9 | 11 => 30,
+        _              => 31,
+    }
+}
+
+/// The number of days between 1970-01-01 and the date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era  = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe  = year - era * 400;
+    let doy  = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe  = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The result of checking many expectations on a document at once
+///
+/// The `Display` implementation lists all failures, one per line.
+#[derive(Debug, Default)]
+pub struct Report {
+    failures: Vec<Error>,
+}
+
+impl Report {
+
+    /// Whether all expectations were met
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn failures(&self) -> &[Error] {
+        &self.failures
+    }
+
+    pub fn into_failures(self) -> Vec<Error> {
+        self.failures
+    }
+
+}
+
+impl From<Vec<Error>> for Report {
+    fn from(failures: Vec<Error>) -> Report {
+        Report { failures }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "No problems found");
+        }
+
+        write!(f, "{} problem(s) found:", self.failures.len())?;
+        for failure in self.failures.iter() {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait TomlValueValidateExt<'doc> : TomlValueReadExt<'doc> {
+
+    /// Check that every query in `expectations` points to a value of the paired type
+    ///
+    /// All expectations are checked, failures do not stop the check. Missing values are reported
+    /// as `Error::NotAvailable`, values of the wrong type as `Error::TypeErrorAt`, and queries
+    /// which cannot be resolved with the error from resolving them.
+    fn check_all(&'doc self, expectations: &[(&str, Type)]) -> Report {
+        let failures = expectations
+            .iter()
+            .filter_map(|&(query, kind)| match self.read(query) {
+                Ok(Some(value)) => if kind.matches(value) {
+                    None
+                } else {
+                    let found = ::util::name_of_val(value);
+                    Some(Error::TypeErrorAt(String::from(query), kind.name(), found))
+                },
+                Ok(None) => Some(Error::NotAvailable(String::from(query))),
+                Err(e)   => Some(e),
+            })
+            .collect();
+
+        Report { failures }
+    }
+
+    /// Check the value at `query` against `validator`
+    ///
+    /// # Return value
+    ///
+    /// * `Ok(())` if the value satisfies the validator
+    /// * `Err(Error::ValidationFailed(_, _))` if it does not
+    /// * `Err(Error::NotAvailable(_))` if there is no value at `query`
+    fn validate_value(&'doc self, query: &str, validator: &Validator) -> Result<()> {
+        match self.read(query)? {
+            Some(value) => validator.check(query, value),
+            None        => Err(Error::NotAvailable(String::from(query))),
+        }
+    }
+
+    /// Check whether the value at `query` has the shape of `pattern`
+    ///
+    /// Strings in `pattern` can be placeholders: `"$any"` matches every value, and `"$string"`,
+    /// `"$int"`, `"$float"`, `"$bool"`, `"$datetime"`, `"$array"` and `"$table"` match every value
+    /// of that type. Other values must be equal.
+    ///
+    /// Every key of a table in `pattern` must be present, other keys are allowed. An array in
+    /// `pattern` with a single element matches arrays whose elements all match that element,
+    /// other arrays must match element by element.
+    ///
+    /// Returns `Ok(false)` if there is no value at `query`.
+    fn matches_shape(&'doc self, query: &str, pattern: &Value) -> Result<bool> {
+        Ok(self.read(query)?.map_or(false, |value| has_shape(value, pattern)))
+    }
+
+}
+
+impl<'doc, T> TomlValueValidateExt<'doc> for T
+    where T: TomlValueReadExt<'doc>
+{ }
+
+/// Options for `TomlValueConformExt::conforms_to()`
+#[derive(Debug, Clone, Copy)]
+pub struct ConformanceOptions {
+    allow_missing: bool,
+    allow_extra: bool,
+    placeholders: bool,
+}
+
+impl ConformanceOptions {
+
+    /// Missing values are allowed, extra values are reported and placeholders are recognized
+    pub fn new() -> ConformanceOptions {
+        ConformanceOptions {
+            allow_missing: true,
+            allow_extra: false,
+            placeholders: true,
+        }
+    }
+
+    /// Whether values of the expected document may be missing in the checked document
+    pub fn allow_missing(mut self, allow: bool) -> ConformanceOptions {
+        self.allow_missing = allow;
+        self
+    }
+
+    /// Whether the checked document may contain values which are not in the expected document
+    pub fn allow_extra(mut self, allow: bool) -> ConformanceOptions {
+        self.allow_extra = allow;
+        self
+    }
+
+    /// Whether strings like `"<integer>"` in the expected document match any value of that type
+    pub fn placeholders(mut self, enabled: bool) -> ConformanceOptions {
+        self.placeholders = enabled;
+        self
+    }
+
+}
+
+impl Default for ConformanceOptions {
+    fn default() -> ConformanceOptions {
+        ConformanceOptions::new()
+    }
+}
+
+pub trait TomlValueConformExt {
+
+    /// Check this document against `expected`, which acts as a pattern
+    ///
+    /// Tables are compared key by key and arrays index by index. Other values must be equal,
+    /// unless the expected value is a placeholder: `"<any>"` matches every value, and the
+    /// lowercase name of a type (`"<string>"`, `"<integer>"`, `"<float>"`, `"<boolean>"`,
+    /// `"<datetime>"`, `"<array>"`, `"<table>"`) matches every value of that type.
+    ///
+    /// All mismatches are reported, with the path to the value:
+    ///
+    /// * `Error::NotAvailable` for missing values, unless they are allowed
+    /// * `Error::ExtraValue` for values which are not expected, unless they are allowed
+    /// * `Error::TypeErrorAt` for values of the wrong type
+    /// * `Error::ValueMismatch` for values of the right type which are not equal
+    fn conforms_to(&self, expected: &Value, options: ConformanceOptions) -> Report;
+
+}
+
+impl TomlValueConformExt for Value {
+
+    fn conforms_to(&self, expected: &Value, options: ConformanceOptions) -> Report {
+        let mut failures = Vec::new();
+        conform(self, expected, &options, &mut TomlPath::new(), &mut failures);
+        Report { failures }
+    }
+
+}
+
+/// The type a placeholder string stands for, `Some(None)` for `"<any>"`
+fn placeholder(s: &str) -> Option<Option<Type>> {
+    let kind = match s {
+        "<any>"      => return Some(None),
+        "<string>"   => Type::String,
+        "<integer>"  => Type::Integer,
+        "<float>"    => Type::Float,
+        "<boolean>"  => Type::Boolean,
+        "<datetime>" => Type::Datetime,
+        "<array>"    => Type::Array,
+        "<table>"    => Type::Table,
+        _            => return None,
+    };
+    Some(Some(kind))
+}
+
+fn has_shape(value: &Value, pattern: &Value) -> bool {
+    let kind = match *pattern {
+        Value::String(ref s) => match s.as_str() {
+            "$any"      => return true,
+            "$string"   => Some(Type::String),
+            "$int"      => Some(Type::Integer),
+            "$float"    => Some(Type::Float),
+            "$bool"     => Some(Type::Boolean),
+            "$datetime" => Some(Type::Datetime),
+            "$array"    => Some(Type::Array),
+            "$table"    => Some(Type::Table),
+            _           => None,
+        },
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        return kind.matches(value);
+    }
+
+    match (value, pattern) {
+        (&Value::Table(ref t), &Value::Table(ref p)) => {
+            p.iter().all(|(key, sub)| t.get(key).map_or(false, |v| has_shape(v, sub)))
+        },
+        (&Value::Array(ref a), &Value::Array(ref p)) if p.len() == 1 => a.iter().all(|v| has_shape(v, &p[0])),
+        (&Value::Array(ref a), &Value::Array(ref p)) => {
+            a.len() == p.len() && a.iter().zip(p.iter()).all(|(v, sub)| has_shape(v, sub))
+        },
+        _ => value == pattern,
+    }
+}
+
+fn conform(actual: &Value, expected: &Value, options: &ConformanceOptions, path: &mut TomlPath, failures: &mut Vec<Error>) {
+    if options.placeholders {
+        if let Value::String(ref s) = *expected {
+            match placeholder(s) {
+                Some(Some(kind)) => {
+                    if !kind.matches(actual) {
+                        failures.push(Error::TypeErrorAt(path.to_string(), kind.name(), ::util::name_of_val(actual)));
+                    }
+                    return;
+                },
+                Some(None) => return,
+                None       => { },
+            }
+        }
+    }
+
+    let mut entry = |actual: Option<&Value>, expected: Option<&Value>, path: &mut TomlPath| {
+        match (actual, expected) {
+            (Some(a), Some(e))                       => conform(a, e, options, path, failures),
+            (None, Some(_)) if !options.allow_missing => failures.push(Error::NotAvailable(path.to_string())),
+            (Some(_), None) if !options.allow_extra   => failures.push(Error::ExtraValue(path.to_string())),
+            _                                        => { },
+        }
+    };
+
+    match (actual, expected) {
+        (&Value::Table(ref a), &Value::Table(ref e)) => {
+            let extra = a.keys().filter(|k| !e.contains_key(*k));
+            for key in e.keys().chain(extra) {
+                path.push(Segment::Key(key.clone()));
+                entry(a.get(key), e.get(key), path);
+                let _ = path.pop();
+            }
+        },
+
+        (&Value::Array(ref a), &Value::Array(ref e)) => {
+            for idx in 0..::std::cmp::max(a.len(), e.len()) {
+                path.push(Segment::Index(idx));
+                entry(a.get(idx), e.get(idx), path);
+                let _ = path.pop();
+            }
+        },
+
+        _ => if Type::from(actual) != Type::from(expected) {
+            let kind = Type::from(expected).name();
+            failures.push(Error::TypeErrorAt(path.to_string(), kind, ::util::name_of_val(actual)));
+        } else if actual != expected {
+            failures.push(Error::ValueMismatch(path.to_string(), expected.to_string(), actual.to_string()));
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Value {
+        toml_from_str(r#"
+        name = "server"
+        level = "debug"
+        port = 80
+        ratio = 0.5
+        empty = ""
+        list = [ 1 ]
+        "#).unwrap()
+    }
+
+    #[test]
+    fn test_range() {
+        let doc = doc();
+        assert!(doc.validate_value("port", &Validator::Range(1..=65535)).is_ok());
+        assert!(doc.validate_value("ratio", &Validator::Range(0..=1)).is_ok());
+
+        let res = doc.validate_value("port", &Validator::Range(1024..=65535));
+        assert!(is_match!(res, Err(Error::ValidationFailed(_, _))));
+        assert!(doc.validate_value("name", &Validator::Range(1..=65535)).is_err());
+    }
+
+    #[test]
+    fn test_regex() {
+        let doc = doc();
+        assert!(doc.validate_value("name", &Validator::regex("^[a-z]+$").unwrap()).is_ok());
+        assert!(doc.validate_value("name", &Validator::regex("^[0-9]+$").unwrap()).is_err());
+        assert!(doc.validate_value("port", &Validator::regex(".*").unwrap()).is_err());
+
+        let res = Validator::regex("([a-z]");
+        assert!(is_match!(res, Err(Error::RegexParsingError(ref re, _)) if re == "([a-z]"));
+    }
+
+    #[test]
+    fn test_one_of() {
+        let doc = doc();
+        let levels = Validator::one_of_strings(&["debug", "info"]);
+        assert!(doc.validate_value("level", &levels).is_ok());
+        assert!(doc.validate_value("name", &levels).is_err());
+    }
+
+    #[test]
+    fn test_non_empty() {
+        let doc = doc();
+        assert!(doc.validate_value("name", &Validator::NonEmpty).is_ok());
+        assert!(doc.validate_value("list", &Validator::NonEmpty).is_ok());
+        assert!(doc.validate_value("port", &Validator::NonEmpty).is_ok());
+        assert!(doc.validate_value("empty", &Validator::NonEmpty).is_err());
+    }
+
+    #[test]
+    fn test_composition() {
+        let doc = doc();
+        let v = Validator::All(vec![Validator::NonEmpty, Validator::regex("^s").unwrap()]);
+        assert!(doc.validate_value("name", &v).is_ok());
+        assert!(doc.validate_value("level", &v).is_err());
+
+        let v = Validator::Any(vec![Validator::Range(1..=10), Validator::Range(80..=80)]);
+        assert!(doc.validate_value("port", &v).is_ok());
+    }
+
+    #[test]
+    fn test_datetime() {
+        let doc : Value = toml_from_str(r#"
+        date = 1979-05-27
+        time = 07:32:00
+        local = 1979-05-27T07:32:00.5
+        utc = 1979-05-27T07:32:00Z
+        shifted = 1979-05-27T09:32:00+02:00
+        "#).unwrap();
+
+        assert!(doc.validate_value("date", &Validator::DateOnly).is_ok());
+        assert!(doc.validate_value("local", &Validator::DateOnly).is_err());
+        assert!(doc.validate_value("utc", &Validator::WithOffset).is_ok());
+        assert!(doc.validate_value("local", &Validator::WithOffset).is_err());
+        assert!(doc.validate_value("time", &Validator::WithOffset).is_err());
+
+        let range = |start: &str, end: &str| {
+            Validator::DatetimeRange(start.parse().unwrap(), end.parse().unwrap())
+        };
+        assert!(doc.validate_value("date", &range("1979-01-01", "1979-12-31")).is_ok());
+        assert!(doc.validate_value("date", &range("1980-01-01", "1980-12-31")).is_err());
+        assert!(doc.validate_value("time", &range("07:00:00", "08:00:00")).is_ok());
+        assert!(doc.validate_value("local", &range("1979-05-27T07:32:00.4", "1979-05-27T07:32:00.6")).is_ok());
+        assert!(doc.validate_value("local", &range("1979-05-27T07:32:01", "1979-05-28T00:00:00")).is_err());
+
+        let utc = range("1979-05-27T07:32:00Z", "1979-05-27T07:32:00Z");
+        assert!(doc.validate_value("utc", &utc).is_ok());
+        assert!(doc.validate_value("shifted", &utc).is_ok());
+        assert!(doc.validate_value("local", &utc).is_err());
+        assert!(doc.validate_value("date", &range("1979-05-27T00:00:00", "1979-05-28T00:00:00")).is_err());
+
+        let err = doc.validate_value("local", &Validator::DateOnly).unwrap_err();
+        assert_eq!(err.to_string(), "Value at 'local' does not satisfy constraint: must be a date without time");
+    }
+
+    #[test]
+    fn test_parse_moment() {
+        let moment = Moment::parse("1970-01-02T00:00:01.25-01:30").unwrap();
+        assert_eq!((moment.date, moment.time, moment.offset), (Some(1), Some((1, 250_000_000)), Some(-5400)));
+
+        let moment = Moment::parse("07:32:00").unwrap();
+        assert_eq!((moment.date, moment.time, moment.offset), (None, Some((27120, 0)), None));
+
+        assert!(Moment::parse("2000-02-29").is_some());
+        assert!(Moment::parse("1900-02-29").is_none());
+        assert!(Moment::parse("1979-13-01").is_none());
+        assert!(Moment::parse("1979-05-27T25:00:00").is_none());
+        assert!(Moment::parse("1979-05-27T07:32").is_none());
+        assert!(Moment::parse("07:32:00.").is_none());
+        assert!(Moment::parse("1979-05-27Z").is_none());
+        assert!(Moment::parse("1979-05-27T07:32:00+0200").is_none());
+    }
+
+    #[test]
+    fn test_missing_value() {
+        let doc = doc();
+        let res = doc.validate_value("nothing", &Validator::NonEmpty);
+        assert!(is_match!(res, Err(Error::NotAvailable(_))));
+    }
+
+    #[test]
+    fn test_error_message() {
+        let doc = doc();
+        let err = doc.validate_value("port", &Validator::Range(1024..=65535)).unwrap_err();
+        assert_eq!(err.to_string(),
+                   "Value at 'port' does not satisfy constraint: must be in range 1024..=65535");
+    }
+
+    #[test]
+    fn test_check_all() {
+        let doc = doc();
+
+        let report = doc.check_all(&[
+            ("name", Type::String),
+            ("port", Type::Integer),
+            ("ratio", Type::Float),
+        ]);
+        assert!(report.is_ok());
+
+        let report = doc.check_all(&[
+            ("name", Type::Integer),
+            ("port", Type::Integer),
+            ("missing", Type::Boolean),
+            ("port.[0]", Type::Integer),
+        ]);
+
+        assert!(!report.is_ok());
+        assert_eq!(report.failures().len(), 3);
+        assert!(is_match!(report.failures()[0], Error::TypeErrorAt(_, "Integer", "String")));
+        assert!(is_match!(report.failures()[1], Error::NotAvailable(_)));
+        assert!(is_match!(report.failures()[2], Error::QueryingValueAsArray(0)));
+
+        assert_eq!(report.to_string(), "3 problem(s) found:\n  \
+            Type Error at 'name'. Requested Integer, but got String\n  \
+            Value at 'missing' not there\n  \
+            Got an index query '0' but have value");
+    }
+
+    #[test]
+    fn test_matches_shape() {
+        let toml : Value = toml_from_str(r#"
+        name = "server"
+        ports = [ 80, 443 ]
+        pair = [ "a", "b" ]
+
+        [tls]
+        enabled = true
+        cert = "a.pem"
+        "#).unwrap();
+
+        let check = |query, pattern: &str| {
+            let pattern : Value = toml_from_str(&format!("p = {}", pattern)).unwrap();
+            toml.matches_shape(query, &pattern["p"]).unwrap()
+        };
+
+        assert!(check("tls", r#"{ enabled = "$bool" }"#));
+        assert!(check("tls", r#"{ enabled = true, cert = "$any" }"#));
+        assert!(!check("tls", r#"{ enabled = "$string" }"#));
+        assert!(!check("tls", r#"{ key = "$any" }"#));
+        assert!(check("ports", r#"[ "$int" ]"#));
+        assert!(!check("ports", r#"[ "$float" ]"#));
+        assert!(check("pair", r#"[ "a", "$string" ]"#));
+        assert!(!check("pair", r#"[ "b", "$string" ]"#));
+        assert!(!check("pair", r#"[ "a", "$string", "$any" ]"#));
+        assert!(check("name", r#""server""#));
+        assert!(check("name", r#""$any""#));
+        assert!(!check("missing", r#""$any""#));
+        assert!(toml.matches_shape("name.[0]", &Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn test_conforms_to() {
+        let expected: Value = toml_from_str(r#"
+        name = "<string>"
+        level = "info"
+        port = "<integer>"
+        ratio = 1
+        list = [ "<integer>" ]
+        other = "<any>"
+        "#).unwrap();
+
+        let report = doc().conforms_to(&expected, ConformanceOptions::new());
+        let failures = report.failures();
+        assert_eq!(failures.len(), 3);
+        assert!(is_match!(failures[0], Error::ValueMismatch(ref p, _, _) if p == "level"));
+        assert!(is_match!(failures[1], Error::TypeErrorAt(ref p, "Integer", "Float") if p == "ratio"));
+        assert!(is_match!(failures[2], Error::ExtraValue(ref p) if p == "empty"));
+        assert_eq!(failures[0].to_string(), "Value at 'level' is \"debug\", expected \"info\"");
+    }
+
+    #[test]
+    fn test_conforms_to_options() {
+        let expected: Value = toml_from_str(r#"
+        name = "<string>"
+        missing = 1
+        "#).unwrap();
+
+        let options = ConformanceOptions::new().allow_extra(true);
+        assert!(doc().conforms_to(&expected, options).is_ok());
+
+        let report = doc().conforms_to(&expected, options.allow_missing(false));
+        assert!(is_match!(report.failures(), [Error::NotAvailable(ref p)] if p == "missing"));
+
+        let report = doc().conforms_to(&expected, options.placeholders(false));
+        assert!(is_match!(report.failures(), [Error::ValueMismatch(_, _, _)]));
+    }
+
+}
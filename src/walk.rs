@@ -0,0 +1,109 @@
+/// Walking over all values in a TOML document
+///
+/// Tables are walked in sorted key order (see `Map`) and arrays in index order, depth first. Each
+/// value is visited before its children, so the order is the same for equal documents.
+
+use toml::Value;
+
+use path::{Segment, TomlPath};
+
+/// Call `f` for every value below `value`, together with its path relative to `value`
+///
+/// `value` itself is not visited. If `max_depth` is `Some(n)`, only values at most `n` levels
+/// below `value` are visited.
+pub fn walk<'doc, F>(value: &'doc Value, max_depth: Option<usize>, f: &mut F)
+    where F: FnMut(&TomlPath, &'doc Value)
+{
+    fn walk_inner<'doc, F>(value: &'doc Value, path: &mut TomlPath, max_depth: Option<usize>, f: &mut F)
+        where F: FnMut(&TomlPath, &'doc Value)
+    {
+        if max_depth.map(|d| path.len() >= d).unwrap_or(false) {
+            return;
+        }
+
+        match *value {
+            Value::Table(ref t) => for (key, sub) in t.iter() {
+                path.push(Segment::Key(key.clone()));
+                f(path, sub);
+                walk_inner(sub, path, max_depth, f);
+                let _ = path.pop();
+            },
+            Value::Array(ref a) => for (idx, sub) in a.iter().enumerate() {
+                path.push(Segment::Index(idx));
+                f(path, sub);
+                walk_inner(sub, path, max_depth, f);
+                let _ = path.pop();
+            },
+            _ => { /* Leaf, nothing to walk */ }
+        }
+    }
+
+    walk_inner(value, &mut TomlPath::new(), max_depth, f)
+}
+
+/// Collect the paths of all values below `value`, in walking order
+///
+/// See `walk()` for the meaning of `max_depth`.
+pub fn paths(value: &Value, max_depth: Option<usize>) -> Vec<TomlPath> {
+    let mut paths = Vec::new();
+    walk(value, max_depth, &mut |path, _| paths.push(path.clone()));
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use toml::from_str as toml_from_str;
+
+    fn doc() -> Value {
+        toml_from_str(r#"
+        top = 1
+
+        [a]
+        b = [ { c = 1 }, { c = 2 } ]
+
+        [a.d]
+        e = true
+        "#).unwrap()
+    }
+
+    fn strings(paths: Vec<TomlPath>) -> Vec<String> {
+        paths.into_iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_paths_unlimited() {
+        let expected = vec![
+            "a", "a.b", "a.b.[0]", "a.b.[0].c", "a.b.[1]", "a.b.[1].c", "a.d", "a.d.e", "top",
+        ];
+        assert_eq!(strings(paths(&doc(), None)), expected);
+    }
+
+    #[test]
+    fn test_paths_depth_limited() {
+        assert_eq!(strings(paths(&doc(), Some(1))), vec!["a", "top"]);
+        assert_eq!(strings(paths(&doc(), Some(2))), vec!["a", "a.b", "a.d", "top"]);
+        assert!(paths(&doc(), Some(0)).is_empty());
+    }
+
+    #[test]
+    fn test_paths_key_order() {
+        let doc : Value = toml_from_str(r#"
+        zebra = 1
+        alpha = 2
+        [mid]
+        y = 3
+        x = 4
+        "#).unwrap();
+
+        // the sorted order relies on the map type
+        let _ : ::std::collections::BTreeMap<String, Value> = ::Map::new();
+        assert_eq!(strings(paths(&doc, None)), vec!["alpha", "mid", "mid.x", "mid.y", "zebra"]);
+    }
+
+    #[test]
+    fn test_walk_scalar() {
+        assert!(paths(&Value::Integer(1), None).is_empty());
+    }
+
+}
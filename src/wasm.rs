@@ -0,0 +1,39 @@
+/// JavaScript bindings for running queries in the browser
+///
+/// This module is only available with the `wasm` feature. The crate itself has no OS
+/// dependencies and builds for `wasm32-unknown-unknown` without it, this module is a small demo
+/// of exposing the query API to JavaScript via `wasm-bindgen`.
+
+use toml::Value;
+use wasm_bindgen::prelude::*;
+
+use read::TomlValueReadExt;
+
+fn parse(document: &str) -> Result<Value, JsValue> {
+    ::toml::from_str(document).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Read the value at `query` from the TOML text `document`
+///
+/// Returns the value rendered as TOML, `undefined` if there is no value at `query`, and throws
+/// the error message if the document cannot be parsed or the query fails.
+#[wasm_bindgen]
+pub fn read(document: &str, query: &str) -> Result<Option<String>, JsValue> {
+    let document = parse(document)?;
+    document
+        .read(query)
+        .map(|o| o.map(|v| v.to_string()))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Check whether all `queries` point to a value in the TOML text `document`
+///
+/// Returns the queries which do not, throws the error message if the document cannot be parsed.
+#[wasm_bindgen]
+pub fn missing(document: &str, queries: Vec<String>) -> Result<Vec<String>, JsValue> {
+    let document = parse(document)?;
+    Ok(queries
+       .into_iter()
+       .filter(|q| document.read(q).map(|o| o.is_none()).unwrap_or(true))
+       .collect())
+}